@@ -0,0 +1,278 @@
+use crate::package::SlowPackage;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// How long to wait for an ack before resending a package.
+pub const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How many times a package is resent before its delivery is given up on.
+pub const DEFAULT_ACK_RETRIES: u32 = 3;
+
+//=============================================================================
+// AckPolicy
+//=============================================================================
+/// Controls how long acknowledged sends wait for an ack and how often they retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckPolicy {
+    /// How long to wait for an ack before resending.
+    pub timeout: Duration,
+
+    /// How many times to resend before giving up.
+    pub retries: u32,
+}
+
+impl Default for AckPolicy {
+    fn default() -> Self {
+        AckPolicy {
+            timeout: DEFAULT_ACK_TIMEOUT,
+            retries: DEFAULT_ACK_RETRIES,
+        }
+    }
+}
+
+//=============================================================================
+// AckError
+//=============================================================================
+/// The reason an acknowledged send did not complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckError {
+    /// No ack arrived after every retry.
+    TimedOut,
+    /// The junction was dropped before an ack arrived.
+    Closed,
+}
+
+impl std::fmt::Display for AckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AckError::TimedOut => write!(f, "no ack received before the retries ran out"),
+            AckError::Closed => write!(f, "junction closed before an ack was received"),
+        }
+    }
+}
+
+impl std::error::Error for AckError {}
+
+//=============================================================================
+// DeliveryHandle
+//=============================================================================
+/// A handle to an acknowledged send that resolves once the recipient acks the package.
+pub struct DeliveryHandle {
+    /// The package ID of the first transmission.
+    package_id: u32,
+
+    /// Receives the outcome of the send.
+    receiver: oneshot::Receiver<Result<(), AckError>>,
+}
+
+impl DeliveryHandle {
+    /// Returns the package ID assigned to the first transmission of the package.
+    pub fn package_id(&self) -> u32 {
+        self.package_id
+    }
+
+    /// Waits until the package is acknowledged or its retries run out.
+    ///
+    /// Retries carry the package ID of the first transmission, so an ack for any
+    /// transmission completes the send and the recipient delivers the package once.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), AckError>` - Ok once the recipient acknowledged the package.
+    pub async fn wait(self) -> Result<(), AckError> {
+        self.receiver.await.unwrap_or(Err(AckError::Closed))
+    }
+}
+
+//=============================================================================
+// PendingAck
+//=============================================================================
+/// An acknowledged send waiting for its ack.
+pub struct PendingAck {
+    /// The package, kept for retransmission.
+    package: SlowPackage,
+
+    /// The number of retransmissions so far.
+    retries: u32,
+
+    /// When the current transmission times out.
+    deadline: Instant,
+
+    /// Completes the `DeliveryHandle`.
+    sender: oneshot::Sender<Result<(), AckError>>,
+}
+
+impl PendingAck {
+    /// Returns the package waiting for an ack.
+    pub fn package(&self) -> &SlowPackage {
+        &self.package
+    }
+
+    /// Returns the package waiting for an ack for modification before a retransmission.
+    pub fn package_mut(&mut self) -> &mut SlowPackage {
+        &mut self.package
+    }
+}
+
+//=============================================================================
+// AckTracker
+//=============================================================================
+/// Tracks acknowledged sends until they are acked or give up.
+///
+/// Each send is keyed by the package ID of its first transmission, which every
+/// retransmission carries and every ack names. When a transmission times out it
+/// is handed back to the junction, which resends it and tracks it again.
+pub struct AckTracker {
+    /// Timeout and retry settings for new sends.
+    policy: AckPolicy,
+
+    /// Sends waiting for an ack, keyed by the package ID of their first transmission.
+    pending: HashMap<u32, PendingAck>,
+}
+
+impl AckTracker {
+    /// Creates a new `AckTracker`.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Timeout and retry settings.
+    pub fn new(policy: AckPolicy) -> Self {
+        AckTracker {
+            policy,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Returns the timeout and retry settings.
+    pub fn policy(&self) -> AckPolicy {
+        self.policy
+    }
+
+    /// Sets the timeout and retry settings used for new transmissions.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The new settings.
+    pub fn set_policy(&mut self, policy: AckPolicy) {
+        self.policy = policy;
+    }
+
+    /// Starts tracking the first transmission of a package.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The package, with its package ID already assigned.
+    ///
+    /// # Returns
+    ///
+    /// * `DeliveryHandle` - A handle that resolves when the package is acked.
+    pub fn track(&mut self, package: SlowPackage) -> DeliveryHandle {
+        let (sender, receiver) = oneshot::channel();
+        let package_id = package.package_id();
+        self.pending.insert(
+            package_id,
+            PendingAck {
+                package,
+                retries: 0,
+                deadline: Instant::now() + self.policy.timeout,
+                sender,
+            },
+        );
+
+        DeliveryHandle {
+            package_id,
+            receiver,
+        }
+    }
+
+    /// Completes the send whose first transmission used `package_id`.
+    ///
+    /// # Arguments
+    ///
+    /// * `package_id` - The package ID carried by the ack.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a pending send was completed, `false` if the ack was unexpected or late.
+    pub fn acknowledge(&mut self, package_id: u32) -> bool {
+        match self.pending.remove(&package_id) {
+            Some(pending) => {
+                let _ = pending.sender.send(Ok(()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stops tracking a send without completing its `DeliveryHandle`.
+    ///
+    /// # Arguments
+    ///
+    /// * `package_id` - The package ID of the first transmission.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a pending send was removed, `false` otherwise.
+    pub fn cancel(&mut self, package_id: u32) -> bool {
+        self.pending.remove(&package_id).is_some()
+    }
+
+    /// Removes the sends whose current transmission has timed out.
+    ///
+    /// Sends that have used up their retries are failed with `AckError::TimedOut`.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<PendingAck>` - The sends to retransmit and hand back with `retry`.
+    pub fn take_due(&mut self) -> Vec<PendingAck> {
+        let now = Instant::now();
+        let due: Vec<u32> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.deadline <= now)
+            .map(|(package_id, _)| *package_id)
+            .collect();
+
+        let mut retries = Vec::new();
+        for package_id in due {
+            if let Some(pending) = self.pending.remove(&package_id) {
+                if pending.retries >= self.policy.retries {
+                    let _ = pending.sender.send(Err(AckError::TimedOut));
+                } else {
+                    retries.push(pending);
+                }
+            }
+        }
+
+        retries
+    }
+
+    /// Tracks a retransmission of a send returned by `take_due`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pending` - The send, whose package carries the ID of its new transmission.
+    pub fn retry(&mut self, mut pending: PendingAck) {
+        pending.retries += 1;
+        pending.deadline = Instant::now() + self.policy.timeout;
+        self.pending
+            .insert(pending.package.first_package_id(), pending);
+    }
+
+    /// Returns the number of sends waiting for an ack.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns `true` if no sends are waiting for an ack.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+impl Default for AckTracker {
+    fn default() -> Self {
+        Self::new(AckPolicy::default())
+    }
+}
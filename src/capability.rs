@@ -0,0 +1,263 @@
+/// The newest version of the protocol spoken by this junction.
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// The oldest version of the protocol this junction still speaks.
+pub const MIN_PROTOCOL_VERSION: u16 = 2;
+
+//=============================================================================
+// Capabilities
+//=============================================================================
+/// A set of optional protocol features, one bit per feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// The empty set.
+    pub const NONE: Capabilities = Capabilities(0);
+
+    /// Payloads may be compressed.
+    pub const COMPRESSION: Capabilities = Capabilities(1 << 0);
+
+    /// Payloads may be encrypted.
+    pub const ENCRYPTION: Capabilities = Capabilities(1 << 1);
+
+    /// Packages may be split into fragments and reassembled.
+    pub const FRAGMENTATION: Capabilities = Capabilities(1 << 2);
+
+    /// Links carry heartbeat frames, and quiet links are closed.
+    pub const HEARTBEAT: Capabilities = Capabilities(1 << 3);
+
+    /// Creates a set from its bits, as sent in a handshake.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits` - One bit per feature.
+    pub fn from_bits(bits: u32) -> Self {
+        Capabilities(bits)
+    }
+
+    /// Returns the bits of the set, as sent in a handshake.
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the features this junction supports.
+    pub fn supported() -> Self {
+        Capabilities::HEARTBEAT
+    }
+
+    /// Returns `true` if every feature in `other` is in this set.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The features to look for.
+    pub fn contains(self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the features in either set.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other set.
+    pub fn union(self, other: Capabilities) -> Self {
+        Capabilities(self.0 | other.0)
+    }
+
+    /// Returns the features in both sets.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other set.
+    pub fn intersection(self, other: Capabilities) -> Self {
+        Capabilities(self.0 & other.0)
+    }
+
+    /// Returns the features in this set that are not in `other`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The features to leave out.
+    pub fn difference(self, other: Capabilities) -> Self {
+        Capabilities(self.0 & !other.0)
+    }
+
+    /// Returns `true` if the set has no features.
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+//=============================================================================
+// NegotiationError
+//=============================================================================
+/// The reason two junctions could not agree on how to talk to each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NegotiationError {
+    /// The version ranges of the two junctions do not overlap.
+    NoCommonVersion {
+        /// The oldest and newest version this junction speaks.
+        local: (u16, u16),
+        /// The oldest and newest version the peer speaks.
+        peer: (u16, u16),
+    },
+    /// The peer sent no offer, so it predates offers and speaks only the legacy protocol.
+    LegacyPeer,
+    /// A feature one junction requires is not supported by the other.
+    MissingCapabilities(Capabilities),
+    /// The peer's offer could not be read.
+    Malformed,
+}
+
+impl std::fmt::Display for NegotiationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NegotiationError::NoCommonVersion { local, peer } => write!(
+                f,
+                "no common protocol version: local speaks {}-{}, peer speaks {}-{}",
+                local.0, local.1, peer.0, peer.1
+            ),
+            NegotiationError::LegacyPeer => write!(
+                f,
+                "peer made no protocol offer, so it only speaks the legacy protocol (version {})",
+                ProtocolOffer::LEGACY.max_version
+            ),
+            NegotiationError::MissingCapabilities(missing) => write!(
+                f,
+                "required capabilities not supported: {:#x}",
+                missing.bits()
+            ),
+            NegotiationError::Malformed => write!(f, "malformed protocol offer"),
+        }
+    }
+}
+
+impl std::error::Error for NegotiationError {}
+
+//=============================================================================
+// Agreement
+//=============================================================================
+/// What two junctions settled on in a handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Agreement {
+    /// The protocol version both sides speak.
+    pub version: u16,
+
+    /// The optional features both sides support.
+    pub capabilities: Capabilities,
+}
+
+//=============================================================================
+// ProtocolOffer
+//=============================================================================
+/// The protocol versions and features a junction advertises in a handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolOffer {
+    /// The oldest version spoken.
+    pub min_version: u16,
+
+    /// The newest version spoken.
+    pub max_version: u16,
+
+    /// The optional features supported.
+    pub capabilities: Capabilities,
+
+    /// The features a peer must support to be accepted.
+    pub required: Capabilities,
+}
+
+impl ProtocolOffer {
+    /// The size of a packed offer in bytes.
+    pub const PACKED_SIZE: usize = 12;
+
+    /// The offer assumed for a peer that sent none, from before offers were added.
+    ///
+    /// Junctions no longer speak this version by default, so such peers are refused
+    /// with `NegotiationError::LegacyPeer`.
+    pub const LEGACY: ProtocolOffer = ProtocolOffer {
+        min_version: 1,
+        max_version: 1,
+        capabilities: Capabilities::NONE,
+        required: Capabilities::NONE,
+    };
+
+    /// Settles on the newest version and the features both offers support.
+    ///
+    /// Both sides of a handshake run this on the two offers and reach the same result.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer` - The offer received from the peer.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Agreement, NegotiationError>` - The agreement, or why the peer is incompatible.
+    pub fn negotiate(&self, peer: &ProtocolOffer) -> Result<Agreement, NegotiationError> {
+        let version = self.max_version.min(peer.max_version);
+        if version < self.min_version.max(peer.min_version) {
+            if *peer == ProtocolOffer::LEGACY {
+                return Err(NegotiationError::LegacyPeer);
+            }
+            return Err(NegotiationError::NoCommonVersion {
+                local: (self.min_version, self.max_version),
+                peer: (peer.min_version, peer.max_version),
+            });
+        }
+
+        let capabilities = self.capabilities.intersection(peer.capabilities);
+        let missing = self.required.union(peer.required).difference(capabilities);
+        if !missing.is_empty() {
+            return Err(NegotiationError::MissingCapabilities(missing));
+        }
+
+        Ok(Agreement {
+            version,
+            capabilities,
+        })
+    }
+
+    /// Serializes the offer.
+    ///
+    /// The format is the oldest and newest version as u16, then the supported and
+    /// required capability bits as u32, all in little-endian.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(Self::PACKED_SIZE);
+        buffer.extend_from_slice(&self.min_version.to_le_bytes());
+        buffer.extend_from_slice(&self.max_version.to_le_bytes());
+        buffer.extend_from_slice(&self.capabilities.bits().to_le_bytes());
+        buffer.extend_from_slice(&self.required.bits().to_le_bytes());
+        buffer
+    }
+
+    /// Deserializes an offer written by `pack`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A byte slice starting with the packed offer.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Self>` - The offer, or `None` if `data` is too short.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        let data = data.get(..Self::PACKED_SIZE)?;
+        Some(ProtocolOffer {
+            min_version: u16::from_le_bytes([data[0], data[1]]),
+            max_version: u16::from_le_bytes([data[2], data[3]]),
+            capabilities: Capabilities::from_bits(u32::from_le_bytes(
+                data[4..8].try_into().unwrap(),
+            )),
+            required: Capabilities::from_bits(u32::from_le_bytes(data[8..12].try_into().unwrap())),
+        })
+    }
+}
+
+impl Default for ProtocolOffer {
+    fn default() -> Self {
+        ProtocolOffer {
+            min_version: MIN_PROTOCOL_VERSION,
+            max_version: PROTOCOL_VERSION,
+            capabilities: Capabilities::supported(),
+            required: Capabilities::NONE,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::junction_id::JunctionId;
+use crate::package::{PackageType, SlowPackage};
+
+/// The default number of packages a junction holds for the application before reporting `QueueFull`.
+pub const DEFAULT_RECEIVE_QUEUE_CAPACITY: usize = 1024;
+
+//=============================================================================
+// DeliveryErrorKind
+//=============================================================================
+/// The reason a package could not be delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryErrorKind {
+    /// A junction on the path had no way to reach the recipient.
+    DestinationUnreachable,
+    /// The package was dropped after taking too many hops.
+    HopLimitExceeded,
+    /// The recipient's receive queue was full.
+    QueueFull,
+}
+
+impl From<DeliveryErrorKind> for u8 {
+    fn from(kind: DeliveryErrorKind) -> Self {
+        match kind {
+            DeliveryErrorKind::DestinationUnreachable => 0,
+            DeliveryErrorKind::HopLimitExceeded => 1,
+            DeliveryErrorKind::QueueFull => 2,
+        }
+    }
+}
+
+impl TryFrom<u8> for DeliveryErrorKind {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(DeliveryErrorKind::DestinationUnreachable),
+            1 => Ok(DeliveryErrorKind::HopLimitExceeded),
+            2 => Ok(DeliveryErrorKind::QueueFull),
+            _ => Err(()),
+        }
+    }
+}
+
+//=============================================================================
+// DeliveryError
+//=============================================================================
+/// A report that a package sent by this junction was dropped on its way to the recipient.
+///
+/// Delivery errors travel back to the sender of the dropped package in `DeliveryError`
+/// packages, much like ICMP messages. No error is ever reported about a `DeliveryError`
+/// package or about broadcast packages, so errors cannot cascade.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryError {
+    /// Why the package was dropped.
+    pub kind: DeliveryErrorKind,
+
+    /// The junction that dropped the package.
+    pub reporter_id: JunctionId,
+
+    /// The recipient the dropped package was addressed to.
+    pub recipient_id: JunctionId,
+
+    /// The ID the sender assigned to the dropped package.
+    pub package_id: u32,
+}
+
+impl DeliveryError {
+    /// Builds the `DeliveryError` package reporting that a package was dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - Why the package was dropped.
+    /// * `package` - The dropped package.
+    /// * `reporter_id` - The ID of the junction that dropped the package.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<SlowPackage>` - The `DeliveryError` package addressed to the sender of the dropped
+    ///   package, or `None` if errors are never reported for this kind of package.
+    pub fn report(
+        kind: DeliveryErrorKind,
+        package: &SlowPackage,
+        reporter_id: &JunctionId,
+    ) -> Option<SlowPackage> {
+        if !Self::is_reportable(package) {
+            return None;
+        }
+
+        let error = DeliveryError {
+            kind,
+            reporter_id: reporter_id.clone(),
+            recipient_id: package.recipient_id().clone(),
+            package_id: package.package_id(),
+        };
+
+        Some(SlowPackage::new_delivery_error(
+            package.sender_id().clone(),
+            reporter_id.clone(),
+            &error.pack(),
+        ))
+    }
+
+    /// Returns `true` if a dropped package should be reported to its sender.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The dropped package.
+    pub fn is_reportable(package: &SlowPackage) -> bool {
+        !matches!(
+            package.package_type(),
+            Ok(PackageType::DeliveryError)
+                | Ok(PackageType::Hello)
+                | Ok(PackageType::Howdy)
+                | Ok(PackageType::RouteAdvertisement)
+                | Err(_)
+        )
+    }
+
+    /// Reads the delivery error carried by a `DeliveryError` package.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `DeliveryError` package.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Self>` - The delivery error, or `None` if the payload is invalid.
+    pub fn from_package(package: &SlowPackage) -> Option<Self> {
+        let data = &package.payload;
+        if data.len() < 5 {
+            return None;
+        }
+
+        let kind = DeliveryErrorKind::try_from(data[0]).ok()?;
+        let package_id = u32::from_le_bytes([data[1], data[2], data[3], data[4]]);
+        let recipient_id = JunctionId::unpack(&data[5..])?;
+        if 5 + recipient_id.pack().len() != data.len() {
+            return None;
+        }
+
+        Some(DeliveryError {
+            kind,
+            reporter_id: package.sender_id().clone(),
+            recipient_id,
+            package_id,
+        })
+    }
+
+    /// Serializes the delivery error into a byte vector.
+    ///
+    /// The reporter is not included, as it is the sender of the `DeliveryError` package.
+    ///
+    /// The format is:
+    /// - 1 byte: The `DeliveryErrorKind`
+    /// - 4 bytes: The ID of the dropped package as u32 in little-endian
+    /// - The packed `JunctionId` of the dropped package's recipient
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<u8>` - The serialized delivery error.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buffer = vec![self.kind.into()];
+        buffer.extend_from_slice(&self.package_id.to_le_bytes());
+        buffer.extend_from_slice(&self.recipient_id.pack());
+        buffer
+    }
+}
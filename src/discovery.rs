@@ -0,0 +1,145 @@
+use crate::junction_id::JunctionId;
+use crate::package::SlowPackage;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long a route discovery may run before its queued packages are given up on.
+pub const ROUTE_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+//=============================================================================
+// PendingDiscovery
+//=============================================================================
+/// A route discovery that is waiting for a route reply.
+struct PendingDiscovery {
+    /// When the route request was sent.
+    started: Instant,
+
+    /// Packages waiting for the route to be discovered.
+    packages: Vec<SlowPackage>,
+}
+
+//=============================================================================
+// RouteDiscovery
+//=============================================================================
+/// Tracks in-progress route discoveries and the packages queued behind them.
+///
+/// A junction that has no route to a destination sends a route request and
+/// parks outgoing packages here. When the route reply arrives the packages are
+/// released; if no reply arrives within the timeout they are released as expired.
+pub struct RouteDiscovery {
+    /// Pending discoveries keyed by the destination junction.
+    pending: HashMap<JunctionId, PendingDiscovery>,
+}
+
+impl RouteDiscovery {
+    /// Creates a new `RouteDiscovery` with no pending discoveries.
+    pub fn new() -> Self {
+        RouteDiscovery {
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Starts a discovery for a destination if one is not already running.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The destination to discover a route to.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a new discovery was started and a route request should be sent, `false` otherwise.
+    pub fn start(&mut self, junction_id: &JunctionId) -> bool {
+        if self.pending.contains_key(junction_id) {
+            return false;
+        }
+
+        self.pending.insert(
+            junction_id.clone(),
+            PendingDiscovery {
+                started: Instant::now(),
+                packages: Vec::new(),
+            },
+        );
+        true
+    }
+
+    /// Queues a package until a route to its recipient is discovered.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The package to queue.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a new discovery was started and a route request should be sent, `false` otherwise.
+    pub fn queue(&mut self, package: SlowPackage) -> bool {
+        let is_new = self.start(package.recipient_id());
+        if let Some(pending) = self.pending.get_mut(package.recipient_id()) {
+            pending.packages.push(package);
+        }
+        is_new
+    }
+
+    /// Completes a discovery, returning the packages that were waiting on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The destination a route was discovered to.
+    ///
+    /// # Returns
+    ///
+    /// The queued packages, or an empty vector if no discovery was pending.
+    pub fn resolve(&mut self, junction_id: &JunctionId) -> Vec<SlowPackage> {
+        self.pending
+            .remove(junction_id)
+            .map(|pending| pending.packages)
+            .unwrap_or_default()
+    }
+
+    /// Removes discoveries that have been running longer than `timeout`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The maximum time a discovery may run.
+    ///
+    /// # Returns
+    ///
+    /// The destination and queued packages for every expired discovery.
+    pub fn expire(&mut self, timeout: Duration) -> Vec<(JunctionId, Vec<SlowPackage>)> {
+        let expired: Vec<JunctionId> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.started.elapsed() >= timeout)
+            .map(|(junction_id, _)| junction_id.clone())
+            .collect();
+
+        expired
+            .into_iter()
+            .map(|junction_id| {
+                let packages = self.resolve(&junction_id);
+                (junction_id, packages)
+            })
+            .collect()
+    }
+
+    /// Returns `true` if a discovery for the destination is in progress.
+    pub fn is_pending(&self, junction_id: &JunctionId) -> bool {
+        self.pending.contains_key(junction_id)
+    }
+
+    /// Returns the number of discoveries in progress.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns `true` if no discoveries are in progress.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+impl Default for RouteDiscovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,128 @@
+use crate::junction_id::JunctionId;
+use std::net::SocketAddr;
+
+/// The hop count that marks a junction as unreachable in an advertisement.
+pub const INFINITE_HOPS: u8 = 16;
+
+/// The maximum number of entries packed into a single advertisement.
+pub const MAX_ADVERTISEMENT_ENTRIES: usize = 64;
+
+/// The number of advertisement intervals a route may go unconfirmed before it expires.
+pub const ROUTE_EXPIRY_INTERVALS: u32 = 3;
+
+//=============================================================================
+// RouteAdvertisement
+//=============================================================================
+/// A distance-vector advertisement listing the junctions a neighbor can reach.
+///
+/// Each entry is a `JunctionId` and the number of hops the advertising junction
+/// needs to reach it. An entry of `INFINITE_HOPS` withdraws the route.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteAdvertisement {
+    /// The advertised junctions and their hop counts.
+    pub entries: Vec<(JunctionId, u8)>,
+}
+
+impl RouteAdvertisement {
+    /// Builds the advertisements to send to one neighbor using poison reverse.
+    ///
+    /// Routes whose next hop is the neighbor itself are advertised back to it with
+    /// `INFINITE_HOPS`, so the neighbor never routes through us to reach a junction
+    /// it is closer to. This prevents two-node count-to-infinity loops.
+    ///
+    /// # Arguments
+    ///
+    /// * `routes` - The best route for each junction as `(junction_id, next_hop, hops)`.
+    /// * `neighbor` - The address of the neighbor the advertisements are for.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Self>` - The advertisements, each holding at most `MAX_ADVERTISEMENT_ENTRIES` entries.
+    ///   At least one advertisement is always returned so the neighbor keeps its route to us alive.
+    pub fn for_neighbor(
+        routes: &[(JunctionId, SocketAddr, u8)],
+        neighbor: SocketAddr,
+    ) -> Vec<Self> {
+        let entries: Vec<(JunctionId, u8)> = routes
+            .iter()
+            .map(|(junction_id, next_hop, hops)| {
+                let hops = if *next_hop == neighbor {
+                    INFINITE_HOPS
+                } else {
+                    (*hops).min(INFINITE_HOPS)
+                };
+                (junction_id.clone(), hops)
+            })
+            .collect();
+
+        if entries.is_empty() {
+            return vec![RouteAdvertisement {
+                entries: Vec::new(),
+            }];
+        }
+
+        entries
+            .chunks(MAX_ADVERTISEMENT_ENTRIES)
+            .map(|chunk| RouteAdvertisement {
+                entries: chunk.to_vec(),
+            })
+            .collect()
+    }
+
+    /// Serializes the advertisement into a byte vector.
+    ///
+    /// The format is:
+    /// - 2 bytes: Number of entries as u16 in little-endian
+    /// - For each entry: the packed `JunctionId` followed by 1 byte of hops
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<u8>` - The serialized advertisement.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&(self.entries.len() as u16).to_le_bytes());
+
+        for (junction_id, hops) in &self.entries {
+            buffer.extend_from_slice(&junction_id.pack());
+            buffer.push(*hops);
+        }
+
+        buffer
+    }
+
+    /// Deserializes a byte slice into a `RouteAdvertisement`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A byte slice containing the serialized advertisement.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Self>` - The advertisement if deserialization is successful, None otherwise.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() < 2 {
+            return None;
+        }
+
+        let count = u16::from_le_bytes([data[0], data[1]]) as usize;
+        let mut pos = 2;
+        let mut entries = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let junction_id = JunctionId::unpack(&data[pos..])?;
+            let id_len = u16::from_le_bytes([data[pos], data[pos + 1]]) as usize;
+            pos += 2 + id_len;
+
+            let hops = *data.get(pos)?;
+            pos += 1;
+
+            entries.push((junction_id, hops));
+        }
+
+        if pos != data.len() {
+            return None;
+        }
+
+        Some(RouteAdvertisement { entries })
+    }
+}
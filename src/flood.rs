@@ -0,0 +1,141 @@
+use crate::junction_id::JunctionId;
+use rand::seq::SliceRandom;
+use std::collections::{HashSet, VecDeque};
+
+/// The default number of `(sender_id, package_id)` pairs remembered by a `SeenPackageCache`.
+pub const DEFAULT_SEEN_CACHE_CAPACITY: usize = 4096;
+
+//=============================================================================
+// FloodMode
+//=============================================================================
+/// Controls how a junction re-broadcasts packages that have no known route.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FloodMode {
+    /// Re-broadcast to every neighbor except the one the package arrived from.
+    #[default]
+    Flood,
+    /// Re-broadcast to at most `fanout` randomly chosen neighbors.
+    Gossip { fanout: usize },
+}
+
+impl FloodMode {
+    /// Selects the neighbors a package should be re-broadcast to.
+    ///
+    /// # Arguments
+    ///
+    /// * `candidates` - All neighbors eligible to receive the package.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<T>` - Every candidate in `Flood` mode, or a random subset of at most
+    ///   `fanout` candidates in `Gossip` mode.
+    pub fn select<T>(&self, mut candidates: Vec<T>) -> Vec<T> {
+        match *self {
+            FloodMode::Flood => candidates,
+            FloodMode::Gossip { fanout } => {
+                candidates.shuffle(&mut rand::thread_rng());
+                candidates.truncate(fanout);
+                candidates
+            }
+        }
+    }
+}
+
+//=============================================================================
+// SeenPackageCache
+//=============================================================================
+/// Remembers recently seen `(sender_id, package_id)` pairs.
+///
+/// The cache holds a bounded number of entries and evicts the oldest pair once
+/// it is full, so a junction can cheaply check whether it has already relayed a
+/// package before broadcasting it again.
+pub struct SeenPackageCache {
+    /// The maximum number of pairs remembered.
+    capacity: usize,
+
+    /// The remembered pairs in insertion order, oldest first.
+    order: VecDeque<(JunctionId, u32)>,
+
+    /// The remembered pairs for fast lookup.
+    seen: HashSet<(JunctionId, u32)>,
+}
+
+impl SeenPackageCache {
+    /// Creates a new `SeenPackageCache`.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of pairs to remember.
+    ///
+    /// # Returns
+    ///
+    /// A new, empty instance of `SeenPackageCache`.
+    pub fn new(capacity: usize) -> Self {
+        SeenPackageCache {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            seen: HashSet::with_capacity(capacity),
+        }
+    }
+
+    /// Records a package as seen.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_id` - The ID of the junction that created the package.
+    /// * `package_id` - The ID the sender assigned to the package.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the pair had not been seen before, `false` otherwise.
+    pub fn insert(&mut self, sender_id: &JunctionId, package_id: u32) -> bool {
+        let key = (sender_id.clone(), package_id);
+        if self.seen.contains(&key) {
+            return false;
+        }
+
+        if self.capacity == 0 {
+            return true;
+        }
+
+        if self.order.len() >= self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.seen.remove(&oldest);
+        }
+
+        self.order.push_back(key.clone());
+        self.seen.insert(key);
+        true
+    }
+
+    /// Checks whether a package has been seen.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_id` - The ID of the junction that created the package.
+    /// * `package_id` - The ID the sender assigned to the package.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the pair is currently remembered, `false` otherwise.
+    pub fn contains(&self, sender_id: &JunctionId, package_id: u32) -> bool {
+        self.seen.contains(&(sender_id.clone(), package_id))
+    }
+
+    /// Returns the number of pairs currently remembered.
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Returns `true` if no pairs are remembered.
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+impl Default for SeenPackageCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEEN_CACHE_CAPACITY)
+    }
+}
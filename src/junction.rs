@@ -1,14 +1,37 @@
 // Re-export JunctionId so it can be imported from this module
 pub use crate::junction_id::JunctionId;
+
+use crate::ack::{AckPolicy, AckTracker, DeliveryHandle};
+use crate::capability::{Agreement, NegotiationError, ProtocolOffer};
+use crate::delivery::{DEFAULT_RECEIVE_QUEUE_CAPACITY, DeliveryError, DeliveryErrorKind};
+use crate::discovery::{ROUTE_DISCOVERY_TIMEOUT, RouteDiscovery};
+use crate::distance_vector::{INFINITE_HOPS, ROUTE_EXPIRY_INTERVALS, RouteAdvertisement};
+use crate::flood::{FloodMode, SeenPackageCache};
+use crate::multipath::{MultipathPolicy, MultipathSelector};
 use crate::package::{PackageType, SlowPackage};
-use crate::route::RouteTable;
+use crate::priority::{Priority, PriorityQueue, PriorityWeights};
+use crate::reorder::{ReorderBuffer, ReorderPolicy, SequenceCounter};
+use crate::route::{RouteTable, RoutingMode};
+use crate::rpc::{self, PendingCalls, RpcError, RpcRegistry, RpcRequest, RpcResponse};
+use crate::store::{PackageStore, StorePolicy};
+use crate::stream::{OutgoingSegment, SlowStream, StreamMux, StreamSegment};
+use crate::traceroute::{TRACEROUTE_TIMEOUT, TracerouteHop, TracerouteRecord};
+use crate::transfer::{CompletedTransfer, TransferHandle, TransferPolicy, Transfers};
+use crate::tunnel::{self, TunnelTargets};
 use crate::udp::udp_socket::SlowUdpSocket;
 use serde_json::Value;
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
-use tokio::sync::{Mutex, Notify};
+use std::sync::{Arc, Weak};
+use std::time::SystemTime;
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, Notify, mpsc, oneshot};
+use tokio::time::{Duration, Instant};
+
+/// How often the junction runs its periodic maintenance.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_millis(100);
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct JsonPacket {
@@ -29,10 +52,17 @@ pub struct SlowJunction {
     known_junctions: Mutex<HashSet<SocketAddr>>,
 
     /// A queue of packages to be sent.
-    send_queue: Mutex<VecDeque<SlowPackage>>,
+    send_queue: Mutex<PriorityQueue<SlowPackage>>,
+
+    /// The package taken from the send queue and not yet sent, so a drain that is
+    /// cancelled part way picks it up again.
+    sending: Mutex<Option<SlowPackage>>,
+
+    /// The IDs of queued packages to be sent over two routes at once.
+    critical_packages: Mutex<HashSet<u32>>,
 
     /// A queue of received JSON packets.
-    received_queue: Mutex<VecDeque<JsonPacket>>,
+    received_queue: Mutex<VecDeque<(JsonPacket, Option<SystemTime>)>>,
 
     /// The address of the junction.
     addr: SocketAddr,
@@ -63,6 +93,85 @@ pub struct SlowJunction {
 
     /// A counter for the number of duplicate packages rejected.
     duplicate_package_count: AtomicUsize,
+
+    /// A counter for the number of packages dropped because they expired.
+    expired_package_count: AtomicUsize,
+
+    /// Recently seen `(sender_id, package_id)` pairs, used to suppress repeat broadcasts.
+    seen_packages: Mutex<SeenPackageCache>,
+
+    /// Acknowledged packages already delivered, keyed by their first package ID, so resent
+    /// copies are acked again without being delivered twice.
+    delivered_packages: Mutex<SeenPackageCache>,
+
+    /// How packages without a known route are re-broadcast.
+    flood_mode: Mutex<FloodMode>,
+
+    /// A flag to indicate if routes are discovered on demand before sending.
+    route_discovery_enabled: AtomicBool,
+
+    /// Route discoveries in progress and the packages waiting on them.
+    route_discovery: Mutex<RouteDiscovery>,
+
+    /// How the route table is populated.
+    routing_mode: Mutex<RoutingMode>,
+
+    /// When the next distance-vector advertisement is due.
+    next_advertisement: Mutex<Instant>,
+
+    /// Picks between the routes available to a destination.
+    multipath: Mutex<MultipathSelector>,
+
+    /// A counter used to identify traceroutes started by this junction.
+    trace_count: AtomicU32,
+
+    /// Traceroutes waiting for a reply, keyed by trace ID.
+    pending_traceroutes: Mutex<HashMap<u32, oneshot::Sender<Vec<TracerouteHop>>>>,
+
+    /// The maximum number of JSON packets held in the received queue.
+    receive_queue_capacity: AtomicUsize,
+
+    /// A queue of errors reported about packages this junction sent.
+    delivery_errors: Mutex<VecDeque<DeliveryError>>,
+
+    /// Acknowledged sends waiting for their ack.
+    acks: Mutex<AckTracker>,
+
+    /// The handlers serving RPC calls made to this junction.
+    rpc_handlers: Mutex<RpcRegistry>,
+
+    /// RPC calls made by this junction that are waiting for a response.
+    rpc_calls: Mutex<PendingCalls>,
+
+    /// The sequence numbers given to ordered packages, per recipient.
+    sequences: Mutex<SequenceCounter>,
+
+    /// Ordered packages held back until the packages sent before them arrive.
+    reorder_buffer: Mutex<ReorderBuffer<(SlowPackage, SocketAddr)>>,
+
+    /// The byte streams to and from other junctions.
+    streams: Mutex<StreamMux>,
+
+    /// Streams opened by other junctions that have not been accepted yet.
+    incoming_streams: Mutex<mpsc::UnboundedReceiver<SlowStream>>,
+
+    /// Hands streams opened by other junctions to `accept_stream`.
+    incoming_sender: mpsc::UnboundedSender<SlowStream>,
+
+    /// The tunnels this junction serves, keyed by name.
+    tunnels: Mutex<TunnelTargets>,
+
+    /// The chunked transfers to and from other junctions.
+    transfers: Mutex<Transfers>,
+
+    /// Packages held for unreachable recipients, or `None` when store-and-forward is off.
+    store: Mutex<Option<PackageStore>>,
+
+    /// The protocol versions and features offered in hellos.
+    protocol_offer: Mutex<ProtocolOffer>,
+
+    /// The outcome of the hello exchange with each neighbor, keyed by address.
+    peer_protocols: Mutex<HashMap<SocketAddr, Result<Agreement, NegotiationError>>>,
 }
 
 impl Drop for SlowJunction {
@@ -84,10 +193,16 @@ impl SlowJunction {
     /// * `Result<Arc<Self>, std::io::Error>` - A result containing a new instance of `SlowJunction` or an error.
     pub async fn new(addr: SocketAddr, junction_id: JunctionId) -> std::io::Result<Arc<Self>> {
         let connection = SlowUdpSocket::new(addr).await?;
+        let (outgoing_sender, outgoing_segments) = mpsc::unbounded_channel();
+        let (incoming_sender, incoming_streams) = mpsc::unbounded_channel();
+        let (transfer_sender, transfer_packages) = mpsc::unbounded_channel();
+        let transfers = Transfers::new(junction_id.clone(), transfer_sender);
         let junction = Arc::new(Self {
             connection,
             known_junctions: Mutex::new(HashSet::new()),
-            send_queue: Mutex::new(VecDeque::new()),
+            send_queue: Mutex::new(PriorityQueue::default()),
+            sending: Mutex::new(None),
+            critical_packages: Mutex::new(HashSet::new()),
             received_queue: Mutex::new(VecDeque::new()),
             addr,
             junction_id, // use passed JunctionId directly
@@ -98,7 +213,33 @@ impl SlowJunction {
             route_table: Mutex::new(RouteTable::new()),
             sent_package_count: AtomicU32::new(0),
             duplicate_package_count: AtomicUsize::new(0),
+            expired_package_count: AtomicUsize::new(0),
             unique_package_count: AtomicU32::new(0),
+            seen_packages: Mutex::new(SeenPackageCache::default()),
+            delivered_packages: Mutex::new(SeenPackageCache::default()),
+            flood_mode: Mutex::new(FloodMode::default()),
+            route_discovery_enabled: AtomicBool::new(false),
+            route_discovery: Mutex::new(RouteDiscovery::new()),
+            routing_mode: Mutex::new(RoutingMode::default()),
+            next_advertisement: Mutex::new(Instant::now()),
+            multipath: Mutex::new(MultipathSelector::default()),
+            trace_count: AtomicU32::new(0),
+            pending_traceroutes: Mutex::new(HashMap::new()),
+            receive_queue_capacity: AtomicUsize::new(DEFAULT_RECEIVE_QUEUE_CAPACITY),
+            delivery_errors: Mutex::new(VecDeque::new()),
+            acks: Mutex::new(AckTracker::default()),
+            rpc_handlers: Mutex::new(RpcRegistry::new()),
+            rpc_calls: Mutex::new(PendingCalls::new()),
+            sequences: Mutex::new(SequenceCounter::new()),
+            reorder_buffer: Mutex::new(ReorderBuffer::default()),
+            streams: Mutex::new(StreamMux::new(outgoing_sender)),
+            incoming_streams: Mutex::new(incoming_streams),
+            incoming_sender,
+            tunnels: Mutex::new(TunnelTargets::new()),
+            transfers: Mutex::new(transfers),
+            store: Mutex::new(None),
+            protocol_offer: Mutex::new(ProtocolOffer::default()),
+            peer_protocols: Mutex::new(HashMap::new()),
         });
 
         let junction_clone = Arc::clone(&junction);
@@ -106,6 +247,9 @@ impl SlowJunction {
             junction_clone.run().await;
         });
 
+        Self::start_stream_sender(Arc::downgrade(&junction), outgoing_segments);
+        Self::start_transfer_sender(Arc::downgrade(&junction), transfer_packages);
+
         Ok(junction)
     }
 
@@ -136,320 +280,1738 @@ impl SlowJunction {
         let mut queue = self.send_queue.lock().await;
         let package =
             SlowPackage::new_json_payload(recipient_id.clone(), self.junction_id.clone(), &json);
-        queue.push_back(package);
+        queue.push(package.priority(), package);
         self.send_notify.notify_one();
     }
 
-    /// Receives a JSON packet from the received queue.
+    /// Queues a JSON value that is dropped if it cannot be delivered within `max_age`.
     ///
-    /// # Returns
-    ///
-    /// * `Option<JsonPacket>` - An optional JSON packet if available.
-    pub async fn recv(&self) -> Option<JsonPacket> {
-        let mut queue = self.received_queue.lock().await;
-        queue.pop_front()
-    }
-
-    /// Adds a seed address to the set of known junction addresses.
+    /// A value that has already expired is dropped without being queued.
     ///
     /// # Arguments
     ///
-    /// * `addr` - A `SocketAddr` to be added to the set of known junction addresses.
-    pub async fn seed(&self, addr: SocketAddr) {
-        if addr == self.addr {
+    /// * `json` - A `Value` representing the JSON data to be sent.
+    /// * `recipient_id` - The `JunctionId` of the recipient.
+    /// * `max_age` - How long the value stays valid, counted from now.
+    pub async fn send_expiring(&self, json: Value, recipient_id: &JunctionId, max_age: Duration) {
+        let mut package =
+            SlowPackage::new_json_payload(recipient_id.clone(), self.junction_id.clone(), &json);
+        package.set_max_age(max_age);
+        if self.drop_if_expired(&package) {
             return;
         }
-        let mut known_junctions = self.known_junctions.lock().await;
-        known_junctions.insert(addr);
-    }
 
-    /// Returns the `SocketAddr` of the `SlowJunction`.
-    pub fn get_address(&self) -> SocketAddr {
-        self.addr
+        let mut queue = self.send_queue.lock().await;
+        queue.push(package.priority(), package);
+        self.send_notify.notify_one();
     }
 
-    /// Returns the number of packets waiting to be received.
+    /// Queues a JSON value to be sent with a given priority instead of `Priority::Interactive`.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// * `usize` - The number of packets in the received queue.
-    pub async fn get_waiting_package_count(&self) -> usize {
-        let queue = self.received_queue.lock().await;
-        queue.len()
+    /// * `json` - A `Value` representing the JSON data to be sent.
+    /// * `recipient_id` - The `JunctionId` of the recipient.
+    /// * `priority` - The `Priority` to schedule the value with.
+    pub async fn send_with_priority(
+        &self,
+        json: Value,
+        recipient_id: &JunctionId,
+        priority: Priority,
+    ) {
+        let mut queue = self.send_queue.lock().await;
+        let mut package =
+            SlowPackage::new_json_payload(recipient_id.clone(), self.junction_id.clone(), &json);
+        package.set_priority(priority);
+        queue.push(priority, package);
+        self.send_notify.notify_one();
     }
 
-    /// Returns the number of duplicate packets received & rejected.
+    /// Sets how many packages of each priority are sent per round of the send scheduler.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// * `usize` - The number of duplicate packets rejected.
-    pub fn get_duplicate_package_count(&self) -> usize {
-        self.duplicate_package_count.load(Ordering::SeqCst)
+    /// * `weights` - The `PriorityWeights` to use.
+    pub async fn set_priority_weights(&self, weights: PriorityWeights) {
+        self.send_queue.lock().await.set_weights(weights);
     }
 
-    /// Returns the number of unique packages received.
-    ///
-    /// # Returns
-    ///
-    /// * `u32` - The number of unique packages received.
-    pub fn get_unique_package_count(&self) -> u32 {
-        self.unique_package_count.load(Ordering::SeqCst)
+    /// Returns the current `PriorityWeights`.
+    pub async fn get_priority_weights(&self) -> PriorityWeights {
+        self.send_queue.lock().await.weights()
     }
 
-    /// Waits for a notification that there are items in the received queue and returns the JSON packet.
+    /// Sends a JSON value and asks the recipient to acknowledge it.
+    ///
+    /// The package is queued like any other, and queued again each time the `AckPolicy`
+    /// timeout passes without an ack. Resent copies carry the ID of the first transmission,
+    /// which the recipient acks and uses to deliver the package only once.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - A `Value` representing the JSON data to be sent.
+    /// * `recipient_id` - The `JunctionId` of the recipient.
     ///
     /// # Returns
     ///
-    /// * `Option<JsonPacket>` - An optional JSON packet if available.
-    pub async fn wait_for_package(&self) -> Option<JsonPacket> {
-        self.receive_notify.notified().await;
-        let mut queue = self.received_queue.lock().await;
-        queue.pop_front()
-    }
-
-    /// Updates the state of the `SlowJunction` by processing received packets and sending queued JSON values.
-    async fn update2(&self) {
-        tokio::select! {
-            _= self.pump_send() => {}
-            _ = self.pump_recv() => {}
-        }
-    }
+    /// * `DeliveryHandle` - A handle that resolves when the recipient acks the package.
+    pub async fn send_acked(&self, json: Value, recipient_id: &JunctionId) -> DeliveryHandle {
+        let mut package =
+            SlowPackage::new_json_payload(recipient_id.clone(), self.junction_id.clone(), &json);
+        package.request_ack();
+        let package_id = self.next_package_id().await;
+        package.set_package_id(package_id);
 
-    /// Runs the main loop of the `SlowJunction`, periodically calling `update2`.
-    async fn run(&self) {
-        while !self.terminate.load(Ordering::SeqCst) {
-            self.update2().await;
-        }
+        let handle = self.acks.lock().await.track(package.clone());
+        let mut queue = self.send_queue.lock().await;
+        queue.push(package.priority(), package);
+        self.send_notify.notify_one();
+        handle
     }
 
-    /// Updates the known junctions by adding the sender address and sender ID.
+    /// Sets how long acknowledged sends wait for an ack and how often they are retried.
     ///
     /// # Arguments
     ///
-    /// * `package` - A reference to the `SlowPackage` that was received.
-    /// * `sender_addr` - The `SocketAddr` of the sender to be added.
-    async fn update_route_table(&self, package: &SlowPackage, sender_addr: SocketAddr) -> bool {
-        let mut known_junctions = self.known_junctions.lock().await;
-        known_junctions.insert(sender_addr);
-
-        let junction_id = package.sender_id();
-        let hop_count = package.hop_count();
-        let package_id = package.package_id();
-        let time = 0.0;
+    /// * `policy` - The `AckPolicy` to use for new sends.
+    pub async fn set_ack_policy(&self, policy: AckPolicy) {
+        self.acks.lock().await.set_policy(policy);
+    }
 
-        let mut route_table = self.route_table.lock().await;
-        route_table.update_route(junction_id, sender_addr, hop_count, time, package_id)
+    /// Returns the current `AckPolicy`.
+    pub async fn get_ack_policy(&self) -> AckPolicy {
+        self.acks.lock().await.policy()
     }
 
-    /// Handles a received package by forwarding it and updating the known junctions and received queue.
+    /// Queues a JSON value to be delivered in order with the other ordered values sent to the recipient.
+    ///
+    /// The recipient holds back values that arrive out of order until the values sent before
+    /// them arrive, or until its `ReorderPolicy` gives up on them.
     ///
     /// # Arguments
     ///
-    /// * `package` - A `SlowPackage` that was received.
-    /// * `sender_addr` - The `SocketAddr` of the sender.
-    async fn on_package_received(&self, package: SlowPackage, sender_addr: SocketAddr) {
-        let package_type = package.package_type();
-
-        if package_type == Ok(PackageType::Hello) {
-            self.on_hello_received(package, sender_addr).await;
-            return;
-        }
-
-        // Update the route table with the sender address.
-        let is_updated = self.update_route_table(&package, sender_addr).await;
-
-        // If the package has already been processed, return.
-        if !is_updated {
-            self.duplicate_package_count.fetch_add(1, Ordering::SeqCst);
-            return;
-        }
-
-        // Increment unique_package_count for each non-rejected package received.
-        self.unique_package_count.fetch_add(1, Ordering::SeqCst);
-
-        if *package.recipient_id() != self.junction_id {
-            self.forward(package, sender_addr).await;
-            return;
-        }
+    /// * `json` - A `Value` representing the JSON data to be queued.
+    /// * `recipient_id` - The `JunctionId` of the recipient.
+    pub async fn send_ordered(&self, json: Value, recipient_id: &JunctionId) {
+        let mut package =
+            SlowPackage::new_json_payload(recipient_id.clone(), self.junction_id.clone(), &json);
+        let sequence = self.sequences.lock().await.next(recipient_id);
+        package.set_sequence(sequence);
 
-        match package_type {
-            Ok(PackageType::Ping) => {
-                self.on_ping_received(package).await;
-            }
-            Ok(PackageType::Pong) => {
-                self.on_pong_received().await;
-            }
-            Ok(PackageType::Json) => {
-                if let Some(json) = package.json_payload() {
-                    let json_packet = JsonPacket {
-                        addr: sender_addr,
-                        json,
-                    };
-
-                    let mut queue = self.received_queue.lock().await;
-                    queue.push_back(json_packet);
-                    self.receive_notify.notify_one();
-                }
-            }
-            Ok(PackageType::Bin) => {}
-            _ => {}
-        }
+        let mut queue = self.send_queue.lock().await;
+        queue.push(package.priority(), package);
+        self.send_notify.notify_one();
     }
 
-    /// Forwards a `SlowPackage` to all known junctions except the sender.
+    /// Sets how long ordered packages are held back waiting for missing packages.
     ///
     /// # Arguments
     ///
-    /// * `package` - A `SlowPackage` to be forwarded.
-    /// * `sender_addr` - The `SocketAddr` of the sender.
-    async fn forward(&self, mut package: SlowPackage, sender_addr: SocketAddr) {
-        if package.increment_hops() >= 128 {
-            return;
-        }
-        if self.send_to_best_route(&package).await {
-            return;
-        }
-        self.send_to_known_junctions(package, Some(sender_addr))
-            .await;
+    /// * `policy` - The `ReorderPolicy` to use.
+    pub async fn set_reorder_policy(&self, policy: ReorderPolicy) {
+        self.reorder_buffer.lock().await.set_policy(policy);
     }
 
-    /// Sends a `SlowPackage` to all known junctions except the specified sender.
+    /// Returns the current `ReorderPolicy`.
+    pub async fn get_reorder_policy(&self) -> ReorderPolicy {
+        self.reorder_buffer.lock().await.policy()
+    }
+
+    /// Opens a reliable byte stream to another junction.
     ///
     /// # Arguments
     ///
-    /// * `package` - The `SlowPackage` to be sent.
-    /// * `exclude_addr` - The `SocketAddr` of the sender to be excluded.
-    pub async fn send_to_known_junctions(
-        &self,
-        package: SlowPackage,
-        exclude_addr: Option<SocketAddr>,
-    ) {
-        let known_junctions = self.known_junctions.lock().await;
-        for addr in known_junctions.iter() {
-            if Some(*addr) != exclude_addr {
-                self.connection
-                    .send_package(&package, addr)
-                    .await
-                    .expect("Failed to send package");
-            }
-        }
-    }
-
-    /// Waits for a package via `connection.recv_package()` and returns it.
+    /// * `recipient_id` - The `JunctionId` of the junction at the other end of the stream.
     ///
     /// # Returns
     ///
-    /// * `Option<(SlowPackage, SocketAddr)>` - An optional tuple containing the received package and sender address.
-    pub async fn read_package(&self) -> Option<(SlowPackage, SocketAddr)> {
-        self.connection.receive_package().await
-    }
-
-    /// Sends all queued packages to known junctions, excluding the address `0.0.0.0:0`.
-    async fn pump_send(&self) {
-        self.send_notify.notified().await;
-        let mut queue = self.send_queue.lock().await;
-
-        while let Some(mut package) = queue.pop_front() {
-            let package_id = self.sent_package_count.fetch_add(1, Ordering::SeqCst) + 1;
-            package.set_package_id(package_id);
-
-            if self.send_to_best_route(&package).await {
-                continue;
-            }
-
-            self.send_to_known_junctions(package, None).await;
-        }
+    /// * `SlowStream` - The stream, which implements `AsyncRead` and `AsyncWrite`.
+    pub async fn open_stream(&self, recipient_id: &JunctionId) -> SlowStream {
+        self.streams.lock().await.open(recipient_id, "")
     }
 
-    /// Receives a package via `connection.recv_package()` and processes it.
-    async fn pump_recv(&self) {
-        if let Some((slow_package, sender_addr)) = self.connection.receive_package().await {
-            self.on_package_received(slow_package, sender_addr).await;
-        }
+    /// Waits for another junction to open a stream to this junction.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<SlowStream>` - The stream, or `None` if the junction is shutting down.
+    pub async fn accept_stream(&self) -> Option<SlowStream> {
+        self.incoming_streams.lock().await.recv().await
     }
 
-    /// Sends a pong message to a specific `SocketAddr`.
+    /// Serves a tunnel, forwarding each connection made through it to a target address.
     ///
     /// # Arguments
     ///
-    /// * `recipient_id` - A u16 representing the recipient ID.
-    pub async fn pong(&self, recipient_id: &JunctionId) {
-        let mut queue = self.send_queue.lock().await;
-        let package = SlowPackage::new_pong(recipient_id.clone(), self.junction_id.clone());
-        queue.push_back(package);
-        self.send_notify.notify_one();
+    /// * `tunnel` - The name other junctions use to reach the tunnel.
+    /// * `target` - The `SocketAddr` connections through the tunnel are forwarded to.
+    pub async fn add_tunnel(&self, tunnel: &str, target: SocketAddr) {
+        self.tunnels.lock().await.add(tunnel, target);
     }
 
-    /// Sends a ping message to a specific `SocketAddr`.
+    /// Stops serving a tunnel. Connections already made through it stay open.
     ///
     /// # Arguments
     ///
-    /// * `junction_id` - The target junction id as a &str.
-    pub async fn ping(&self, junction_id: &JunctionId) {
-        let mut queue = self.send_queue.lock().await;
-        let package = SlowPackage::new_ping(junction_id.clone(), self.junction_id.clone());
-        queue.push_back(package);
-        self.send_notify.notify_one();
-    }
-
-    /// Returns the current value of the pong counter.
+    /// * `tunnel` - The name of the tunnel.
     ///
     /// # Returns
     ///
-    /// * `u32` - The current value of the pong counter.
-    pub async fn get_pong_counter(&self) -> u32 {
-        self.pong_counter.load(Ordering::SeqCst)
-    }
-
-    /// Increments the pong counter when a pong message is received.
-    async fn on_pong_received(&self) {
-        self.pong_counter.fetch_add(1, Ordering::SeqCst);
+    /// * `bool` - `true` if the tunnel was being served.
+    pub async fn remove_tunnel(&self, tunnel: &str) -> bool {
+        self.tunnels.lock().await.remove(tunnel)
     }
 
-    /// Handles a received ping message by sending a pong response.
+    /// Listens on a local TCP port and forwards each connection to a tunnel served by another junction.
     ///
     /// # Arguments
     ///
-    /// * `package` - The `SlowPackage` that was received.
-    async fn on_ping_received(&self, package: SlowPackage) {
-        let sender_id = package.sender_id();
-        self.pong(&sender_id).await;
-    }
-
-    /// Handles a received hello message by sending a hello response.
+    /// * `listen_addr` - The `SocketAddr` to listen on; port 0 picks a free port.
+    /// * `remote_id` - The `JunctionId` of the junction serving the tunnel.
+    /// * `tunnel` - The name of the tunnel.
     ///
-    /// # Arguments
+    /// # Returns
     ///
-    /// * `sender_addr` - The `SocketAddr` of the sender.
-    async fn on_hello_received(&self, package: SlowPackage, sender_addr: SocketAddr) {
-        if package.package_id() == 0 {
-            self.send_hello_response(sender_addr).await;
-        }
-        self.known_junctions.lock().await.insert(sender_addr);
+    /// * `std::io::Result<SocketAddr>` - The address being listened on, or an error if binding failed.
+    pub async fn forward_port(
+        self: &Arc<Self>,
+        listen_addr: SocketAddr,
+        remote_id: &JunctionId,
+        tunnel: &str,
+    ) -> std::io::Result<SocketAddr> {
+        let listener = TcpListener::bind(listen_addr).await?;
+        let local_addr = listener.local_addr()?;
+        let junction = Arc::downgrade(self);
+        let remote_id = remote_id.clone();
+        let service = tunnel::service_name(tunnel);
+
+        tokio::spawn(async move {
+            while let Ok((connection, _)) = listener.accept().await {
+                let junction = match junction.upgrade() {
+                    Some(junction) => junction,
+                    None => break,
+                };
+
+                let stream = junction.streams.lock().await.open(&remote_id, &service);
+                tokio::spawn(tunnel::splice(connection, stream));
+            }
+        });
+
+        Ok(local_addr)
     }
 
-    /// Gets the best route to a junction.
+    /// Starts sending data to another junction in chunks, each acknowledged and checked by the recipient.
+    ///
+    /// Sending the same data under the same name again after an interruption resumes
+    /// the transfer from the last chunk the recipient confirmed.
     ///
     /// # Arguments
     ///
-    /// * `junction_id` - The `JunctionId` of the junction.
+    /// * `recipient_id` - The `JunctionId` of the recipient.
+    /// * `name` - The name of the transfer, such as a file name.
+    /// * `data` - The data to send.
     ///
     /// # Returns
     ///
-    /// * `Option<SocketAddr>` - The best route to the junction.
-    pub async fn get_best_route(&self, junction_id: &JunctionId) -> Option<SocketAddr> {
-        let route_table = self.route_table.lock().await;
-        let best_route_addr = route_table.get_best_route(junction_id);
-        best_route_addr
+    /// * `TransferHandle` - A handle that reports progress and resolves when the transfer ends.
+    pub async fn send_transfer(
+        &self,
+        recipient_id: &JunctionId,
+        name: &str,
+        data: Vec<u8>,
+    ) -> TransferHandle {
+        self.transfers.lock().await.start(recipient_id, name, data)
     }
 
-    /// Sends a `SlowPackage` to the best route available.
+    /// Starts sending a file to another junction, named after the file.
     ///
     /// # Arguments
     ///
-    /// * `package` - The `SlowPackage` to be sent.
-    pub async fn send_to_best_route(&self, package: &SlowPackage) -> bool {
-        if let Some(best_route) = self.get_best_route(package.recipient_id()).await {
+    /// * `recipient_id` - The `JunctionId` of the recipient.
+    /// * `path` - The path of the file.
+    ///
+    /// # Returns
+    ///
+    /// * `std::io::Result<TransferHandle>` - A handle to the transfer, or an error if the file could not be read.
+    pub async fn send_file(
+        &self,
+        recipient_id: &JunctionId,
+        path: impl AsRef<Path>,
+    ) -> std::io::Result<TransferHandle> {
+        let path = path.as_ref();
+        let data = tokio::fs::read(path).await?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(self.send_transfer(recipient_id, &name, data).await)
+    }
+
+    /// Removes the next transfer received in full from another junction.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<CompletedTransfer>` - The transfer, if one has completed.
+    pub async fn receive_transfer(&self) -> Option<CompletedTransfer> {
+        self.transfers.lock().await.pop_completed()
+    }
+
+    /// Sets how chunks of new outgoing transfers are acknowledged and resent, and which
+    /// transfers from other junctions are accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The `TransferPolicy` to use.
+    pub async fn set_transfer_policy(&self, policy: TransferPolicy) {
+        self.transfers.lock().await.set_policy(policy);
+    }
+
+    /// Returns the current `TransferPolicy`.
+    pub async fn get_transfer_policy(&self) -> TransferPolicy {
+        self.transfers.lock().await.policy()
+    }
+
+    /// Turns on store-and-forward, holding packages for recipients with no known route
+    /// until they are seen again through a hello or a new route.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The `StorePolicy` limiting what is held. Packages saved in its file are loaded.
+    ///
+    /// # Returns
+    ///
+    /// * `std::io::Result<()>` - An error if the policy's file could not be read.
+    pub async fn enable_store_and_forward(&self, policy: StorePolicy) -> std::io::Result<()> {
+        let store = PackageStore::open(policy)?;
+        *self.store.lock().await = Some(store);
+        Ok(())
+    }
+
+    /// Turns off store-and-forward, dropping the held packages. A policy file is left as it is.
+    pub async fn disable_store_and_forward(&self) {
+        *self.store.lock().await = None;
+    }
+
+    /// Returns the number of packages held for unreachable recipients.
+    pub async fn get_stored_package_count(&self) -> usize {
+        self.store
+            .lock()
+            .await
+            .as_ref()
+            .map_or(0, PackageStore::len)
+    }
+
+    /// Registers the handler serving RPC calls to a method, replacing any previous handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The name of the method.
+    /// * `handler` - A function that takes the call arguments and returns the result or an error message.
+    pub async fn register_rpc_handler<F>(&self, method: &str, handler: F)
+    where
+        F: Fn(Value) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.rpc_handlers
+            .lock()
+            .await
+            .register(method, Arc::new(handler));
+    }
+
+    /// Removes the handler serving RPC calls to a method.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The name of the method.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - `true` if a handler was removed.
+    pub async fn unregister_rpc_handler(&self, method: &str) -> bool {
+        self.rpc_handlers.lock().await.unregister(method)
+    }
+
+    /// Calls a method on another junction and waits for its response.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - The `JunctionId` of the junction serving the call.
+    /// * `method` - The name of the method.
+    /// * `args` - The arguments passed to the handler.
+    /// * `timeout` - How long to wait for the response.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Value, RpcError>` - The value returned by the handler, or why there is none.
+    pub async fn call(
+        &self,
+        recipient_id: &JunctionId,
+        method: &str,
+        args: Value,
+        timeout: Duration,
+    ) -> Result<Value, RpcError> {
+        let (call_id, receiver) = self.rpc_calls.lock().await.start();
+        let request = RpcRequest {
+            call_id,
+            method: method.to_string(),
+            args,
+        };
+
+        {
+            let mut queue = self.send_queue.lock().await;
+            let package = SlowPackage::new_rpc_request(
+                recipient_id.clone(),
+                self.junction_id.clone(),
+                &request.pack(),
+            );
+            queue.push(package.priority(), package);
+            self.send_notify.notify_one();
+        }
+
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(result)) => result,
+            _ => {
+                self.rpc_calls.lock().await.cancel(call_id);
+                Err(RpcError::TimedOut)
+            }
+        }
+    }
+
+    /// Sends a JSON value over the two best routes to the recipient at once.
+    ///
+    /// The copies leave through different next hops, so the message survives the loss
+    /// of either neighbor. Only the first hop is chosen here: the two paths may meet
+    /// again further on, and a junction both pass through can still lose both copies.
+    /// The recipient discards whichever copy arrives second. The value is queued like a
+    /// regular `send`, and if fewer than two routes are known when it leaves the queue
+    /// it is sent like one.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - A `Value` representing the JSON data to be sent.
+    /// * `recipient_id` - The `JunctionId` of the recipient.
+    pub async fn send_critical(&self, json: Value, recipient_id: &JunctionId) {
+        let mut package =
+            SlowPackage::new_json_payload(recipient_id.clone(), self.junction_id.clone(), &json);
+        let package_id = self.next_package_id().await;
+        package.set_package_id(package_id);
+        self.critical_packages.lock().await.insert(package_id);
+
+        let mut queue = self.send_queue.lock().await;
+        queue.push(package.priority(), package);
+        self.send_notify.notify_one();
+    }
+
+    /// Receives a JSON packet from the received queue.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<JsonPacket>` - An optional JSON packet if available.
+    pub async fn recv(&self) -> Option<JsonPacket> {
+        let mut queue = self.received_queue.lock().await;
+        self.pop_unexpired(&mut queue)
+    }
+
+    /// Receives an error reported about a package this junction sent.
+    ///
+    /// Junctions that drop a package because it exceeded its hop limit, because the
+    /// recipient's receive queue is full, or because no route to the recipient was
+    /// found in time, report the drop back to the sender.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<DeliveryError>` - An optional delivery error if available.
+    pub async fn recv_delivery_error(&self) -> Option<DeliveryError> {
+        let mut queue = self.delivery_errors.lock().await;
+        queue.pop_front()
+    }
+
+    /// Sets the maximum number of JSON packets held in the received queue.
+    ///
+    /// Packets that arrive while the queue is full are dropped and reported to their
+    /// sender as `DeliveryErrorKind::QueueFull`.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of packets to hold.
+    pub fn set_receive_queue_capacity(&self, capacity: usize) {
+        self.receive_queue_capacity
+            .store(capacity, Ordering::SeqCst);
+    }
+
+    /// Adds a seed address to the set of known junction addresses.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - A `SocketAddr` to be added to the set of known junction addresses.
+    pub async fn seed(&self, addr: SocketAddr) {
+        if addr == self.addr {
+            return;
+        }
+        let mut known_junctions = self.known_junctions.lock().await;
+        known_junctions.insert(addr);
+    }
+
+    /// Returns the `SocketAddr` of the `SlowJunction`.
+    pub fn get_address(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the number of packets waiting to be received.
+    ///
+    /// # Returns
+    ///
+    /// * `usize` - The number of packets in the received queue.
+    pub async fn get_waiting_package_count(&self) -> usize {
+        let queue = self.received_queue.lock().await;
+        queue.len()
+    }
+
+    /// Returns the number of duplicate packets received & rejected.
+    ///
+    /// # Returns
+    ///
+    /// * `usize` - The number of duplicate packets rejected.
+    pub fn get_duplicate_package_count(&self) -> usize {
+        self.duplicate_package_count.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of packages dropped because they expired before being sent,
+    /// forwarded or delivered.
+    ///
+    /// # Returns
+    ///
+    /// * `usize` - The number of expired packages dropped.
+    pub fn get_expired_package_count(&self) -> usize {
+        self.expired_package_count.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of unique packages received.
+    ///
+    /// # Returns
+    ///
+    /// * `u32` - The number of unique packages received.
+    pub fn get_unique_package_count(&self) -> u32 {
+        self.unique_package_count.load(Ordering::SeqCst)
+    }
+
+    /// Sets how packages without a known route are re-broadcast.
+    ///
+    /// # Arguments
+    ///
+    /// * `flood_mode` - The `FloodMode` to use when forwarding.
+    pub async fn set_flood_mode(&self, flood_mode: FloodMode) {
+        *self.flood_mode.lock().await = flood_mode;
+    }
+
+    /// Returns the current `FloodMode`.
+    pub async fn get_flood_mode(&self) -> FloodMode {
+        *self.flood_mode.lock().await
+    }
+
+    /// Enables or disables on-demand route discovery.
+    ///
+    /// When enabled, packages for a junction with no known route are queued while a
+    /// route request is flooded, and sent along the discovered route once the reply
+    /// arrives. If no reply arrives in time the queued packages are held and flooded
+    /// when store-and-forward is on, and reported as `DestinationUnreachable` otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - `true` to discover routes before sending, `false` to flood immediately.
+    pub fn set_route_discovery(&self, enabled: bool) {
+        self.route_discovery_enabled
+            .store(enabled, Ordering::SeqCst);
+    }
+
+    /// Sets how the route table is populated.
+    ///
+    /// In `RoutingMode::DistanceVector` the junction periodically advertises its
+    /// reachable junctions to every known junction, and learns routes from the
+    /// advertisements it receives, in addition to learning from traffic.
+    ///
+    /// # Arguments
+    ///
+    /// * `routing_mode` - The `RoutingMode` to use.
+    pub async fn set_routing_mode(&self, routing_mode: RoutingMode) {
+        *self.routing_mode.lock().await = routing_mode;
+        *self.next_advertisement.lock().await = Instant::now();
+    }
+
+    /// Returns the current `RoutingMode`.
+    pub async fn get_routing_mode(&self) -> RoutingMode {
+        *self.routing_mode.lock().await
+    }
+
+    /// Sets how traffic is spread across the routes available to a destination.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The `MultipathPolicy` to use.
+    pub async fn set_multipath_policy(&self, policy: MultipathPolicy) {
+        self.multipath.lock().await.set_policy(policy);
+    }
+
+    /// Returns the current `MultipathPolicy`.
+    pub async fn get_multipath_policy(&self) -> MultipathPolicy {
+        self.multipath.lock().await.policy()
+    }
+
+    /// Starts discovering a route to a junction by flooding a route request.
+    ///
+    /// Does nothing if a discovery for the junction is already in progress.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The `JunctionId` to discover a route to.
+    pub async fn discover_route(&self, junction_id: &JunctionId) {
+        let is_new = self.route_discovery.lock().await.start(junction_id);
+        if is_new {
+            self.send_route_request(junction_id).await;
+        }
+    }
+
+    /// Returns `true` if a route discovery to the junction is in progress.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The `JunctionId` of the junction.
+    pub async fn is_discovering_route(&self, junction_id: &JunctionId) -> bool {
+        self.route_discovery.lock().await.is_pending(junction_id)
+    }
+
+    /// Waits for a notification that there are items in the received queue and returns the JSON packet.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<JsonPacket>` - An optional JSON packet if available.
+    pub async fn wait_for_package(&self) -> Option<JsonPacket> {
+        self.receive_notify.notified().await;
+        let mut queue = self.received_queue.lock().await;
+        self.pop_unexpired(&mut queue)
+    }
+
+    /// Removes the oldest received packet that has not expired, dropping expired ones on the way.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - The locked received queue.
+    fn pop_unexpired(
+        &self,
+        queue: &mut VecDeque<(JsonPacket, Option<SystemTime>)>,
+    ) -> Option<JsonPacket> {
+        while let Some((packet, expires_at)) = queue.pop_front() {
+            if expires_at.is_some_and(|deadline| deadline <= SystemTime::now()) {
+                self.expired_package_count.fetch_add(1, Ordering::SeqCst);
+                continue;
+            }
+            return Some(packet);
+        }
+        None
+    }
+
+    /// Updates the state of the `SlowJunction` by processing received packets and sending queued JSON values.
+    ///
+    /// Whichever comes first cancels the others, so only cancellation-safe work is raced:
+    /// a received package is processed after the race is over.
+    ///
+    /// # Arguments
+    ///
+    /// * `next_tick` - The `Instant` at which periodic maintenance is next due.
+    async fn update2(&self, next_tick: Instant) {
+        tokio::select! {
+            _= self.pump_send() => {}
+            received = self.read_package() => {
+                if let Some((slow_package, sender_addr)) = received {
+                    self.on_package_received(slow_package, sender_addr).await;
+                }
+            }
+            _ = tokio::time::sleep_until(next_tick) => {}
+        }
+    }
+
+    /// Runs the main loop of the `SlowJunction`, periodically calling `update2` and `maintain`.
+    async fn run(&self) {
+        let mut next_tick = Instant::now() + MAINTENANCE_INTERVAL;
+        while !self.terminate.load(Ordering::SeqCst) {
+            self.update2(next_tick).await;
+            if Instant::now() >= next_tick {
+                self.maintain().await;
+                next_tick = Instant::now() + MAINTENANCE_INTERVAL;
+            }
+        }
+    }
+
+    /// Performs periodic maintenance, such as expiring route discoveries that got no reply.
+    async fn maintain(&self) {
+        if let RoutingMode::DistanceVector { interval } = *self.routing_mode.lock().await {
+            self.maintain_distance_vector(interval).await;
+        }
+
+        let expired = self
+            .route_discovery
+            .lock()
+            .await
+            .expire(ROUTE_DISCOVERY_TIMEOUT);
+
+        for (junction_id, packages) in expired {
+            self.log(&format!("Route discovery to {} timed out", junction_id));
+            for package in packages {
+                if self.drop_if_expired(&package) {
+                    continue;
+                }
+                // Without a store to hold it until a route appears, the package is given up on
+                if !self.hold_for_recipient(&package).await {
+                    self.report_delivery_error(DeliveryErrorKind::DestinationUnreachable, &package)
+                        .await;
+                    continue;
+                }
+                self.send_to_known_junctions(package, None).await;
+            }
+        }
+
+        self.release_routable().await;
+
+        let released = self.reorder_buffer.lock().await.take_expired();
+        for (package, sender_addr) in released {
+            self.deliver_json(package, sender_addr).await;
+        }
+
+        self.streams.lock().await.tick();
+
+        let retries = self.acks.lock().await.take_due();
+        for mut pending in retries {
+            let package_id = self.next_package_id().await;
+            pending.package_mut().set_retransmit(package_id);
+            let package = pending.package().clone();
+            self.acks.lock().await.retry(pending);
+            let mut queue = self.send_queue.lock().await;
+            queue.push(package.priority(), package);
+            self.send_notify.notify_one();
+        }
+    }
+
+    /// Updates the known junctions by adding the sender address and sender ID.
+    ///
+    /// Routes are learned from every copy of a package, including duplicates.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - A reference to the `SlowPackage` that was received.
+    /// * `sender_addr` - The `SocketAddr` of the sender to be added.
+    async fn update_route_table(&self, package: &SlowPackage, sender_addr: SocketAddr) {
+        let mut known_junctions = self.known_junctions.lock().await;
+        known_junctions.insert(sender_addr);
+
+        let junction_id = package.sender_id();
+        let hop_count = package.hop_count();
+        let package_id = package.package_id();
+        let time = 0.0;
+
+        let mut route_table = self.route_table.lock().await;
+        route_table.update_route(junction_id, sender_addr, hop_count, time, package_id);
+    }
+
+    /// Handles a received package by forwarding it and updating the known junctions and received queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - A `SlowPackage` that was received.
+    /// * `sender_addr` - The `SocketAddr` of the sender.
+    async fn on_package_received(&self, package: SlowPackage, sender_addr: SocketAddr) {
+        let package_type = package.package_type();
+
+        if package_type == Ok(PackageType::Hello) {
+            self.on_hello_received(package, sender_addr).await;
+            return;
+        }
+
+        if let Some(Err(_)) = self.peer_protocols.lock().await.get(&sender_addr) {
+            self.log(&format!(
+                "Dropping package from refused peer {}",
+                sender_addr
+            ));
+            return;
+        }
+
+        if package_type == Ok(PackageType::RouteAdvertisement) {
+            self.on_route_advertisement_received(package, sender_addr)
+                .await;
+            return;
+        }
+
+        // Update the route table with the sender address.
+        self.update_route_table(&package, sender_addr).await;
+
+        // A package already seen, including an echo of our own, is neither delivered
+        // nor re-broadcast again.
+        let is_new = self
+            .seen_packages
+            .lock()
+            .await
+            .insert(package.sender_id(), package.package_id());
+        if !is_new {
+            self.duplicate_package_count.fetch_add(1, Ordering::SeqCst);
+            return;
+        }
+
+        // Increment unique_package_count for each non-rejected package received.
+        self.unique_package_count.fetch_add(1, Ordering::SeqCst);
+
+        if self.drop_if_expired(&package) {
+            return;
+        }
+
+        if package_type == Ok(PackageType::Ack) {
+            self.discard_held(&package).await;
+        }
+
+        if *package.recipient_id() != self.junction_id {
+            self.forward(package, sender_addr).await;
+            return;
+        }
+
+        let ack = package
+            .is_ack_requested()
+            .then(|| (package.sender_id().clone(), package.first_package_id()));
+
+        // A resent copy of a delivered package only needs to be acked again
+        if let Some((sender_id, package_id)) = &ack
+            && self
+                .delivered_packages
+                .lock()
+                .await
+                .contains(sender_id, *package_id)
+        {
+            self.duplicate_package_count.fetch_add(1, Ordering::SeqCst);
+            self.send_ack(sender_id.clone(), *package_id).await;
+            return;
+        }
+
+        match package_type {
+            Ok(PackageType::Ack) => {
+                if let Some(package_id) = package.acked_package_id() {
+                    self.acks.lock().await.acknowledge(package_id);
+                }
+            }
+            Ok(PackageType::RouteRequest) => {
+                self.on_route_request_received(package).await;
+            }
+            Ok(PackageType::RouteReply) => {
+                self.on_route_reply_received(package).await;
+            }
+            Ok(PackageType::Traceroute) => {
+                self.on_traceroute_received(package).await;
+            }
+            Ok(PackageType::TracerouteReply) => {
+                self.on_traceroute_reply_received(package).await;
+            }
+            Ok(PackageType::RpcRequest) => {
+                self.on_rpc_request_received(package).await;
+            }
+            Ok(PackageType::RpcResponse) => {
+                self.on_rpc_response_received(package).await;
+            }
+            Ok(PackageType::DeliveryError) => {
+                self.on_error_received(package).await;
+            }
+            Ok(PackageType::Stream) => {
+                if let Some(segment) = StreamSegment::unpack(&package.payload) {
+                    let opened = self
+                        .streams
+                        .lock()
+                        .await
+                        .handle(package.sender_id(), segment);
+                    if let Some(stream) = opened {
+                        self.on_stream_opened(stream).await;
+                    }
+                }
+            }
+            Ok(PackageType::Transfer) => {
+                self.transfers
+                    .lock()
+                    .await
+                    .handle(package.sender_id(), &package.payload);
+            }
+            Ok(PackageType::Ping) => {
+                self.on_ping_received(package).await;
+            }
+            Ok(PackageType::Pong) => {
+                self.on_pong_received().await;
+            }
+            Ok(PackageType::Json) => {
+                if let Some(sequence) = package.sequence() {
+                    let sender_id = package.sender_id().clone();
+                    let released = self.reorder_buffer.lock().await.push(
+                        &sender_id,
+                        sequence,
+                        (package, sender_addr),
+                    );
+                    for (package, sender_addr) in released {
+                        self.deliver_json(package, sender_addr).await;
+                    }
+                } else if !self.deliver_json(package, sender_addr).await {
+                    return;
+                }
+            }
+            Ok(PackageType::Bin) => {}
+            _ => {}
+        }
+
+        if let Some((sender_id, package_id)) = ack {
+            self.delivered_packages
+                .lock()
+                .await
+                .insert(&sender_id, package_id);
+            self.send_ack(sender_id, package_id).await;
+        }
+    }
+
+    /// Queues an ack for a package that asked for one.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - The `JunctionId` of the junction that sent the package.
+    /// * `package_id` - The ID of the first transmission of the package.
+    async fn send_ack(&self, recipient_id: JunctionId, package_id: u32) {
+        let mut queue = self.send_queue.lock().await;
+        let ack = SlowPackage::new_ack(recipient_id, self.junction_id.clone(), package_id);
+        queue.push(ack.priority(), ack);
+        self.send_notify.notify_one();
+    }
+
+    /// Adds the JSON payload of a package for this junction to the received queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - A `SlowPackage` carrying a JSON payload.
+    /// * `sender_addr` - The `SocketAddr` the package was received from.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - `false` if the received queue was full and the package was dropped.
+    async fn deliver_json(&self, package: SlowPackage, sender_addr: SocketAddr) -> bool {
+        // Ordered packages may have expired while waiting in the reorder buffer
+        if self.drop_if_expired(&package) {
+            return true;
+        }
+
+        let json = match package.json_payload() {
+            Some(json) => json,
+            None => return true,
+        };
+
+        let mut queue = self.received_queue.lock().await;
+        if queue.len() >= self.receive_queue_capacity.load(Ordering::SeqCst) {
+            drop(queue);
+            self.report_delivery_error(DeliveryErrorKind::QueueFull, &package)
+                .await;
+            return false;
+        }
+
+        queue.push_back((
+            JsonPacket {
+                addr: sender_addr,
+                json,
+            },
+            package.expires_at(),
+        ));
+        self.receive_notify.notify_one();
+        true
+    }
+
+    /// Forwards a `SlowPackage` along the best route, or re-broadcasts it if no route is known.
+    ///
+    /// Only packages seen for the first time get here, so each is re-broadcast at most once.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - A `SlowPackage` to be forwarded.
+    /// * `sender_addr` - The `SocketAddr` of the sender.
+    async fn forward(&self, mut package: SlowPackage, sender_addr: SocketAddr) {
+        if package.increment_hops() >= 128 {
+            self.report_delivery_error(DeliveryErrorKind::HopLimitExceeded, &package)
+                .await;
+            return;
+        }
+        if package.package_type() == Ok(PackageType::Traceroute) {
+            TracerouteRecord::record_hop(&mut package, &self.junction_id);
+        }
+        if self.send_to_best_route(&package).await {
+            return;
+        }
+
+        self.hold_for_recipient(&package).await;
+        self.send_to_selected_junctions(package, sender_addr).await;
+    }
+
+    /// Sends a `SlowPackage` to the known junctions chosen by the current `FloodMode`.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` to be sent.
+    /// * `exclude_addr` - The `SocketAddr` of the sender to be excluded.
+    async fn send_to_selected_junctions(&self, package: SlowPackage, exclude_addr: SocketAddr) {
+        let flood_mode = *self.flood_mode.lock().await;
+        let candidates: Vec<SocketAddr> = {
+            let known_junctions = self.known_junctions.lock().await;
+            known_junctions
+                .iter()
+                .filter(|&&addr| addr != exclude_addr)
+                .copied()
+                .collect()
+        };
+
+        for addr in flood_mode.select(candidates) {
+            self.connection
+                .send_package(&package, &addr)
+                .await
+                .expect("Failed to send package");
+        }
+    }
+
+    /// Sends a `SlowPackage` to all known junctions except the specified sender.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` to be sent.
+    /// * `exclude_addr` - The `SocketAddr` of the sender to be excluded.
+    pub async fn send_to_known_junctions(
+        &self,
+        package: SlowPackage,
+        exclude_addr: Option<SocketAddr>,
+    ) {
+        let known_junctions = self.known_junctions.lock().await;
+        for addr in known_junctions.iter() {
+            if Some(*addr) != exclude_addr {
+                self.connection
+                    .send_package(&package, addr)
+                    .await
+                    .expect("Failed to send package");
+            }
+        }
+    }
+
+    /// Waits for a package via `connection.recv_package()` and returns it.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<(SlowPackage, SocketAddr)>` - An optional tuple containing the received package and sender address.
+    pub async fn read_package(&self) -> Option<(SlowPackage, SocketAddr)> {
+        self.connection.receive_package().await
+    }
+
+    /// Sends all queued packages to known junctions, excluding the address `0.0.0.0:0`.
+    ///
+    /// Packages are taken one at a time in `PriorityWeights` order, so packages queued
+    /// while the queue drains can overtake lower priority ones.
+    ///
+    /// The drain may be cancelled at any point. The package being sent is kept in
+    /// `sending` until it has been dispatched, and the next drain starts with it
+    /// without waiting for another notification.
+    async fn pump_send(&self) {
+        let is_idle =
+            self.sending.lock().await.is_none() && self.send_queue.lock().await.is_empty();
+        if is_idle {
+            self.send_notify.notified().await;
+        }
+
+        loop {
+            let package = {
+                let mut sending = self.sending.lock().await;
+                if sending.is_none() {
+                    *sending = self.send_queue.lock().await.pop();
+                }
+                if let Some(package) = sending.as_mut()
+                    && package.package_id() == 0
+                {
+                    let package_id = self.next_package_id().await;
+                    package.set_package_id(package_id);
+                }
+                sending.clone()
+            };
+            let package = match package {
+                Some(package) => package,
+                None => break,
+            };
+
+            let package_id = package.package_id();
+            let is_critical = self.critical_packages.lock().await.contains(&package_id);
+            if is_critical {
+                self.dispatch_critical(package).await;
+            } else {
+                self.dispatch(package).await;
+            }
+
+            self.critical_packages.lock().await.remove(&package_id);
+            *self.sending.lock().await = None;
+        }
+    }
+
+    /// Sends a package to the next hops of the two best routes to its recipient at once.
+    ///
+    /// With fewer than two known routes the package is dispatched like any other.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` to be sent, with its package ID already set.
+    async fn dispatch_critical(&self, package: SlowPackage) {
+        if self.drop_if_expired(&package) {
+            return;
+        }
+
+        let routes = self
+            .route_table
+            .lock()
+            .await
+            .get_routes(package.recipient_id());
+        if routes.len() < 2 {
+            self.dispatch(package).await;
+            return;
+        }
+
+        for (addr, _) in routes.iter().take(2) {
+            self.connection
+                .send_package(&package, addr)
+                .await
+                .expect("Failed to send package");
+        }
+    }
+
+    /// Sends a package that already has its package ID.
+    ///
+    /// The package goes along the best route if one is known. Otherwise it waits on a
+    /// route discovery when discovery is enabled, or is sent to all known junctions.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` to be sent.
+    async fn dispatch(&self, package: SlowPackage) {
+        if self.drop_if_expired(&package) {
+            return;
+        }
+
+        if self.send_to_best_route(&package).await {
+            return;
+        }
+
+        if self.route_discovery_enabled.load(Ordering::SeqCst)
+            && package.package_type() != Ok(PackageType::RouteRequest)
+            && package.package_type() != Ok(PackageType::RouteReply)
+        {
+            let recipient_id = package.recipient_id().clone();
+            let is_new = self.route_discovery.lock().await.queue(package);
+            if is_new {
+                self.send_route_request(&recipient_id).await;
+            }
+            return;
+        }
+
+        self.hold_for_recipient(&package).await;
+        self.send_to_known_junctions(package, None).await;
+    }
+
+    /// Sends a pong message to a specific `SocketAddr`.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - A u16 representing the recipient ID.
+    pub async fn pong(&self, recipient_id: &JunctionId) {
+        let mut queue = self.send_queue.lock().await;
+        let package = SlowPackage::new_pong(recipient_id.clone(), self.junction_id.clone());
+        queue.push(package.priority(), package);
+        self.send_notify.notify_one();
+    }
+
+    /// Sends a ping message to a specific `SocketAddr`.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The target junction id as a &str.
+    pub async fn ping(&self, junction_id: &JunctionId) {
+        let mut queue = self.send_queue.lock().await;
+        let package = SlowPackage::new_ping(junction_id.clone(), self.junction_id.clone());
+        queue.push(package.priority(), package);
+        self.send_notify.notify_one();
+    }
+
+    /// Traces the path packages take to a junction.
+    ///
+    /// A traceroute package is sent to the junction and every junction that forwards
+    /// it records itself along with a timestamp. The junction echoes the record back.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The `JunctionId` of the junction to trace the path to.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Vec<TracerouteHop>>` - The junctions on the path, starting with this one and
+    ///   ending with the traced junction, or `None` if no reply arrived within `TRACEROUTE_TIMEOUT`.
+    pub async fn traceroute(&self, junction_id: &JunctionId) -> Option<Vec<TracerouteHop>> {
+        let trace_id = self.trace_count.fetch_add(1, Ordering::SeqCst) + 1;
+        let (sender, receiver) = oneshot::channel();
+        self.pending_traceroutes
+            .lock()
+            .await
+            .insert(trace_id, sender);
+
+        {
+            let record = TracerouteRecord::new(trace_id, &self.junction_id);
+            let package = SlowPackage::new_traceroute(
+                junction_id.clone(),
+                self.junction_id.clone(),
+                &record.pack(),
+            );
+            let mut queue = self.send_queue.lock().await;
+            queue.push(package.priority(), package);
+            self.send_notify.notify_one();
+        }
+
+        let result = tokio::time::timeout(TRACEROUTE_TIMEOUT, receiver).await;
+        self.pending_traceroutes.lock().await.remove(&trace_id);
+        result.ok()?.ok()
+    }
+
+    /// Returns the current value of the pong counter.
+    ///
+    /// # Returns
+    ///
+    /// * `u32` - The current value of the pong counter.
+    pub async fn get_pong_counter(&self) -> u32 {
+        self.pong_counter.load(Ordering::SeqCst)
+    }
+
+    /// Increments the pong counter when a pong message is received.
+    async fn on_pong_received(&self) {
+        self.pong_counter.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Handles a received ping message by sending a pong response.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` that was received.
+    async fn on_ping_received(&self, package: SlowPackage) {
+        let sender_id = package.sender_id();
+        self.pong(sender_id).await;
+    }
+
+    /// Handles a received route request by replying along the reverse route to the requester.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` that was received.
+    async fn on_route_request_received(&self, package: SlowPackage) {
+        let mut queue = self.send_queue.lock().await;
+        let reply =
+            SlowPackage::new_route_reply(package.sender_id().clone(), self.junction_id.clone());
+        queue.push(reply.priority(), reply);
+        self.send_notify.notify_one();
+    }
+
+    /// Handles a received route reply by sending the packages that were waiting on the route.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` that was received.
+    async fn on_route_reply_received(&self, package: SlowPackage) {
+        let packages = self
+            .route_discovery
+            .lock()
+            .await
+            .resolve(package.sender_id());
+
+        for package in packages {
+            if self.drop_if_expired(&package) {
+                continue;
+            }
+            if !self.send_to_best_route(&package).await {
+                self.send_to_known_junctions(package, None).await;
+            }
+        }
+    }
+
+    /// Handles a received traceroute by recording this junction and echoing the record to the origin.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` that was received.
+    async fn on_traceroute_received(&self, mut package: SlowPackage) {
+        if !TracerouteRecord::record_hop(&mut package, &self.junction_id) {
+            return;
+        }
+
+        let mut queue = self.send_queue.lock().await;
+        let reply = SlowPackage::new_traceroute_reply(
+            package.sender_id().clone(),
+            self.junction_id.clone(),
+            &package.payload,
+        );
+        queue.push(reply.priority(), reply);
+        self.send_notify.notify_one();
+    }
+
+    /// Handles a received traceroute reply by completing the traceroute waiting on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` that was received.
+    async fn on_traceroute_reply_received(&self, package: SlowPackage) {
+        let record = match TracerouteRecord::unpack(&package.payload) {
+            Some(record) => record,
+            None => return,
+        };
+
+        let pending = self
+            .pending_traceroutes
+            .lock()
+            .await
+            .remove(&record.trace_id);
+        if let Some(sender) = pending {
+            let _ = sender.send(record.hops);
+        }
+    }
+
+    /// Handles a received delivery error package by queuing the delivery error it reports.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` that was received.
+    async fn on_error_received(&self, package: SlowPackage) {
+        if let Some(error) = DeliveryError::from_package(&package) {
+            self.log(&format!(
+                "Package {} to {} was dropped by {}: {:?}",
+                error.package_id, error.recipient_id, error.reporter_id, error.kind
+            ));
+            self.delivery_errors.lock().await.push_back(error);
+        }
+    }
+
+    /// Handles a received RPC request by running its handler and sending the response to the caller.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` that was received.
+    async fn on_rpc_request_received(&self, package: SlowPackage) {
+        let request = match RpcRequest::unpack(&package.payload) {
+            Some(request) => request,
+            None => return,
+        };
+
+        let handler = self.rpc_handlers.lock().await.get(&request.method);
+        let response = rpc::serve(handler, request);
+
+        let mut queue = self.send_queue.lock().await;
+        let reply = SlowPackage::new_rpc_response(
+            package.sender_id().clone(),
+            self.junction_id.clone(),
+            &response.pack(),
+        );
+        queue.push(reply.priority(), reply);
+        self.send_notify.notify_one();
+    }
+
+    /// Handles a received RPC response by completing the call waiting on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` that was received.
+    async fn on_rpc_response_received(&self, package: SlowPackage) {
+        if let Some(response) = RpcResponse::unpack(&package.payload) {
+            self.rpc_calls.lock().await.complete(response);
+        }
+    }
+
+    /// Hands a stream opened by another junction to its tunnel, or to `accept_stream`.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The new stream.
+    async fn on_stream_opened(&self, stream: SlowStream) {
+        let service = stream.service();
+        let tunnel = match tunnel::tunnel_name(&service) {
+            Some(tunnel) => tunnel,
+            None => {
+                let _ = self.incoming_sender.send(stream);
+                return;
+            }
+        };
+
+        match self.tunnels.lock().await.get(tunnel) {
+            Some(target) => {
+                tokio::spawn(tunnel::serve(stream, target));
+            }
+            None => {
+                // Dropping the stream closes it
+                self.log(&format!("No tunnel named {}", tunnel));
+            }
+        }
+    }
+
+    /// Starts the task that sends the segments produced by this junction's streams.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction` - A weak reference to the junction, so the task ends with it.
+    /// * `segments` - The channel the `StreamMux` hands segments to.
+    fn start_stream_sender(
+        junction: Weak<Self>,
+        mut segments: mpsc::UnboundedReceiver<OutgoingSegment>,
+    ) {
+        tokio::spawn(async move {
+            while let Some((recipient_id, segment)) = segments.recv().await {
+                let junction = match junction.upgrade() {
+                    Some(junction) => junction,
+                    None => break,
+                };
+
+                let mut queue = junction.send_queue.lock().await;
+                let package = SlowPackage::new_stream_segment(
+                    recipient_id,
+                    junction.junction_id.clone(),
+                    &segment.pack(),
+                );
+                queue.push(package.priority(), package);
+                junction.send_notify.notify_one();
+            }
+        });
+    }
+
+    /// Starts the task that sends the packages produced by this junction's transfers.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction` - A weak reference to the junction, so the task ends with it.
+    /// * `packages` - The channel `Transfers` hands packages to.
+    fn start_transfer_sender(
+        junction: Weak<Self>,
+        mut packages: mpsc::UnboundedReceiver<SlowPackage>,
+    ) {
+        tokio::spawn(async move {
+            while let Some(package) = packages.recv().await {
+                let junction = match junction.upgrade() {
+                    Some(junction) => junction,
+                    None => break,
+                };
+
+                junction
+                    .send_queue
+                    .lock()
+                    .await
+                    .push(package.priority(), package);
+                junction.send_notify.notify_one();
+            }
+        });
+    }
+
+    /// Reports a dropped package back to its sender.
+    ///
+    /// Nothing is sent if the package is not reportable, such as a delivery error package itself.
+    /// A package this junction sent is reported to it directly.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - Why the package was dropped.
+    /// * `package` - The dropped `SlowPackage`.
+    async fn report_delivery_error(&self, kind: DeliveryErrorKind, package: &SlowPackage) {
+        if let Some(error) = DeliveryError::report(kind, package, &self.junction_id) {
+            if *error.recipient_id() == self.junction_id {
+                self.on_error_received(error).await;
+                return;
+            }
+            let mut queue = self.send_queue.lock().await;
+            queue.push(error.priority(), error);
+            self.send_notify.notify_one();
+        }
+    }
+
+    /// Floods a route request for a junction to all known junctions.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The `JunctionId` to discover a route to.
+    async fn send_route_request(&self, junction_id: &JunctionId) {
+        let mut package =
+            SlowPackage::new_route_request(junction_id.clone(), self.junction_id.clone());
+        let package_id = self.next_package_id().await;
+        package.set_package_id(package_id);
+
+        self.send_to_known_junctions(package, None).await;
+    }
+
+    /// Allocates the next package ID for a package sent by this junction.
+    ///
+    /// The ID is remembered as seen so echoes of the package from the mesh are not re-broadcast.
+    ///
+    /// # Returns
+    ///
+    /// * `u32` - The package ID.
+    async fn next_package_id(&self) -> u32 {
+        let package_id = self.sent_package_count.fetch_add(1, Ordering::SeqCst) + 1;
+        self.seen_packages
+            .lock()
+            .await
+            .insert(&self.junction_id, package_id);
+        package_id
+    }
+
+    /// Sends distance-vector advertisements when due and expires routes that stopped being advertised.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - The time between advertisements.
+    async fn maintain_distance_vector(&self, interval: Duration) {
+        {
+            let mut next_advertisement = self.next_advertisement.lock().await;
+            if Instant::now() < *next_advertisement {
+                return;
+            }
+            *next_advertisement = Instant::now() + interval;
+        }
+
+        let routes = {
+            let mut route_table = self.route_table.lock().await;
+            route_table.expire_routes(interval * ROUTE_EXPIRY_INTERVALS);
+            route_table.get_best_routes()
+        };
+        let routes: Vec<_> = routes
+            .into_iter()
+            .filter(|(junction_id, _, _)| *junction_id != self.junction_id)
+            .collect();
+
+        let neighbors: Vec<SocketAddr> =
+            self.known_junctions.lock().await.iter().copied().collect();
+        for neighbor in neighbors {
+            for advertisement in RouteAdvertisement::for_neighbor(&routes, neighbor) {
+                let package = SlowPackage::new_route_advertisement(
+                    self.junction_id.clone(),
+                    &advertisement.pack(),
+                );
+                self.connection
+                    .send_package(&package, &neighbor)
+                    .await
+                    .expect("Failed to send route advertisement");
+            }
+        }
+    }
+
+    /// Handles a received route advertisement by updating the route table.
+    ///
+    /// The advertising neighbor is recorded as directly reachable, every advertised
+    /// junction is recorded as reachable through the neighbor with one more hop, and
+    /// junctions advertised as unreachable are withdrawn.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` that was received.
+    /// * `sender_addr` - The `SocketAddr` of the sender.
+    async fn on_route_advertisement_received(&self, package: SlowPackage, sender_addr: SocketAddr) {
+        self.known_junctions.lock().await.insert(sender_addr);
+
+        let advertisement = match RouteAdvertisement::unpack(&package.payload) {
+            Some(advertisement) => advertisement,
+            None => return,
+        };
+
+        let mut route_table = self.route_table.lock().await;
+        route_table.set_route(package.sender_id(), sender_addr, 0);
+
+        for (junction_id, hops) in advertisement.entries {
+            if junction_id == self.junction_id {
+                continue;
+            }
+            if hops.saturating_add(1) >= INFINITE_HOPS {
+                route_table.remove_route(&junction_id, &sender_addr);
+            } else {
+                route_table.set_route(&junction_id, sender_addr, hops + 1);
+            }
+        }
+    }
+
+    /// Handles a received hello message by sending a hello response and negotiating
+    /// the protocol with the sender.
+    ///
+    /// The response is sent even to an incompatible sender, so it sees the mismatch too.
+    /// An incompatible sender is not added to the known junctions, and the packages it
+    /// sends are dropped until a later hello agrees.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The hello `SlowPackage`.
+    /// * `sender_addr` - The `SocketAddr` of the sender.
+    async fn on_hello_received(&self, package: SlowPackage, sender_addr: SocketAddr) {
+        if package.package_id() == 0 {
+            self.send_hello_response(sender_addr).await;
+        }
+
+        let offer = *self.protocol_offer.lock().await;
+        let result = package
+            .protocol_offer()
+            .and_then(|peer_offer| offer.negotiate(&peer_offer));
+        let agreed = result.is_ok();
+        if let Err(e) = &result {
+            self.log(&format!(
+                "Refusing {} at {}: {}",
+                package.sender_id(),
+                sender_addr,
+                e
+            ));
+        }
+        self.peer_protocols.lock().await.insert(sender_addr, result);
+
+        if agreed {
+            self.known_junctions.lock().await.insert(sender_addr);
+            self.release_stored(package.sender_id(), sender_addr).await;
+        } else {
+            self.known_junctions.lock().await.remove(&sender_addr);
+        }
+    }
+
+    /// Holds a copy of a package that has no known route, if store-and-forward is on.
+    ///
+    /// Every package flooded for lack of a route is held, even when there are junctions to
+    /// flood it to. The held copy is sent once a hello or a route for the recipient arrives,
+    /// and dropped once the recipient acks the package. Relays that passed the flooded copy
+    /// on drop the held one as a duplicate.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` about to be flooded.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - `true` if the package is now held.
+    async fn hold_for_recipient(&self, package: &SlowPackage) -> bool {
+        if let Some(store) = self.store.lock().await.as_mut()
+            && store.hold(package.clone())
+        {
+            self.log(&format!("Holding package for {}", package.recipient_id()));
+            return true;
+        }
+        false
+    }
+
+    /// Drops the held copies of a package that an ack shows was delivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `ack` - An `Ack` package on its way back to the sender of the acked package.
+    async fn discard_held(&self, ack: &SlowPackage) {
+        let package_id = match ack.acked_package_id() {
+            Some(package_id) => package_id,
+            None => return,
+        };
+        if let Some(store) = self.store.lock().await.as_mut()
+            && store.discard(ack.recipient_id(), package_id)
+        {
+            self.log(&format!(
+                "Dropping held package {} from {}, which was delivered",
+                package_id,
+                ack.recipient_id()
+            ));
+        }
+    }
+
+    /// Sends the packages held for a junction that was just seen.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The `JunctionId` of the junction.
+    /// * `addr` - The `SocketAddr` the junction was seen at.
+    async fn release_stored(&self, junction_id: &JunctionId, addr: SocketAddr) {
+        let packages = match self.store.lock().await.as_mut() {
+            Some(store) => store.take(junction_id),
+            None => return,
+        };
+
+        for package in packages {
+            if self.drop_if_expired(&package) {
+                continue;
+            }
+            self.connection
+                .send_package(&package, &addr)
+                .await
+                .expect("Failed to send package");
+        }
+    }
+
+    /// Counts a package as expired if its deadline has passed.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` about to be sent, forwarded or delivered.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - `true` if the package expired and must be dropped.
+    fn drop_if_expired(&self, package: &SlowPackage) -> bool {
+        if !package.is_expired() {
+            return false;
+        }
+
+        self.log(&format!(
+            "Dropping expired package {} from {} for {}",
+            package.package_id(),
+            package.sender_id(),
+            package.recipient_id()
+        ));
+        self.expired_package_count.fetch_add(1, Ordering::SeqCst);
+        true
+    }
+
+    /// Drops held packages that are too old and sends those whose recipient now has a route.
+    async fn release_routable(&self) {
+        let (expired, recipients) = match self.store.lock().await.as_mut() {
+            Some(store) => (store.expire(), store.recipients()),
+            None => return,
+        };
+
+        for package in expired {
+            self.log(&format!(
+                "Held package for {} expired undelivered",
+                package.recipient_id()
+            ));
+            self.report_delivery_error(DeliveryErrorKind::DestinationUnreachable, &package)
+                .await;
+        }
+
+        for recipient_id in recipients {
+            if let Some(addr) = self.get_best_route(&recipient_id).await {
+                self.release_stored(&recipient_id, addr).await;
+            }
+        }
+    }
+
+    /// Gets the best route to a junction.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The `JunctionId` of the junction.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<SocketAddr>` - The best route to the junction.
+    pub async fn get_best_route(&self, junction_id: &JunctionId) -> Option<SocketAddr> {
+        let route_table = self.route_table.lock().await;
+        route_table.get_best_route(junction_id)
+    }
+
+    /// Sends a `SlowPackage` to the best route available.
+    ///
+    /// When a `MultipathPolicy` other than `Single` is set, the route is picked from
+    /// all routes with a near-best hop count.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The `SlowPackage` to be sent.
+    pub async fn send_to_best_route(&self, package: &SlowPackage) -> bool {
+        let candidates = {
+            let route_table = self.route_table.lock().await;
+            route_table.get_multipath_routes(package.recipient_id())
+        };
+        let best_route = self.multipath.lock().await.select(package, &candidates);
+
+        if let Some(best_route) = best_route {
             self.connection
                 .send_package(package, &best_route)
                 .await
@@ -466,6 +2028,37 @@ impl SlowJunction {
         &self.junction_id
     }
 
+    /// Sets the protocol versions and features offered in hellos.
+    ///
+    /// # Arguments
+    ///
+    /// * `offer` - The `ProtocolOffer` to make.
+    pub async fn set_protocol_offer(&self, offer: ProtocolOffer) {
+        *self.protocol_offer.lock().await = offer;
+    }
+
+    /// Returns the current `ProtocolOffer`.
+    pub async fn get_protocol_offer(&self) -> ProtocolOffer {
+        *self.protocol_offer.lock().await
+    }
+
+    /// Returns the outcome of the last hello exchanged with a neighbor.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The `SocketAddr` of the neighbor.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Result<Agreement, NegotiationError>>` - The `Agreement` reached, why the neighbor
+    ///   was refused, or `None` if no hello has been exchanged with it.
+    pub async fn get_peer_protocol(
+        &self,
+        addr: SocketAddr,
+    ) -> Option<Result<Agreement, NegotiationError>> {
+        self.peer_protocols.lock().await.get(&addr).cloned()
+    }
+
     /// Joins a junction by sending a hello message to the specified address.
     ///
     /// # Arguments
@@ -481,7 +2074,8 @@ impl SlowJunction {
     ///
     /// * `addr` - The `SocketAddr` to send the hello message to.
     async fn send_hello(&self, addr: SocketAddr) {
-        let package = SlowPackage::new_hello(0, self.junction_id.clone());
+        let offer = *self.protocol_offer.lock().await;
+        let package = SlowPackage::new_hello(0, self.junction_id.clone(), &offer);
         self.connection
             .send_package(&package, &addr)
             .await
@@ -494,7 +2088,8 @@ impl SlowJunction {
     ///
     /// * `addr` - The `SocketAddr` to send the hello message to.
     async fn send_hello_response(&self, addr: SocketAddr) {
-        let package = SlowPackage::new_hello(1, self.junction_id.clone());
+        let offer = *self.protocol_offer.lock().await;
+        let package = SlowPackage::new_hello(1, self.junction_id.clone(), &offer);
         self.connection
             .send_package(&package, &addr)
             .await
@@ -1,7 +1,9 @@
 /// A `JunctionId` represents the unique identifier for a network junction.
 ///
 /// This struct provides methods to create a new junction ID and format it for display.
-#[derive(Clone, Hash, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Clone, Hash, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
 pub struct JunctionId {
     /// The unique identifier for the junction.
     id: String,
@@ -1,8 +1,23 @@
+pub mod ack;
+pub mod capability;
+pub mod delivery;
+pub mod discovery;
+pub mod distance_vector;
+pub mod flood;
 pub mod junction;
 pub mod junction_id;
 pub mod link_packet;
+pub mod multipath;
 pub mod package;
+pub mod priority;
+pub mod reorder;
 pub mod route;
+pub mod rpc;
+pub mod store;
+pub mod stream;
 pub mod tcp;
+pub mod traceroute;
+pub mod transfer;
 pub mod tracker;
+pub mod tunnel;
 pub mod udp;
@@ -164,7 +164,7 @@ impl SlowLinkPayloadPacket {
 
         // Read payload data
         let mut payload = vec![0; payload_size as usize];
-        if let Err(_) = cursor.read_exact(&mut payload) {
+        if cursor.read_exact(&mut payload).is_err() {
             return Err("Failed to read payload data");
         }
 
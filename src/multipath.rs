@@ -0,0 +1,131 @@
+use crate::junction_id::JunctionId;
+use crate::package::SlowPackage;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+//=============================================================================
+// MultipathPolicy
+//=============================================================================
+/// Controls how traffic to a destination is spread over its candidate next hops.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MultipathPolicy {
+    /// Always use the single best next hop.
+    #[default]
+    Single,
+    /// Rotate through the candidate next hops in proportion to their weights.
+    WeightedRoundRobin,
+    /// Pin each flow (sender and recipient pair) to one candidate, chosen by
+    /// hashing the flow and weighting the candidates.
+    FlowHash,
+}
+
+//=============================================================================
+// MultipathSelector
+//=============================================================================
+/// Picks next hops for packages according to a `MultipathPolicy`.
+///
+/// The selector keeps a round-robin counter for each destination so that weighted
+/// round-robin spreads consecutive packages across the candidates.
+pub struct MultipathSelector {
+    /// The policy used to pick next hops.
+    policy: MultipathPolicy,
+
+    /// Round-robin counters keyed by destination junction.
+    counters: HashMap<JunctionId, u64>,
+}
+
+impl MultipathSelector {
+    /// Creates a new `MultipathSelector`.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The policy used to pick next hops.
+    pub fn new(policy: MultipathPolicy) -> Self {
+        MultipathSelector {
+            policy,
+            counters: HashMap::new(),
+        }
+    }
+
+    /// Returns the policy used to pick next hops.
+    pub fn policy(&self) -> MultipathPolicy {
+        self.policy
+    }
+
+    /// Sets the policy used to pick next hops.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The new policy.
+    pub fn set_policy(&mut self, policy: MultipathPolicy) {
+        self.policy = policy;
+        self.counters.clear();
+    }
+
+    /// Picks the next hop for a package from a list of weighted candidates.
+    ///
+    /// The candidates are expected to be ordered best first; `Single` always picks
+    /// the first one. Candidates with a weight of zero are never picked.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The package being sent.
+    /// * `candidates` - The candidate next hops and their weights.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<T>` - The chosen next hop, or `None` if there are no candidates.
+    pub fn select<T: Copy>(&mut self, package: &SlowPackage, candidates: &[(T, u32)]) -> Option<T> {
+        let total_weight: u64 = candidates.iter().map(|&(_, weight)| weight as u64).sum();
+        if total_weight == 0 {
+            return candidates.first().map(|&(candidate, _)| candidate);
+        }
+
+        let position = match self.policy {
+            MultipathPolicy::Single => return candidates.first().map(|&(candidate, _)| candidate),
+            MultipathPolicy::WeightedRoundRobin => {
+                let counter = self
+                    .counters
+                    .entry(package.recipient_id().clone())
+                    .or_insert(0);
+                let position = *counter % total_weight;
+                *counter = counter.wrapping_add(1);
+                position
+            }
+            MultipathPolicy::FlowHash => flow_hash(package) % total_weight,
+        };
+
+        let mut position = position;
+        for &(candidate, weight) in candidates {
+            if position < weight as u64 {
+                return Some(candidate);
+            }
+            position -= weight as u64;
+        }
+
+        None
+    }
+}
+
+impl Default for MultipathSelector {
+    fn default() -> Self {
+        Self::new(MultipathPolicy::default())
+    }
+}
+
+/// Hashes the flow a package belongs to, identified by its sender and recipient.
+///
+/// # Arguments
+///
+/// * `package` - The package to hash.
+///
+/// # Returns
+///
+/// * `u64` - The flow hash.
+pub fn flow_hash(package: &SlowPackage) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    package.sender_id().hash(&mut hasher);
+    package.recipient_id().hash(&mut hasher);
+    hasher.finish()
+}
@@ -1,6 +1,9 @@
+use crate::capability::{NegotiationError, ProtocolOffer};
 use crate::junction::JunctionId;
+use crate::priority::Priority;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // ===========================================================================
 // PackageType
@@ -19,6 +22,17 @@ pub enum PackageType {
     Json,
     Bin,
     Howdy,
+    RouteRequest,
+    RouteReply,
+    RouteAdvertisement,
+    Traceroute,
+    TracerouteReply,
+    DeliveryError,
+    Ack,
+    RpcRequest,
+    RpcResponse,
+    Stream,
+    Transfer,
 }
 
 impl From<PackageType> for u8 {
@@ -41,6 +55,17 @@ impl From<PackageType> for u8 {
             PackageType::Json => 3,
             PackageType::Bin => 4,
             PackageType::Howdy => 5,
+            PackageType::RouteRequest => 6,
+            PackageType::RouteReply => 7,
+            PackageType::RouteAdvertisement => 8,
+            PackageType::Traceroute => 9,
+            PackageType::TracerouteReply => 10,
+            PackageType::DeliveryError => 11,
+            PackageType::Ack => 12,
+            PackageType::RpcRequest => 13,
+            PackageType::RpcResponse => 14,
+            PackageType::Stream => 15,
+            PackageType::Transfer => 16,
         }
     }
 }
@@ -69,6 +94,17 @@ impl TryFrom<u8> for PackageType {
             3 => Ok(PackageType::Json),
             4 => Ok(PackageType::Bin),
             5 => Ok(PackageType::Howdy),
+            6 => Ok(PackageType::RouteRequest),
+            7 => Ok(PackageType::RouteReply),
+            8 => Ok(PackageType::RouteAdvertisement),
+            9 => Ok(PackageType::Traceroute),
+            10 => Ok(PackageType::TracerouteReply),
+            11 => Ok(PackageType::DeliveryError),
+            12 => Ok(PackageType::Ack),
+            13 => Ok(PackageType::RpcRequest),
+            14 => Ok(PackageType::RpcResponse),
+            15 => Ok(PackageType::Stream),
+            16 => Ok(PackageType::Transfer),
             _ => Err(()),
         }
     }
@@ -78,11 +114,29 @@ impl TryFrom<u8> for PackageType {
 // SlowPackageHeader
 // ===========================================================================
 
+/// Header flag asking the recipient to acknowledge the package with an `Ack`.
+pub const FLAG_ACK_REQUESTED: u8 = 0x01;
+
+/// Header flag marking a package for in-order delivery; the header then carries a `sequence`.
+pub const FLAG_ORDERED: u8 = 0x02;
+
+/// Header flag giving a package a lifetime; the header then carries an `expires_at`.
+pub const FLAG_EXPIRES: u8 = 0x04;
+
+/// Header flag bits holding an explicitly set `Priority`; zero means the priority of the package type.
+pub const FLAG_PRIORITY_MASK: u8 = 0x18;
+
+/// The position of the lowest priority bit in the header flags.
+const FLAG_PRIORITY_SHIFT: u8 = 3;
+
+/// Header flag marking a resent package; the header then carries a `first_package_id`.
+pub const FLAG_RETRANSMIT: u8 = 0x20;
+
 /// Represents the header of a SlowPackage.
 ///
 /// The header contains metadata about the package, such as the recipient ID,
 /// sender ID, hop count, and payload size.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SlowPackageHeader {
     /// The type of data contained in the payload (see PayloadType).
     pub package_type: u8,
@@ -96,6 +150,21 @@ pub struct SlowPackageHeader {
     /// The number of hops the package has taken.
     pub hop_count: u8,
 
+    /// Bit flags such as `FLAG_ACK_REQUESTED`.
+    pub flags: u8,
+
+    /// The position of the package among the ordered packages from the sender to the
+    /// recipient. Only sent when `FLAG_ORDERED` is set.
+    pub sequence: u32,
+
+    /// The time after which the package is dropped, in milliseconds since the Unix
+    /// epoch. Only sent when `FLAG_EXPIRES` is set.
+    pub expires_at: u64,
+
+    /// The package ID of the first transmission of a resent package. Only sent when
+    /// `FLAG_RETRANSMIT` is set.
+    pub first_package_id: u32,
+
     /// An incrementing number that uniquely identifies a package from the specific sender.
     pub package_id: u32,
 
@@ -111,6 +180,7 @@ pub struct SlowPackageHeader {
 ///
 /// A SlowPackage consists of a header and a payload. The header contains metadata
 /// about the package, while the payload contains the actual data being transmitted.
+#[derive(Clone)]
 pub struct SlowPackage {
     /// The header of the package containing metadata.
     pub header: SlowPackageHeader,
@@ -137,6 +207,10 @@ impl SlowPackage {
             recipient_id,
             sender_id,
             hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
             package_type: PackageType::Json.into(),
             package_id: 0,
             payload_size: payload.len() as u16,
@@ -162,6 +236,10 @@ impl SlowPackage {
             recipient_id,
             sender_id,
             hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
             package_type: PackageType::Bin.into(),
             package_id: 0,
             payload_size: payload.len() as u16,
@@ -186,6 +264,10 @@ impl SlowPackage {
             recipient_id,
             sender_id,
             hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
             package_type: PackageType::Ping.into(),
             package_id: 0,
             payload_size: payload.len() as u16,
@@ -210,6 +292,10 @@ impl SlowPackage {
             recipient_id,
             sender_id,
             hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
             package_type: PackageType::Pong.into(),
             package_id: 0,
             payload_size: payload.len() as u16,
@@ -223,17 +309,22 @@ impl SlowPackage {
     /// # Arguments
     ///
     /// * `sender_id` - A `JunctionId` representing the sender.
+    /// * `offer` - The `ProtocolOffer` of the sender, carried as the payload.
     ///
     /// # Returns
     ///
     /// * `Self` - A `SlowPackage` instance.
-    pub fn new_hello(package_id: u32, sender_id: JunctionId) -> Self {
-        let payload = Vec::new();
+    pub fn new_hello(package_id: u32, sender_id: JunctionId, offer: &ProtocolOffer) -> Self {
+        let payload = offer.pack();
         let recipient_id = JunctionId::new("none");
         let header = SlowPackageHeader {
             recipient_id,
             sender_id,
             hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
             package_type: PackageType::Hello.into(),
             package_id,
             payload_size: payload.len() as u16,
@@ -258,6 +349,10 @@ impl SlowPackage {
             recipient_id,
             sender_id,
             hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
             package_type: PackageType::Howdy.into(),
             package_id: 0,
             payload_size: payload.len() as u16,
@@ -266,6 +361,369 @@ impl SlowPackage {
         SlowPackage { header, payload }
     }
 
+    /// Creates a new `SlowPackage` instance representing a RouteRequest package.
+    ///
+    /// A route request is flooded through the mesh until it reaches `recipient_id`,
+    /// leaving a reverse route to the sender at every junction it passes.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - A `JunctionId` representing the junction to discover.
+    /// * `sender_id` - A `JunctionId` representing the sender.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A `SlowPackage` instance.
+    pub fn new_route_request(recipient_id: JunctionId, sender_id: JunctionId) -> Self {
+        let payload = Vec::new();
+        let header = SlowPackageHeader {
+            recipient_id,
+            sender_id,
+            hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
+            package_type: PackageType::RouteRequest.into(),
+            package_id: 0,
+            payload_size: payload.len() as u16,
+        };
+
+        SlowPackage { header, payload }
+    }
+
+    /// Creates a new `SlowPackage` instance representing a RouteReply package.
+    ///
+    /// A route reply travels back along the reverse route to the junction that sent
+    /// the route request, leaving a forward route to the sender along the way.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - A `JunctionId` representing the junction that requested the route.
+    /// * `sender_id` - A `JunctionId` representing the sender.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A `SlowPackage` instance.
+    pub fn new_route_reply(recipient_id: JunctionId, sender_id: JunctionId) -> Self {
+        let payload = Vec::new();
+        let header = SlowPackageHeader {
+            recipient_id,
+            sender_id,
+            hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
+            package_type: PackageType::RouteReply.into(),
+            package_id: 0,
+            payload_size: payload.len() as u16,
+        };
+
+        SlowPackage { header, payload }
+    }
+
+    /// Creates a new `SlowPackage` instance representing a RouteAdvertisement package.
+    ///
+    /// Route advertisements are sent directly to a neighbor and are never forwarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_id` - A `JunctionId` representing the sender.
+    /// * `advertisement` - A reference to a slice holding the packed `RouteAdvertisement`.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A `SlowPackage` instance.
+    pub fn new_route_advertisement(sender_id: JunctionId, advertisement: &[u8]) -> Self {
+        let payload = advertisement.to_vec();
+        let recipient_id = JunctionId::new("none");
+        let header = SlowPackageHeader {
+            recipient_id,
+            sender_id,
+            hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
+            package_type: PackageType::RouteAdvertisement.into(),
+            package_id: 0,
+            payload_size: payload.len() as u16,
+        };
+
+        SlowPackage { header, payload }
+    }
+
+    /// Creates a new `SlowPackage` instance representing a Traceroute package.
+    ///
+    /// Every junction that forwards a traceroute appends itself to the record in the payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - A `JunctionId` representing the junction to trace the path to.
+    /// * `sender_id` - A `JunctionId` representing the sender.
+    /// * `record` - A reference to a slice holding the packed `TracerouteRecord`.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A `SlowPackage` instance.
+    pub fn new_traceroute(recipient_id: JunctionId, sender_id: JunctionId, record: &[u8]) -> Self {
+        let payload = record.to_vec();
+        let header = SlowPackageHeader {
+            recipient_id,
+            sender_id,
+            hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
+            package_type: PackageType::Traceroute.into(),
+            package_id: 0,
+            payload_size: payload.len() as u16,
+        };
+
+        SlowPackage { header, payload }
+    }
+
+    /// Creates a new `SlowPackage` instance representing a TracerouteReply package.
+    ///
+    /// A traceroute reply carries the completed record from the traced junction back to the origin.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - A `JunctionId` representing the junction that started the traceroute.
+    /// * `sender_id` - A `JunctionId` representing the sender.
+    /// * `record` - A reference to a slice holding the packed `TracerouteRecord`.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A `SlowPackage` instance.
+    pub fn new_traceroute_reply(
+        recipient_id: JunctionId,
+        sender_id: JunctionId,
+        record: &[u8],
+    ) -> Self {
+        let payload = record.to_vec();
+        let header = SlowPackageHeader {
+            recipient_id,
+            sender_id,
+            hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
+            package_type: PackageType::TracerouteReply.into(),
+            package_id: 0,
+            payload_size: payload.len() as u16,
+        };
+
+        SlowPackage { header, payload }
+    }
+
+    /// Creates a new `SlowPackage` instance representing a DeliveryError package.
+    ///
+    /// A delivery error package tells the sender of a dropped package why it was not delivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - A `JunctionId` representing the sender of the dropped package.
+    /// * `sender_id` - A `JunctionId` representing the junction that dropped the package.
+    /// * `error` - A reference to a slice holding the packed `DeliveryError`.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A `SlowPackage` instance.
+    pub fn new_delivery_error(
+        recipient_id: JunctionId,
+        sender_id: JunctionId,
+        error: &[u8],
+    ) -> Self {
+        let payload = error.to_vec();
+        let header = SlowPackageHeader {
+            recipient_id,
+            sender_id,
+            hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
+            package_type: PackageType::DeliveryError.into(),
+            package_id: 0,
+            payload_size: payload.len() as u16,
+        };
+
+        SlowPackage { header, payload }
+    }
+
+    /// Creates a new `SlowPackage` instance representing an Ack package.
+    ///
+    /// An ack tells the sender of a package with `FLAG_ACK_REQUESTED` that it was delivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - A `JunctionId` representing the sender of the acknowledged package.
+    /// * `sender_id` - A `JunctionId` representing the sender.
+    /// * `acked_package_id` - The package ID of the acknowledged package.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A `SlowPackage` instance.
+    pub fn new_ack(recipient_id: JunctionId, sender_id: JunctionId, acked_package_id: u32) -> Self {
+        let payload = acked_package_id.to_le_bytes().to_vec();
+        let header = SlowPackageHeader {
+            recipient_id,
+            sender_id,
+            hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
+            package_type: PackageType::Ack.into(),
+            package_id: 0,
+            payload_size: payload.len() as u16,
+        };
+
+        SlowPackage { header, payload }
+    }
+
+    /// Creates a new `SlowPackage` instance representing an RpcRequest package.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - A `JunctionId` representing the junction serving the call.
+    /// * `sender_id` - A `JunctionId` representing the caller.
+    /// * `request` - A reference to a slice holding the packed `RpcRequest`.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A `SlowPackage` instance.
+    pub fn new_rpc_request(
+        recipient_id: JunctionId,
+        sender_id: JunctionId,
+        request: &[u8],
+    ) -> Self {
+        let payload = request.to_vec();
+        let header = SlowPackageHeader {
+            recipient_id,
+            sender_id,
+            hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
+            package_type: PackageType::RpcRequest.into(),
+            package_id: 0,
+            payload_size: payload.len() as u16,
+        };
+
+        SlowPackage { header, payload }
+    }
+
+    /// Creates a new `SlowPackage` instance representing an RpcResponse package.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - A `JunctionId` representing the caller.
+    /// * `sender_id` - A `JunctionId` representing the junction that served the call.
+    /// * `response` - A reference to a slice holding the packed `RpcResponse`.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A `SlowPackage` instance.
+    pub fn new_rpc_response(
+        recipient_id: JunctionId,
+        sender_id: JunctionId,
+        response: &[u8],
+    ) -> Self {
+        let payload = response.to_vec();
+        let header = SlowPackageHeader {
+            recipient_id,
+            sender_id,
+            hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
+            package_type: PackageType::RpcResponse.into(),
+            package_id: 0,
+            payload_size: payload.len() as u16,
+        };
+
+        SlowPackage { header, payload }
+    }
+
+    /// Creates a new `SlowPackage` instance representing a Stream package.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - A `JunctionId` representing the junction at the other end of the stream.
+    /// * `sender_id` - A `JunctionId` representing the sender.
+    /// * `segment` - A reference to a slice holding the packed `StreamSegment`.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A `SlowPackage` instance.
+    pub fn new_stream_segment(
+        recipient_id: JunctionId,
+        sender_id: JunctionId,
+        segment: &[u8],
+    ) -> Self {
+        let payload = segment.to_vec();
+        let header = SlowPackageHeader {
+            recipient_id,
+            sender_id,
+            hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
+            package_type: PackageType::Stream.into(),
+            package_id: 0,
+            payload_size: payload.len() as u16,
+        };
+
+        SlowPackage { header, payload }
+    }
+
+    /// Creates a new `SlowPackage` instance representing a Transfer package.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - A `JunctionId` representing the other junction of the transfer.
+    /// * `sender_id` - A `JunctionId` representing the sender.
+    /// * `message` - A reference to a slice holding the packed `TransferMessage`.
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A `SlowPackage` instance.
+    pub fn new_transfer(recipient_id: JunctionId, sender_id: JunctionId, message: &[u8]) -> Self {
+        let payload = message.to_vec();
+        let header = SlowPackageHeader {
+            recipient_id,
+            sender_id,
+            hop_count: 0,
+            flags: 0,
+            sequence: 0,
+            expires_at: 0,
+            first_package_id: 0,
+            package_type: PackageType::Transfer.into(),
+            package_id: 0,
+            payload_size: payload.len() as u16,
+        };
+
+        SlowPackage { header, payload }
+    }
+
+    /// Returns the package ID acknowledged by an Ack package.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<u32>` - The acknowledged package ID, or None if the payload is not a valid ack.
+    pub fn acked_package_id(&self) -> Option<u32> {
+        let bytes: [u8; 4] = self.payload.as_slice().try_into().ok()?;
+        Some(u32::from_le_bytes(bytes))
+    }
+
     /// Unpackages a byte slice into a `SlowPackage`.
     ///
     /// # Arguments
@@ -296,10 +754,7 @@ impl SlowPackage {
             return None;
         }
 
-        let recipient_id = match JunctionId::unpack(&data[pos..]) {
-            Some(id) => id,
-            None => return None,
-        };
+        let recipient_id = JunctionId::unpack(&data[pos..])?;
 
         // Move position past the recipient_id bytes
         // Format: 2 bytes for length + N bytes for id string
@@ -313,10 +768,7 @@ impl SlowPackage {
             return None;
         }
 
-        let sender_id = match JunctionId::unpack(&data[pos..]) {
-            Some(id) => id,
-            None => return None,
-        };
+        let sender_id = JunctionId::unpack(&data[pos..])?;
 
         // Move position past the sender_id bytes
         let sender_id_len = u16::from_le_bytes([data[pos], data[pos + 1]]) as usize;
@@ -329,6 +781,44 @@ impl SlowPackage {
         let hop_count = data[pos];
         pos += 1;
 
+        // Read flags (u8)
+        if pos >= data.len() {
+            return None;
+        }
+        let flags = data[pos];
+        pos += 1;
+
+        // Read sequence (u32), present only for ordered packages
+        let mut sequence = 0;
+        if flags & FLAG_ORDERED != 0 {
+            if pos + 4 > data.len() {
+                return None;
+            }
+            sequence = u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
+            pos += 4;
+        }
+
+        // Read expires_at (u64), present only for packages with a lifetime
+        let mut expires_at = 0;
+        if flags & FLAG_EXPIRES != 0 {
+            if pos + 8 > data.len() {
+                return None;
+            }
+            expires_at = u64::from_le_bytes(data[pos..pos + 8].try_into().unwrap());
+            pos += 8;
+        }
+
+        // Read first_package_id (u32), present only for resent packages
+        let mut first_package_id = 0;
+        if flags & FLAG_RETRANSMIT != 0 {
+            if pos + 4 > data.len() {
+                return None;
+            }
+            first_package_id =
+                u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
+            pos += 4;
+        }
+
         // Read package_id (u32)
         if pos + 4 > data.len() {
             return None;
@@ -358,6 +848,10 @@ impl SlowPackage {
             recipient_id,
             sender_id,
             hop_count,
+            flags,
+            sequence,
+            expires_at,
+            first_package_id,
             package_id,
             payload_size,
         };
@@ -387,6 +881,24 @@ impl SlowPackage {
         // Write hop_count (u8)
         package.push(self.header.hop_count);
 
+        // Write flags (u8)
+        package.push(self.header.flags);
+
+        // Write sequence (u32) for ordered packages
+        if self.header.flags & FLAG_ORDERED != 0 {
+            package.extend_from_slice(&self.header.sequence.to_le_bytes());
+        }
+
+        // Write expires_at (u64) for packages with a lifetime
+        if self.header.flags & FLAG_EXPIRES != 0 {
+            package.extend_from_slice(&self.header.expires_at.to_le_bytes());
+        }
+
+        // Write first_package_id (u32) for resent packages
+        if self.header.flags & FLAG_RETRANSMIT != 0 {
+            package.extend_from_slice(&self.header.first_package_id.to_le_bytes());
+        }
+
         // Write package_id (u32)
         package.extend_from_slice(&package_id.to_le_bytes());
 
@@ -408,6 +920,16 @@ impl SlowPackage {
         serde_json::from_slice(&self.payload).ok()
     }
 
+    /// Replaces the payload and updates `payload_size` to match.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The new payload.
+    pub fn set_payload(&mut self, payload: Vec<u8>) {
+        self.header.payload_size = payload.len() as u16;
+        self.payload = payload;
+    }
+
     /// Increments the `hop_count` field by 1.
     ///
     /// # Returns
@@ -445,6 +967,124 @@ impl SlowPackage {
         self.header.hop_count
     }
 
+    /// Returns the `flags` from the header.
+    ///
+    /// # Returns
+    ///
+    /// * `u8` - The header flags.
+    pub fn flags(&self) -> u8 {
+        self.header.flags
+    }
+
+    /// Asks the recipient to acknowledge the package.
+    pub fn request_ack(&mut self) {
+        self.header.flags |= FLAG_ACK_REQUESTED;
+    }
+
+    /// Returns `true` if the sender asked for the package to be acknowledged.
+    pub fn is_ack_requested(&self) -> bool {
+        self.header.flags & FLAG_ACK_REQUESTED != 0
+    }
+
+    /// Marks the package for in-order delivery.
+    ///
+    /// # Arguments
+    ///
+    /// * `sequence` - The position of the package among the ordered packages to its recipient.
+    pub fn set_sequence(&mut self, sequence: u32) {
+        self.header.flags |= FLAG_ORDERED;
+        self.header.sequence = sequence;
+    }
+
+    /// Returns the sequence number of an ordered package.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<u32>` - The sequence number, or `None` if the package is not ordered.
+    pub fn sequence(&self) -> Option<u32> {
+        (self.header.flags & FLAG_ORDERED != 0).then_some(self.header.sequence)
+    }
+
+    /// Sets the priority the package is scheduled with when it waits to be sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `priority` - The `Priority` to send the package with.
+    pub fn set_priority(&mut self, priority: Priority) {
+        let bits = u8::from(priority) << FLAG_PRIORITY_SHIFT;
+        self.header.flags = (self.header.flags & !FLAG_PRIORITY_MASK) | bits;
+    }
+
+    /// Returns the priority of the package.
+    ///
+    /// # Returns
+    ///
+    /// * `Priority` - The priority set with `set_priority`, or else the priority of the package type.
+    pub fn priority(&self) -> Priority {
+        let bits = (self.header.flags & FLAG_PRIORITY_MASK) >> FLAG_PRIORITY_SHIFT;
+        match (Priority::try_from(bits), self.package_type()) {
+            (Ok(priority), _) => priority,
+            (Err(_), Ok(package_type)) => Priority::for_package_type(package_type),
+            (Err(_), Err(_)) => Priority::Bulk,
+        }
+    }
+
+    /// Returns the protocol offer carried by a Hello package.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<ProtocolOffer, NegotiationError>` - The offer, `ProtocolOffer::LEGACY` if the
+    ///   hello carries none, or `NegotiationError::Malformed` if it cannot be read.
+    pub fn protocol_offer(&self) -> Result<ProtocolOffer, NegotiationError> {
+        if self.payload.is_empty() {
+            return Ok(ProtocolOffer::LEGACY);
+        }
+        ProtocolOffer::unpack(&self.payload).ok_or(NegotiationError::Malformed)
+    }
+
+    /// Gives the package a deadline after which junctions drop it instead of sending,
+    /// forwarding or delivering it.
+    ///
+    /// Deadlines are compared against each junction's own clock, so junctions should
+    /// keep their clocks roughly in step.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - The time after which the package is dropped.
+    pub fn set_expires_at(&mut self, deadline: SystemTime) {
+        let millis = deadline
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        self.header.flags |= FLAG_EXPIRES;
+        self.header.expires_at = millis as u64;
+    }
+
+    /// Gives the package a maximum age, counted from now, after which junctions drop it.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_age` - How long the package stays valid.
+    pub fn set_max_age(&mut self, max_age: Duration) {
+        self.set_expires_at(SystemTime::now() + max_age);
+    }
+
+    /// Returns the deadline of a package with a lifetime.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<SystemTime>` - The deadline, or `None` if the package never expires.
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        (self.header.flags & FLAG_EXPIRES != 0)
+            .then(|| UNIX_EPOCH + Duration::from_millis(self.header.expires_at))
+    }
+
+    /// Returns `true` if the package has a deadline and it has passed.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at()
+            .is_some_and(|deadline| deadline <= SystemTime::now())
+    }
+
     /// Sets the `package_id` field.
     ///
     /// # Arguments
@@ -454,6 +1094,39 @@ impl SlowPackage {
         self.header.package_id = package_id;
     }
 
+    /// Prepares the package to be sent again under a new package ID.
+    ///
+    /// Junctions relaying the package suppress repeats of an ID they have seen, so each
+    /// transmission needs its own. The package remembers the ID of its first transmission,
+    /// which the recipient acks and uses to recognize a package it already delivered.
+    ///
+    /// # Arguments
+    ///
+    /// * `package_id` - The package ID of the new transmission.
+    pub fn set_retransmit(&mut self, package_id: u32) {
+        self.header.first_package_id = self.first_package_id();
+        self.header.flags |= FLAG_RETRANSMIT;
+        self.header.package_id = package_id;
+    }
+
+    /// Returns `true` if the package is a repeat of an earlier transmission.
+    pub fn is_retransmit(&self) -> bool {
+        self.header.flags & FLAG_RETRANSMIT != 0
+    }
+
+    /// Returns the package ID of the first transmission of the package.
+    ///
+    /// # Returns
+    ///
+    /// * `u32` - The ID the package was first sent with, which is its own ID unless it was resent.
+    pub fn first_package_id(&self) -> u32 {
+        if self.is_retransmit() {
+            self.header.first_package_id
+        } else {
+            self.header.package_id
+        }
+    }
+
     /// Returns the `package_id` from the header.
     ///
     /// # Returns
@@ -468,6 +1141,7 @@ impl SlowPackage {
     /// # Returns
     ///
     /// * `PackageType` - The package type.
+    #[allow(clippy::result_unit_err)]
     pub fn package_type(&self) -> Result<PackageType, ()> {
         PackageType::try_from(self.header.package_type)
     }
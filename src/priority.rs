@@ -0,0 +1,358 @@
+use crate::package::PackageType;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+
+/// How many control packages are sent per round of the scheduler by default.
+pub const DEFAULT_CONTROL_WEIGHT: u32 = 8;
+
+/// How many interactive packages are sent per round of the scheduler by default.
+pub const DEFAULT_INTERACTIVE_WEIGHT: u32 = 4;
+
+/// How many bulk packages are sent per round of the scheduler by default.
+pub const DEFAULT_BULK_WEIGHT: u32 = 1;
+
+//=============================================================================
+// Priority
+//=============================================================================
+/// The class a package is scheduled in when it waits to be sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// Mesh upkeep such as hellos, pings, acks and route updates.
+    Control,
+    /// Application messages that someone is waiting on.
+    Interactive,
+    /// Large or background data such as streams and transfers.
+    Bulk,
+}
+
+impl Priority {
+    /// All priorities, highest first.
+    pub const ALL: [Priority; 3] = [Priority::Control, Priority::Interactive, Priority::Bulk];
+
+    /// Returns the priority a package of the given type gets unless it is set explicitly.
+    ///
+    /// # Arguments
+    ///
+    /// * `package_type` - The type of the package.
+    pub fn for_package_type(package_type: PackageType) -> Self {
+        match package_type {
+            PackageType::Json | PackageType::RpcRequest | PackageType::RpcResponse => {
+                Priority::Interactive
+            }
+            PackageType::Bin | PackageType::Stream | PackageType::Transfer => Priority::Bulk,
+            _ => Priority::Control,
+        }
+    }
+
+    /// Returns the position of the priority in `Priority::ALL`.
+    fn index(self) -> usize {
+        match self {
+            Priority::Control => 0,
+            Priority::Interactive => 1,
+            Priority::Bulk => 2,
+        }
+    }
+}
+
+impl From<Priority> for u8 {
+    fn from(priority: Priority) -> Self {
+        match priority {
+            Priority::Control => 1,
+            Priority::Interactive => 2,
+            Priority::Bulk => 3,
+        }
+    }
+}
+
+impl TryFrom<u8> for Priority {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Priority::Control),
+            2 => Ok(Priority::Interactive),
+            3 => Ok(Priority::Bulk),
+            _ => Err(()),
+        }
+    }
+}
+
+//=============================================================================
+// PriorityWeights
+//=============================================================================
+/// How many packages of each priority are sent per round of the scheduler.
+///
+/// Every class with waiting packages is served in each round, so a weight above
+/// zero guarantees a class is never starved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriorityWeights {
+    /// Control packages sent per round.
+    pub control: u32,
+
+    /// Interactive packages sent per round.
+    pub interactive: u32,
+
+    /// Bulk packages sent per round.
+    pub bulk: u32,
+}
+
+impl PriorityWeights {
+    /// Returns the weight of a priority.
+    fn get(&self, priority: Priority) -> u32 {
+        match priority {
+            Priority::Control => self.control,
+            Priority::Interactive => self.interactive,
+            Priority::Bulk => self.bulk,
+        }
+    }
+}
+
+impl Default for PriorityWeights {
+    fn default() -> Self {
+        PriorityWeights {
+            control: DEFAULT_CONTROL_WEIGHT,
+            interactive: DEFAULT_INTERACTIVE_WEIGHT,
+            bulk: DEFAULT_BULK_WEIGHT,
+        }
+    }
+}
+
+//=============================================================================
+// PriorityQueue
+//=============================================================================
+/// A queue per priority, drained by weighted round-robin.
+///
+/// Within a round the highest priority with credit left goes first. A round ends
+/// when every class with waiting items has used its credit, and the next round
+/// refills the credit from the weights.
+pub struct PriorityQueue<T> {
+    /// The scheduling weights.
+    weights: PriorityWeights,
+
+    /// The waiting items, one queue per priority, highest first.
+    queues: [VecDeque<T>; 3],
+
+    /// The items each priority may still send in the current round.
+    credits: [u32; 3],
+}
+
+impl<T> PriorityQueue<T> {
+    /// Creates a new `PriorityQueue`.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights` - The scheduling weights.
+    pub fn new(weights: PriorityWeights) -> Self {
+        PriorityQueue {
+            weights,
+            queues: [VecDeque::new(), VecDeque::new(), VecDeque::new()],
+            credits: Priority::ALL.map(|priority| weights.get(priority)),
+        }
+    }
+
+    /// Returns the scheduling weights.
+    pub fn weights(&self) -> PriorityWeights {
+        self.weights
+    }
+
+    /// Sets the scheduling weights, starting a new round.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights` - The new weights.
+    pub fn set_weights(&mut self, weights: PriorityWeights) {
+        self.weights = weights;
+        self.refill();
+    }
+
+    /// Adds an item to the back of its priority's queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `priority` - The priority of the item.
+    /// * `item` - The item.
+    pub fn push(&mut self, priority: Priority, item: T) {
+        self.queues[priority.index()].push_back(item);
+    }
+
+    /// Removes the next item to send.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<T>` - The item, or `None` if every queue is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        for _ in 0..2 {
+            for index in 0..self.queues.len() {
+                if self.credits[index] > 0
+                    && let Some(item) = self.queues[index].pop_front()
+                {
+                    self.credits[index] -= 1;
+                    return Some(item);
+                }
+            }
+            self.refill();
+        }
+
+        // Every class with waiting items has a weight of zero
+        self.queues.iter_mut().find_map(VecDeque::pop_front)
+    }
+
+    /// Returns the number of waiting items.
+    pub fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    /// Returns `true` if no items are waiting.
+    pub fn is_empty(&self) -> bool {
+        self.queues.iter().all(VecDeque::is_empty)
+    }
+
+    /// Starts a new round.
+    fn refill(&mut self) {
+        self.credits = Priority::ALL.map(|priority| self.weights.get(priority));
+    }
+}
+
+impl<T> Default for PriorityQueue<T> {
+    fn default() -> Self {
+        Self::new(PriorityWeights::default())
+    }
+}
+
+//=============================================================================
+// SendScheduler
+//=============================================================================
+/// The state of a `SendScheduler`.
+struct SchedulerState {
+    /// Whether a permit is out.
+    busy: bool,
+
+    /// The tasks waiting for a permit.
+    waiting: PriorityQueue<oneshot::Sender<()>>,
+}
+
+/// Lets one send through at a time, choosing among waiting sends by priority.
+///
+/// This is used where packages are written straight to a connection rather than
+/// taken from a queue: each send waits for a permit, and permits are handed to the
+/// waiting sends in the order a `PriorityQueue` would send them.
+pub struct SendScheduler {
+    /// The scheduler state. A std `Mutex` because permits release it when dropped.
+    state: Mutex<SchedulerState>,
+}
+
+impl SendScheduler {
+    /// Creates a new `SendScheduler`.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights` - The scheduling weights.
+    pub fn new(weights: PriorityWeights) -> Self {
+        SendScheduler {
+            state: Mutex::new(SchedulerState {
+                busy: false,
+                waiting: PriorityQueue::new(weights),
+            }),
+        }
+    }
+
+    /// Returns the scheduling weights.
+    pub fn weights(&self) -> PriorityWeights {
+        self.state.lock().unwrap().waiting.weights()
+    }
+
+    /// Sets the scheduling weights.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights` - The new weights.
+    pub fn set_weights(&self, weights: PriorityWeights) {
+        self.state.lock().unwrap().waiting.set_weights(weights);
+    }
+
+    /// Waits until a send of the given priority may go ahead.
+    ///
+    /// # Arguments
+    ///
+    /// * `priority` - The priority of the package about to be sent.
+    ///
+    /// # Returns
+    ///
+    /// * `SendPermit` - The permit, which lets the next send through when dropped.
+    pub async fn acquire(&self, priority: Priority) -> SendPermit<'_> {
+        let granted = {
+            let mut state = self.state.lock().unwrap();
+            if !state.busy {
+                state.busy = true;
+                return SendPermit { scheduler: self };
+            }
+
+            let (sender, granted) = oneshot::channel();
+            state.waiting.push(priority, sender);
+            granted
+        };
+
+        let mut waiter = Waiter {
+            scheduler: self,
+            granted: Some(granted),
+        };
+        let _ = waiter.granted.as_mut().unwrap().await;
+        waiter.granted = None;
+        SendPermit { scheduler: self }
+    }
+
+    /// Hands the permit to the next waiting send, or marks the scheduler idle.
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        while let Some(next) = state.waiting.pop() {
+            if next.send(()).is_ok() {
+                return;
+            }
+        }
+        state.busy = false;
+    }
+}
+
+impl Default for SendScheduler {
+    fn default() -> Self {
+        Self::new(PriorityWeights::default())
+    }
+}
+
+/// Permission to send, handed on to the next waiting send when dropped.
+pub struct SendPermit<'a> {
+    /// The scheduler that issued the permit.
+    scheduler: &'a SendScheduler,
+}
+
+impl Drop for SendPermit<'_> {
+    fn drop(&mut self) {
+        self.scheduler.release();
+    }
+}
+
+/// A send waiting for its permit, which passes the permit on if the send is cancelled after
+/// being granted it.
+struct Waiter<'a> {
+    /// The scheduler being waited on.
+    scheduler: &'a SendScheduler,
+
+    /// Resolves when the permit is granted; `None` once it has been.
+    granted: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        if let Some(mut granted) = self.granted.take() {
+            granted.close();
+            if granted.try_recv().is_ok() {
+                self.scheduler.release();
+            }
+        }
+    }
+}
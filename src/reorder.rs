@@ -0,0 +1,234 @@
+use crate::junction_id::JunctionId;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/// How many out-of-order packages are buffered per sender before the gap is skipped.
+pub const DEFAULT_REORDER_WINDOW: usize = 64;
+
+/// How long a gap in the sequence is waited on before it is skipped.
+pub const DEFAULT_REORDER_TIMEOUT: Duration = Duration::from_millis(500);
+
+//=============================================================================
+// ReorderPolicy
+//=============================================================================
+/// Controls how long ordered packages wait for the packages sent before them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReorderPolicy {
+    /// The most packages buffered per sender while waiting for a missing one.
+    pub window: usize,
+
+    /// How long to wait for a missing package before giving up on it.
+    pub timeout: Duration,
+}
+
+impl Default for ReorderPolicy {
+    fn default() -> Self {
+        ReorderPolicy {
+            window: DEFAULT_REORDER_WINDOW,
+            timeout: DEFAULT_REORDER_TIMEOUT,
+        }
+    }
+}
+
+//=============================================================================
+// SequenceCounter
+//=============================================================================
+/// Hands out the sequence numbers for ordered packages, counting separately per recipient.
+pub struct SequenceCounter {
+    /// The last sequence number used for each recipient.
+    last: HashMap<JunctionId, u32>,
+}
+
+impl SequenceCounter {
+    /// Creates a new `SequenceCounter`.
+    pub fn new() -> Self {
+        SequenceCounter {
+            last: HashMap::new(),
+        }
+    }
+
+    /// Returns the next sequence number for a recipient, starting at 1.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - The recipient of the ordered package.
+    pub fn next(&mut self, recipient_id: &JunctionId) -> u32 {
+        let last = self.last.entry(recipient_id.clone()).or_insert(0);
+        *last = last.wrapping_add(1).max(1);
+        *last
+    }
+}
+
+impl Default for SequenceCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//=============================================================================
+// ReorderBuffer
+//=============================================================================
+/// The ordered packages held back for a single sender.
+struct SenderQueue<T> {
+    /// The sequence number expected next.
+    next: u32,
+
+    /// Packages that arrived ahead of `next`, keyed by sequence number.
+    pending: BTreeMap<u32, T>,
+
+    /// When the current gap in the sequence was first noticed.
+    gap_since: Option<Instant>,
+}
+
+impl<T> SenderQueue<T> {
+    /// Moves the packages at the front of the sequence into `released`.
+    fn release(&mut self, released: &mut Vec<T>) {
+        let mut progressed = false;
+        while let Some(item) = self.pending.remove(&self.next) {
+            released.push(item);
+            self.next = self.next.wrapping_add(1);
+            progressed = true;
+        }
+
+        // Each new gap gets the full timeout
+        self.gap_since = match self.gap_since {
+            _ if self.pending.is_empty() => None,
+            Some(since) if !progressed => Some(since),
+            _ => Some(Instant::now()),
+        };
+    }
+
+    /// Gives up on the missing packages before the first buffered one.
+    fn skip_gap(&mut self, released: &mut Vec<T>) {
+        if let Some(&sequence) = self.pending.keys().next() {
+            self.next = sequence;
+            self.gap_since = None;
+            self.release(released);
+        }
+    }
+}
+
+/// Puts ordered packages back into the order they were sent in, per sender.
+///
+/// Packages are released as soon as every package before them has arrived. A
+/// gap is skipped when it has been waited on longer than the policy timeout, or
+/// when more packages than the policy window are buffered behind it. Packages
+/// that arrive after their place in the sequence has passed are dropped, which
+/// also drops duplicates, except that a sender starting over at 1 more than
+/// a window behind is taken to have restarted.
+pub struct ReorderBuffer<T> {
+    /// Window and timeout settings.
+    policy: ReorderPolicy,
+
+    /// The held back packages of each sender.
+    senders: HashMap<JunctionId, SenderQueue<T>>,
+}
+
+impl<T> ReorderBuffer<T> {
+    /// Creates a new `ReorderBuffer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Window and timeout settings.
+    pub fn new(policy: ReorderPolicy) -> Self {
+        ReorderBuffer {
+            policy,
+            senders: HashMap::new(),
+        }
+    }
+
+    /// Returns the window and timeout settings.
+    pub fn policy(&self) -> ReorderPolicy {
+        self.policy
+    }
+
+    /// Sets the window and timeout settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The new settings.
+    pub fn set_policy(&mut self, policy: ReorderPolicy) {
+        self.policy = policy;
+    }
+
+    /// Adds an ordered package from a sender.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_id` - The sender of the package.
+    /// * `sequence` - The sequence number the sender gave the package.
+    /// * `item` - The package.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<T>` - The packages that are now in order, oldest first.
+    pub fn push(&mut self, sender_id: &JunctionId, sequence: u32, item: T) -> Vec<T> {
+        let window = self.policy.window;
+        let queue = self
+            .senders
+            .entry(sender_id.clone())
+            .or_insert_with(|| SenderQueue {
+                next: 1,
+                pending: BTreeMap::new(),
+                gap_since: None,
+            });
+
+        let mut released = Vec::new();
+        let behind = queue.next.wrapping_sub(sequence);
+        if behind != 0 && behind <= u32::MAX / 2 {
+            if sequence == 1 && behind as usize > window {
+                // The sender started counting again, most likely after a restart
+                released.extend(std::mem::take(&mut queue.pending).into_values());
+                queue.next = sequence;
+            } else {
+                // Already released, or skipped over
+                return released;
+            }
+        }
+
+        queue.pending.insert(sequence, item);
+        queue.release(&mut released);
+
+        while queue.pending.len() > window {
+            queue.skip_gap(&mut released);
+        }
+
+        released
+    }
+
+    /// Skips the gaps that have been waited on longer than the policy timeout.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<T>` - The packages released by skipping, oldest first per sender.
+    pub fn take_expired(&mut self) -> Vec<T> {
+        let now = Instant::now();
+        let mut released = Vec::new();
+
+        for queue in self.senders.values_mut() {
+            if let Some(since) = queue.gap_since
+                && now.duration_since(since) >= self.policy.timeout
+            {
+                queue.skip_gap(&mut released);
+            }
+        }
+
+        released
+    }
+
+    /// Returns the number of packages held back across all senders.
+    pub fn len(&self) -> usize {
+        self.senders.values().map(|queue| queue.pending.len()).sum()
+    }
+
+    /// Returns `true` if no packages are held back.
+    pub fn is_empty(&self) -> bool {
+        self.senders.values().all(|queue| queue.pending.is_empty())
+    }
+}
+
+impl<T> Default for ReorderBuffer<T> {
+    fn default() -> Self {
+        Self::new(ReorderPolicy::default())
+    }
+}
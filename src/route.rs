@@ -1,6 +1,25 @@
 use crate::junction::JunctionId;
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// How many more hops than the best route a route may take and still be used for multipath.
+pub const MULTIPATH_HOP_SLACK: u8 = 1;
+
+//=============================================================================
+// RoutingMode
+//=============================================================================
+/// Controls how a junction populates its `RouteTable`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RoutingMode {
+    /// Routes are learned only from the traffic passing through the junction.
+    #[default]
+    Passive,
+    /// Routes are also learned from distance-vector advertisements exchanged with
+    /// neighbors every `interval`. Routes that are not refreshed within a few
+    /// intervals are expired.
+    DistanceVector { interval: Duration },
+}
 
 /// Represents information about a route package, including the greatest package ID and a bitfield for package tracking.
 ///
@@ -73,6 +92,12 @@ impl RoutePackageInfo {
     }
 }
 
+impl Default for RoutePackageInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Represents information about a route, including the number of hops and the time taken.
 pub struct RouteInfo {
     /// The number of hops to reach the destination.
@@ -80,6 +105,25 @@ pub struct RouteInfo {
 
     /// The time taken to reach the destination.
     pub time: f32,
+
+    /// When the route was last confirmed.
+    pub updated: Instant,
+}
+
+impl RouteInfo {
+    /// Creates a new `RouteInfo` confirmed now.
+    ///
+    /// # Arguments
+    ///
+    /// * `hops` - The number of hops to reach the destination.
+    /// * `time` - The time taken to reach the destination.
+    pub fn new(hops: u8, time: f32) -> Self {
+        RouteInfo {
+            hops,
+            time,
+            updated: Instant::now(),
+        }
+    }
 }
 
 /// Represents a collection of routes and manages route updates and retrievals.
@@ -113,10 +157,90 @@ impl Route {
     ///
     /// `true` if the packet is not a duplicate and everything was successfully updated, `false` otherwise.
     pub fn update_route(&mut self, addr: SocketAddr, hops: u8, time: f32, package_id: u32) -> bool {
-        self.routes.insert(addr, RouteInfo { hops, time });
-        let success = self.package_info.update(package_id);
+        self.routes.insert(addr, RouteInfo::new(hops, time));
+        self.package_info.update(package_id)
+    }
+
+    /// Sets the route information for a given address without tracking a package.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The socket address of the route.
+    /// * `hops` - The number of hops to reach the destination.
+    pub fn set_route(&mut self, addr: SocketAddr, hops: u8) {
+        self.routes.insert(addr, RouteInfo::new(hops, 0.0));
+    }
+
+    /// Removes the route through a given address.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The socket address of the route.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a route was removed, `false` otherwise.
+    pub fn remove_route(&mut self, addr: &SocketAddr) -> bool {
+        self.routes.remove(addr).is_some()
+    }
+
+    /// Removes routes that have not been confirmed within `max_age`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_age` - The maximum age of a route.
+    pub fn expire_routes(&mut self, max_age: Duration) {
+        self.routes
+            .retain(|_, route_info| route_info.updated.elapsed() < max_age);
+    }
 
-        success
+    /// Gets all routes ordered from fewest to most hops.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the socket address and hops of each route.
+    pub fn get_routes(&self) -> Vec<(SocketAddr, u8)> {
+        let mut routes: Vec<(SocketAddr, u8)> = self
+            .routes
+            .iter()
+            .map(|(&addr, route_info)| (addr, route_info.hops))
+            .collect();
+        routes.sort_by_key(|&(addr, hops)| (hops, addr));
+        routes
+    }
+
+    /// Gets the routes eligible for multipath forwarding along with their weights.
+    ///
+    /// A route is eligible if it takes at most `MULTIPATH_HOP_SLACK` more hops than
+    /// the best route. Routes with fewer hops get a higher weight.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the socket address and weight of each eligible route, best first.
+    pub fn get_multipath_routes(&self) -> Vec<(SocketAddr, u32)> {
+        let routes = self.get_routes();
+        let best_hops = match routes.first() {
+            Some(&(_, hops)) => hops,
+            None => return Vec::new(),
+        };
+
+        routes
+            .into_iter()
+            .filter(|&(_, hops)| hops - best_hops <= MULTIPATH_HOP_SLACK)
+            .map(|(addr, hops)| (addr, (MULTIPATH_HOP_SLACK + 1 - (hops - best_hops)) as u32))
+            .collect()
+    }
+
+    /// Gets the best route along with its number of hops.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the socket address and hops of the best route, or `None` if no routes are available.
+    pub fn get_best_route_hops(&self) -> Option<(SocketAddr, u8)> {
+        self.routes
+            .iter()
+            .min_by_key(|&(_, route_info)| route_info.hops)
+            .map(|(&addr, route_info)| (addr, route_info.hops))
     }
 
     /// Gets the best route with the minimum number of hops.
@@ -132,6 +256,12 @@ impl Route {
     }
 }
 
+impl Default for Route {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Represents a table of routes for different junctions and manages route updates and retrievals.
 pub struct RouteTable {
     /// A map of junction IDs to routes.
@@ -167,10 +297,7 @@ impl RouteTable {
         time: f32,
         package_id: u32,
     ) -> bool {
-        let route = self
-            .junctions
-            .entry(junction_id.clone())
-            .or_insert_with(Route::new);
+        let route = self.junctions.entry(junction_id.clone()).or_default();
 
         route.update_route(addr, hops, time, package_id)
     }
@@ -190,6 +317,99 @@ impl RouteTable {
             .and_then(|route| route.get_best_route())
     }
 
+    /// Sets the route information for a given junction without tracking a package.
+    ///
+    /// This is used for routes learned from advertisements rather than from traffic.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The ID of the junction.
+    /// * `addr` - The socket address of the route.
+    /// * `hops` - The number of hops to reach the destination.
+    pub fn set_route(&mut self, junction_id: &JunctionId, addr: SocketAddr, hops: u8) {
+        self.junctions
+            .entry(junction_id.clone())
+            .or_default()
+            .set_route(addr, hops);
+    }
+
+    /// Removes the route through a given address for a given junction.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The ID of the junction.
+    /// * `addr` - The socket address of the route.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a route was removed, `false` otherwise.
+    pub fn remove_route(&mut self, junction_id: &JunctionId, addr: &SocketAddr) -> bool {
+        self.junctions
+            .get_mut(junction_id)
+            .is_some_and(|route| route.remove_route(addr))
+    }
+
+    /// Removes routes that have not been confirmed within `max_age`.
+    ///
+    /// Package tracking for each junction is kept so duplicates are still detected.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_age` - The maximum age of a route.
+    pub fn expire_routes(&mut self, max_age: Duration) {
+        for route in self.junctions.values_mut() {
+            route.expire_routes(max_age);
+        }
+    }
+
+    /// Gets all routes for a given junction ordered from fewest to most hops.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The ID of the junction.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the socket address and hops of each route.
+    pub fn get_routes(&self, junction_id: &JunctionId) -> Vec<(SocketAddr, u8)> {
+        self.junctions
+            .get(junction_id)
+            .map(|route| route.get_routes())
+            .unwrap_or_default()
+    }
+
+    /// Gets the routes for a given junction that are eligible for multipath forwarding.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The ID of the junction.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the socket address and weight of each eligible route, best first.
+    pub fn get_multipath_routes(&self, junction_id: &JunctionId) -> Vec<(SocketAddr, u32)> {
+        self.junctions
+            .get(junction_id)
+            .map(|route| route.get_multipath_routes())
+            .unwrap_or_default()
+    }
+
+    /// Gets the best route and its number of hops for every known junction.
+    ///
+    /// # Returns
+    ///
+    /// A vector of junction IDs with the socket address and hops of their best route.
+    pub fn get_best_routes(&self) -> Vec<(JunctionId, SocketAddr, u8)> {
+        self.junctions
+            .iter()
+            .filter_map(|(junction_id, route)| {
+                route
+                    .get_best_route_hops()
+                    .map(|(addr, hops)| (junction_id.clone(), addr, hops))
+            })
+            .collect()
+    }
+
     /// Removes the route information for a given junction.
     ///
     /// # Arguments
@@ -203,3 +423,9 @@ impl RouteTable {
         self.junctions.remove(junction_id)
     }
 }
+
+impl Default for RouteTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
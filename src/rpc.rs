@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+/// A function that handles calls to one RPC method.
+///
+/// The handler receives the call arguments and returns the result, or a message
+/// describing why the call failed.
+pub type RpcHandler = Arc<dyn Fn(Value) -> Result<Value, String> + Send + Sync>;
+
+//=============================================================================
+// RpcError
+//=============================================================================
+/// The reason an RPC call did not return a result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RpcError {
+    /// The callee has no handler registered for the method.
+    MethodNotFound(String),
+    /// The handler on the callee returned an error.
+    HandlerFailed(String),
+    /// No response arrived before the timeout.
+    TimedOut,
+    /// The request could not be sent.
+    SendFailed(String),
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RpcError::MethodNotFound(method) => write!(f, "unknown method '{}'", method),
+            RpcError::HandlerFailed(message) => write!(f, "handler failed: {}", message),
+            RpcError::TimedOut => write!(f, "no response before the timeout"),
+            RpcError::SendFailed(message) => write!(f, "failed to send request: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+//=============================================================================
+// RpcRequest
+//=============================================================================
+/// The payload of an `RpcRequest` package.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcRequest {
+    /// Correlates the response with the call, unique per caller.
+    pub call_id: u64,
+
+    /// The name of the method to call.
+    pub method: String,
+
+    /// The arguments passed to the handler.
+    pub args: Value,
+}
+
+impl RpcRequest {
+    /// Serializes the request into a byte vector.
+    pub fn pack(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    /// Deserializes a byte slice into an `RpcRequest`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A byte slice containing the serialized request.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Self>` - The request if deserialization is successful, None otherwise.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        serde_json::from_slice(data).ok()
+    }
+}
+
+//=============================================================================
+// RpcResponse
+//=============================================================================
+/// The payload of an `RpcResponse` package.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcResponse {
+    /// The call ID of the request being answered.
+    pub call_id: u64,
+
+    /// The value returned by the handler, or why there is none.
+    pub result: Result<Value, RpcError>,
+}
+
+impl RpcResponse {
+    /// Serializes the response into a byte vector.
+    pub fn pack(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    /// Deserializes a byte slice into an `RpcResponse`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A byte slice containing the serialized response.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Self>` - The response if deserialization is successful, None otherwise.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        serde_json::from_slice(data).ok()
+    }
+}
+
+//=============================================================================
+// RpcRegistry
+//=============================================================================
+/// Maps method names to the handlers that serve them.
+pub struct RpcRegistry {
+    /// Registered handlers keyed by method name.
+    handlers: HashMap<String, RpcHandler>,
+}
+
+impl RpcRegistry {
+    /// Creates a new `RpcRegistry` with no handlers.
+    pub fn new() -> Self {
+        RpcRegistry {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registers the handler for a method, replacing any previous handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The name of the method.
+    /// * `handler` - The handler serving the method.
+    pub fn register(&mut self, method: &str, handler: RpcHandler) {
+        self.handlers.insert(method.to_string(), handler);
+    }
+
+    /// Removes the handler for a method.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The name of the method.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a handler was removed, `false` otherwise.
+    pub fn unregister(&mut self, method: &str) -> bool {
+        self.handlers.remove(method).is_some()
+    }
+
+    /// Returns the handler for a method.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The name of the method.
+    pub fn get(&self, method: &str) -> Option<RpcHandler> {
+        self.handlers.get(method).cloned()
+    }
+}
+
+impl Default for RpcRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs the handler for a request and builds the response.
+///
+/// # Arguments
+///
+/// * `handler` - The handler for the requested method, if one is registered.
+/// * `request` - The request to serve.
+///
+/// # Returns
+///
+/// * `RpcResponse` - The handler's result, or `MethodNotFound` if there is no handler.
+pub fn serve(handler: Option<RpcHandler>, request: RpcRequest) -> RpcResponse {
+    let result = match handler {
+        Some(handler) => handler(request.args).map_err(RpcError::HandlerFailed),
+        None => Err(RpcError::MethodNotFound(request.method)),
+    };
+
+    RpcResponse {
+        call_id: request.call_id,
+        result,
+    }
+}
+
+//=============================================================================
+// PendingCalls
+//=============================================================================
+/// Tracks RPC calls waiting for their response.
+pub struct PendingCalls {
+    /// The call ID given to the most recent call.
+    last_call_id: u64,
+
+    /// Calls waiting for a response, keyed by call ID.
+    pending: HashMap<u64, oneshot::Sender<Result<Value, RpcError>>>,
+}
+
+impl PendingCalls {
+    /// Creates a new `PendingCalls` with no calls in flight.
+    pub fn new() -> Self {
+        PendingCalls {
+            last_call_id: 0,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Starts tracking a new call.
+    ///
+    /// # Returns
+    ///
+    /// * `(u64, oneshot::Receiver<Result<Value, RpcError>>)` - The call ID and a receiver for the result.
+    pub fn start(&mut self) -> (u64, oneshot::Receiver<Result<Value, RpcError>>) {
+        self.last_call_id += 1;
+        let (sender, receiver) = oneshot::channel();
+        self.pending.insert(self.last_call_id, sender);
+        (self.last_call_id, receiver)
+    }
+
+    /// Completes a call with the result carried by its response.
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - The response that arrived.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a pending call was completed, `false` if the response was unexpected or late.
+    pub fn complete(&mut self, response: RpcResponse) -> bool {
+        match self.pending.remove(&response.call_id) {
+            Some(sender) => {
+                let _ = sender.send(response.result);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stops tracking a call, such as one that timed out.
+    ///
+    /// # Arguments
+    ///
+    /// * `call_id` - The call ID.
+    pub fn cancel(&mut self, call_id: u64) {
+        self.pending.remove(&call_id);
+    }
+
+    /// Returns the number of calls waiting for a response.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns `true` if no calls are waiting for a response.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+impl Default for PendingCalls {
+    fn default() -> Self {
+        Self::new()
+    }
+}
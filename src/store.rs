@@ -0,0 +1,329 @@
+use crate::junction_id::JunctionId;
+use crate::package::{PackageType, SlowPackage};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The most packages a store holds before the oldest are dropped.
+pub const DEFAULT_STORE_MAX_PACKAGES: usize = 1024;
+
+/// The most bytes of packed packages a store holds before the oldest are dropped.
+pub const DEFAULT_STORE_MAX_BYTES: usize = 1024 * 1024;
+
+/// How long a package is held before it is given up on.
+pub const DEFAULT_STORE_MAX_AGE: Duration = Duration::from_secs(600);
+
+/// Returns `true` if a package may be held for a recipient that cannot be reached.
+///
+/// Only application messages are held. Control packages lose their meaning when
+/// delivered late, and streams and transfers retry on their own.
+///
+/// # Arguments
+///
+/// * `package` - The package that could not be forwarded.
+pub fn is_storable(package: &SlowPackage) -> bool {
+    matches!(
+        package.package_type(),
+        Ok(PackageType::Json)
+            | Ok(PackageType::Bin)
+            | Ok(PackageType::RpcRequest)
+            | Ok(PackageType::RpcResponse)
+    )
+}
+
+//=============================================================================
+// StorePolicy
+//=============================================================================
+/// Limits how much a junction holds for unreachable recipients, and where it is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorePolicy {
+    /// The most packages held at once.
+    pub max_packages: usize,
+
+    /// The most bytes of packed packages held at once.
+    pub max_bytes: usize,
+
+    /// How long a package is held before it is dropped.
+    pub max_age: Duration,
+
+    /// The file held packages are saved to, so they survive a restart.
+    pub path: Option<PathBuf>,
+}
+
+impl Default for StorePolicy {
+    fn default() -> Self {
+        StorePolicy {
+            max_packages: DEFAULT_STORE_MAX_PACKAGES,
+            max_bytes: DEFAULT_STORE_MAX_BYTES,
+            max_age: DEFAULT_STORE_MAX_AGE,
+            path: None,
+        }
+    }
+}
+
+/// A package held for an unreachable recipient.
+struct StoredPackage {
+    /// When the package was first held.
+    stored_at: SystemTime,
+
+    /// The package, with the sender's package ID.
+    package: SlowPackage,
+
+    /// The size of the packed package.
+    size: usize,
+}
+
+//=============================================================================
+// PackageStore
+//=============================================================================
+/// Holds packages for recipients that cannot be reached until they are seen again.
+///
+/// Packages keep their original sender and package ID, so a recipient that also
+/// got a flooded copy drops the held one as a duplicate. When the store is over
+/// its package or byte limit the oldest packages are dropped first. If the policy
+/// names a file, the store is saved to it after every change; saving is best
+/// effort, and a failed write is retried on the next change.
+pub struct PackageStore {
+    /// Size and age limits, and the file to save to.
+    policy: StorePolicy,
+
+    /// The held packages, oldest first.
+    packages: VecDeque<StoredPackage>,
+
+    /// The total size of the held packages.
+    bytes: usize,
+}
+
+impl PackageStore {
+    /// Creates a new `PackageStore`, loading the packages saved in the policy's file if there is one.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Size and age limits, and the file to save to.
+    ///
+    /// # Returns
+    ///
+    /// * `std::io::Result<Self>` - The store, or an error if the file exists but could not be read.
+    pub fn open(policy: StorePolicy) -> std::io::Result<Self> {
+        let mut store = PackageStore {
+            policy,
+            packages: VecDeque::new(),
+            bytes: 0,
+        };
+
+        if let Some(path) = &store.policy.path
+            && path.exists()
+        {
+            let data = std::fs::read(path)?;
+            for (stored_at, package) in Self::decode(&data) {
+                store.push(stored_at, package);
+            }
+            store.expire();
+        }
+
+        Ok(store)
+    }
+
+    /// Returns the size and age limits.
+    pub fn policy(&self) -> &StorePolicy {
+        &self.policy
+    }
+
+    /// Holds a package until its recipient is seen again.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The package to hold.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - `true` if the package is held, `false` if it is not storable or larger than the store.
+    pub fn hold(&mut self, package: SlowPackage) -> bool {
+        if !is_storable(&package) {
+            return false;
+        }
+
+        let held = self.push(SystemTime::now(), package);
+        if held {
+            self.save();
+        }
+        held
+    }
+
+    /// Removes the packages held for a recipient.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - The recipient that was seen again.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<SlowPackage>` - The held packages, oldest first.
+    pub fn take(&mut self, recipient_id: &JunctionId) -> Vec<SlowPackage> {
+        let mut taken = Vec::new();
+        let mut kept = VecDeque::new();
+        for stored in self.packages.drain(..) {
+            if stored.package.recipient_id() == recipient_id {
+                self.bytes -= stored.size;
+                taken.push(stored.package);
+            } else {
+                kept.push_back(stored);
+            }
+        }
+        self.packages = kept;
+
+        if !taken.is_empty() {
+            self.save();
+        }
+        taken
+    }
+
+    /// Drops the held copies of a package its recipient is known to have received.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_id` - The junction that sent the package.
+    /// * `package_id` - The ID of the first transmission of the package.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - `true` if a held copy was dropped.
+    pub fn discard(&mut self, sender_id: &JunctionId, package_id: u32) -> bool {
+        let before = self.packages.len();
+        self.packages.retain(|stored| {
+            stored.package.sender_id() != sender_id
+                || stored.package.first_package_id() != package_id
+        });
+        if self.packages.len() == before {
+            return false;
+        }
+
+        self.bytes = self.packages.iter().map(|stored| stored.size).sum();
+        self.save();
+        true
+    }
+
+    /// Returns the recipients that packages are held for.
+    pub fn recipients(&self) -> Vec<JunctionId> {
+        let mut recipients: Vec<JunctionId> = Vec::new();
+        for stored in &self.packages {
+            if !recipients.contains(stored.package.recipient_id()) {
+                recipients.push(stored.package.recipient_id().clone());
+            }
+        }
+        recipients
+    }
+
+    /// Drops the packages held longer than the policy's maximum age.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<SlowPackage>` - The dropped packages, which were never delivered.
+    pub fn expire(&mut self) -> Vec<SlowPackage> {
+        let max_age = self.policy.max_age;
+        let (kept, expired): (VecDeque<StoredPackage>, VecDeque<StoredPackage>) = self
+            .packages
+            .drain(..)
+            .partition(|stored| stored.stored_at.elapsed().unwrap_or_default() < max_age);
+        self.packages = kept;
+        self.bytes = self.packages.iter().map(|stored| stored.size).sum();
+
+        if !expired.is_empty() {
+            self.save();
+        }
+        expired.into_iter().map(|stored| stored.package).collect()
+    }
+
+    /// Returns the number of held packages.
+    pub fn len(&self) -> usize {
+        self.packages.len()
+    }
+
+    /// Returns `true` if no packages are held.
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    /// Adds a package, dropping the oldest ones to make room.
+    fn push(&mut self, stored_at: SystemTime, package: SlowPackage) -> bool {
+        let size = package.pack(package.package_id()).len();
+        if size > self.policy.max_bytes || self.policy.max_packages == 0 {
+            return false;
+        }
+
+        while self.packages.len() >= self.policy.max_packages
+            || self.bytes + size > self.policy.max_bytes
+        {
+            match self.packages.pop_front() {
+                Some(oldest) => self.bytes -= oldest.size,
+                None => break,
+            }
+        }
+
+        self.bytes += size;
+        self.packages.push_back(StoredPackage {
+            stored_at,
+            package,
+            size,
+        });
+        true
+    }
+
+    /// Saves the held packages to the policy's file, if it has one.
+    fn save(&self) {
+        if let Some(path) = &self.policy.path {
+            let _ = Self::write(path, &self.encode());
+        }
+    }
+
+    /// Writes a file by renaming a temporary file over it, so a crash never leaves it half written.
+    fn write(path: &Path, data: &[u8]) -> std::io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, data)?;
+        std::fs::rename(&temporary, path)
+    }
+
+    /// Serializes the held packages.
+    ///
+    /// Each package is written as the milliseconds since the Unix epoch at which it
+    /// was stored as u64, the size of the packed package as u32, and the packed
+    /// package, all in little-endian.
+    fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(self.bytes + self.packages.len() * 12);
+        for stored in &self.packages {
+            let millis = stored
+                .stored_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64;
+            let packed = stored.package.pack(stored.package.package_id());
+            buffer.extend_from_slice(&millis.to_le_bytes());
+            buffer.extend_from_slice(&(packed.len() as u32).to_le_bytes());
+            buffer.extend_from_slice(&packed);
+        }
+        buffer
+    }
+
+    /// Deserializes packages written by `encode`, stopping at the first damaged record.
+    fn decode(data: &[u8]) -> Vec<(SystemTime, SlowPackage)> {
+        let mut packages = Vec::new();
+        let mut pos = 0;
+        while let Some(header) = data.get(pos..pos + 12) {
+            let millis = u64::from_le_bytes(header[..8].try_into().unwrap());
+            let size = u32::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+            let package = match data.get(pos + 12..pos + 12 + size) {
+                Some(packed) => SlowPackage::unpack(packed),
+                None => None,
+            };
+            let package = match package {
+                Some(package) => package,
+                None => break,
+            };
+
+            packages.push((UNIX_EPOCH + Duration::from_millis(millis), package));
+            pos += 12 + size;
+        }
+        packages
+    }
+}
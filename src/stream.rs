@@ -0,0 +1,691 @@
+use crate::junction_id::JunctionId;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io;
+use std::pin::Pin;
+// A std mutex, as the stream state is locked from `AsyncRead`/`AsyncWrite` polls.
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::mpsc;
+
+/// The most payload bytes carried by a single stream segment.
+pub const MAX_SEGMENT_SIZE: usize = 1024;
+
+/// The most segments a stream receiver buffers, and so the most a sender has in flight.
+pub const STREAM_WINDOW: u32 = 32;
+
+/// How long a segment waits for an ack before it is resent.
+pub const STREAM_RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// How many times a segment is resent before the stream is reset.
+pub const STREAM_MAX_RETRIES: u32 = 20;
+
+/// How long a closed stream is kept to answer late segments from its peer.
+pub const STREAM_LINGER: Duration = Duration::from_secs(2);
+
+/// The size of a packed segment without its data.
+const SEGMENT_HEADER_SIZE: usize = 16;
+
+/// Segment flag set when the sender of the segment opened the stream.
+const FLAG_INITIATOR: u8 = 0x01;
+
+//=============================================================================
+// StreamSegment
+//=============================================================================
+/// The kind of a stream segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    /// Opens a new stream, carrying the name of the service it is for.
+    Open,
+    /// Carries stream data.
+    Data,
+    /// Closes the sender's half of the stream.
+    Fin,
+    /// Acknowledges received segments and advertises the receive window.
+    Ack,
+    /// Aborts the stream.
+    Reset,
+    /// Asks the peer to repeat its ack, used while the peer's window is closed.
+    Probe,
+}
+
+impl SegmentKind {
+    /// Returns `true` if segments of this kind take a sequence number and are resent until acked.
+    pub fn is_sequenced(self) -> bool {
+        matches!(
+            self,
+            SegmentKind::Open | SegmentKind::Data | SegmentKind::Fin
+        )
+    }
+}
+
+impl From<SegmentKind> for u8 {
+    fn from(kind: SegmentKind) -> Self {
+        match kind {
+            SegmentKind::Open => 0,
+            SegmentKind::Data => 1,
+            SegmentKind::Fin => 2,
+            SegmentKind::Ack => 3,
+            SegmentKind::Reset => 4,
+            SegmentKind::Probe => 5,
+        }
+    }
+}
+
+impl TryFrom<u8> for SegmentKind {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(SegmentKind::Open),
+            1 => Ok(SegmentKind::Data),
+            2 => Ok(SegmentKind::Fin),
+            3 => Ok(SegmentKind::Ack),
+            4 => Ok(SegmentKind::Reset),
+            5 => Ok(SegmentKind::Probe),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The payload of a `Stream` package.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamSegment {
+    /// What the segment does.
+    pub kind: SegmentKind,
+
+    /// The stream ID, chosen by the junction that opened the stream.
+    pub stream_id: u32,
+
+    /// `true` if the sender of the segment opened the stream.
+    pub initiator: bool,
+
+    /// The sequence number of the segment, or the next one for segments without their own.
+    pub sequence: u32,
+
+    /// The sequence number the sender expects next from its peer.
+    pub ack: u32,
+
+    /// How many more segments the sender can buffer.
+    pub window: u16,
+
+    /// The stream data carried by a `Data` segment.
+    pub data: Vec<u8>,
+}
+
+impl StreamSegment {
+    /// Serializes the segment into a byte vector.
+    ///
+    /// The format is:
+    /// - 1 byte: The `SegmentKind`
+    /// - 1 byte: Flags
+    /// - 4 bytes: The stream ID as u32 in little-endian
+    /// - 4 bytes: The sequence number as u32 in little-endian
+    /// - 4 bytes: The ack as u32 in little-endian
+    /// - 2 bytes: The window as u16 in little-endian
+    /// - The data
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<u8>` - The serialized segment.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(SEGMENT_HEADER_SIZE + self.data.len());
+        buffer.push(self.kind.into());
+        buffer.push(if self.initiator { FLAG_INITIATOR } else { 0 });
+        buffer.extend_from_slice(&self.stream_id.to_le_bytes());
+        buffer.extend_from_slice(&self.sequence.to_le_bytes());
+        buffer.extend_from_slice(&self.ack.to_le_bytes());
+        buffer.extend_from_slice(&self.window.to_le_bytes());
+        buffer.extend_from_slice(&self.data);
+        buffer
+    }
+
+    /// Deserializes a byte slice into a `StreamSegment`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A byte slice containing the serialized segment.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Self>` - The segment if deserialization is successful, None otherwise.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() < SEGMENT_HEADER_SIZE {
+            return None;
+        }
+
+        let read_u32 = |pos: usize| {
+            u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+        };
+
+        Some(StreamSegment {
+            kind: SegmentKind::try_from(data[0]).ok()?,
+            initiator: data[1] & FLAG_INITIATOR != 0,
+            stream_id: read_u32(2),
+            sequence: read_u32(6),
+            ack: read_u32(10),
+            window: u16::from_le_bytes([data[14], data[15]]),
+            data: data[SEGMENT_HEADER_SIZE..].to_vec(),
+        })
+    }
+}
+
+/// A segment waiting to be sent by the junction, with the junction it is addressed to.
+pub type OutgoingSegment = (JunctionId, StreamSegment);
+
+//=============================================================================
+// StreamState
+//=============================================================================
+/// A sequenced segment waiting for its ack.
+struct Unacked {
+    /// The segment, kept for retransmission.
+    segment: StreamSegment,
+
+    /// When the segment was last sent.
+    sent_at: Instant,
+
+    /// The number of retransmissions so far.
+    retries: u32,
+}
+
+/// The state of one stream, shared by its `SlowStream` and the `StreamMux`.
+struct StreamState {
+    /// The junction at the other end of the stream.
+    peer_id: JunctionId,
+
+    /// The stream ID.
+    stream_id: u32,
+
+    /// The service the stream was opened for, empty for plain streams.
+    service: String,
+
+    /// `true` if this junction opened the stream.
+    initiator: bool,
+
+    /// The sequence number of the next sequenced segment sent.
+    next_sequence: u32,
+
+    /// The sequence number the peer expects next from this junction.
+    peer_ack: u32,
+
+    /// How many segments the peer last said it can buffer.
+    peer_window: u32,
+
+    /// Sent segments waiting for an ack, keyed by sequence number.
+    unacked: BTreeMap<u32, Unacked>,
+
+    /// `true` once this junction closed its half of the stream.
+    fin_sent: bool,
+
+    /// The sequence number expected next from the peer.
+    next_expected: u32,
+
+    /// Segments that arrived ahead of `next_expected`, keyed by sequence number.
+    out_of_order: BTreeMap<u32, StreamSegment>,
+
+    /// Received data waiting to be read.
+    readable: VecDeque<u8>,
+
+    /// `true` once the peer closed its half of the stream.
+    fin_received: bool,
+
+    /// The window last advertised to the peer.
+    advertised_window: u32,
+
+    /// Why the stream failed, if it did.
+    error: Option<io::ErrorKind>,
+
+    /// When the stream finished closing or failed.
+    closed_at: Option<Instant>,
+
+    /// The task waiting to read.
+    reader: Option<Waker>,
+
+    /// The task waiting to write, flush or shut down.
+    writer: Option<Waker>,
+
+    /// Hands segments to the junction for sending.
+    outgoing: mpsc::UnboundedSender<OutgoingSegment>,
+}
+
+/// Wakes the task waiting on `waker`, if any.
+fn wake(waker: &mut Option<Waker>) {
+    if let Some(waker) = waker.take() {
+        waker.wake();
+    }
+}
+
+impl StreamState {
+    /// Creates the state of a stream.
+    fn new(
+        peer_id: JunctionId,
+        stream_id: u32,
+        service: String,
+        initiator: bool,
+        outgoing: mpsc::UnboundedSender<OutgoingSegment>,
+    ) -> Self {
+        StreamState {
+            peer_id,
+            stream_id,
+            service,
+            initiator,
+            next_sequence: 0,
+            peer_ack: 0,
+            peer_window: STREAM_WINDOW,
+            unacked: BTreeMap::new(),
+            fin_sent: false,
+            next_expected: 0,
+            out_of_order: BTreeMap::new(),
+            readable: VecDeque::new(),
+            fin_received: false,
+            advertised_window: STREAM_WINDOW,
+            error: None,
+            closed_at: None,
+            reader: None,
+            writer: None,
+            outgoing,
+        }
+    }
+
+    /// Returns how many more segments this junction can buffer.
+    fn window(&self) -> u32 {
+        let buffered = self.readable.len().div_ceil(MAX_SEGMENT_SIZE) + self.out_of_order.len();
+        STREAM_WINDOW.saturating_sub(buffered as u32)
+    }
+
+    /// Returns `true` if the peer's window has room for another segment.
+    fn can_send(&self) -> bool {
+        self.next_sequence - self.peer_ack < self.peer_window
+    }
+
+    /// Builds a segment carrying the current ack and window.
+    fn segment(&mut self, kind: SegmentKind, data: Vec<u8>) -> StreamSegment {
+        self.advertised_window = self.window();
+        StreamSegment {
+            kind,
+            stream_id: self.stream_id,
+            initiator: self.initiator,
+            sequence: self.next_sequence,
+            ack: self.next_expected,
+            window: self.advertised_window as u16,
+            data,
+        }
+    }
+
+    /// Hands a segment to the junction.
+    fn emit(&self, segment: StreamSegment) {
+        let _ = self.outgoing.send((self.peer_id.clone(), segment));
+    }
+
+    /// Sends a segment, tracking it for retransmission if it is sequenced.
+    fn send(&mut self, kind: SegmentKind, data: Vec<u8>) {
+        let segment = self.segment(kind, data);
+        if kind.is_sequenced() {
+            self.unacked.insert(
+                self.next_sequence,
+                Unacked {
+                    segment: segment.clone(),
+                    sent_at: Instant::now(),
+                    retries: 0,
+                },
+            );
+            self.next_sequence += 1;
+        }
+        self.emit(segment);
+    }
+
+    /// Fails the stream and wakes everything waiting on it.
+    fn fail(&mut self, kind: io::ErrorKind) {
+        self.error = Some(kind);
+        self.unacked.clear();
+        self.closed_at = Some(Instant::now());
+        wake(&mut self.reader);
+        wake(&mut self.writer);
+    }
+
+    /// Marks the stream closed once both halves are closed and acknowledged.
+    fn check_closed(&mut self) {
+        if self.fin_sent && self.unacked.is_empty() && self.fin_received {
+            self.closed_at.get_or_insert_with(Instant::now);
+        }
+    }
+
+    /// Handles a segment received from the peer.
+    fn on_segment(&mut self, segment: StreamSegment) {
+        if segment.kind == SegmentKind::Reset {
+            self.fail(io::ErrorKind::ConnectionReset);
+            return;
+        }
+
+        if segment.ack >= self.peer_ack {
+            self.peer_ack = segment.ack;
+            self.unacked.retain(|sequence, _| *sequence >= segment.ack);
+            self.peer_window = segment.window as u32;
+            wake(&mut self.writer);
+        }
+
+        if segment.kind.is_sequenced() {
+            if segment.sequence >= self.next_expected
+                && segment.sequence < self.next_expected + STREAM_WINDOW
+            {
+                self.out_of_order.insert(segment.sequence, segment);
+            }
+
+            while let Some(segment) = self.out_of_order.remove(&self.next_expected) {
+                self.next_expected += 1;
+                match segment.kind {
+                    SegmentKind::Data => self.readable.extend(segment.data),
+                    SegmentKind::Fin => self.fin_received = true,
+                    _ => {}
+                }
+            }
+
+            wake(&mut self.reader);
+            self.send(SegmentKind::Ack, Vec::new());
+        } else if segment.kind == SegmentKind::Probe {
+            self.send(SegmentKind::Ack, Vec::new());
+        }
+
+        self.check_closed();
+    }
+
+    /// Resends the oldest segment if its ack is overdue, and probes a closed peer window.
+    ///
+    /// Only the oldest segment is resent; the ack it brings back shows what else
+    /// the peer is missing.
+    fn tick(&mut self, now: Instant) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let ack = self.next_expected;
+        let window = self.window() as u16;
+        let resend = match self.unacked.values_mut().next() {
+            Some(unacked) if now.duration_since(unacked.sent_at) >= STREAM_RETRANSMIT_TIMEOUT => {
+                if unacked.retries >= STREAM_MAX_RETRIES {
+                    let reset = self.segment(SegmentKind::Reset, Vec::new());
+                    self.emit(reset);
+                    self.fail(io::ErrorKind::TimedOut);
+                    return;
+                }
+
+                unacked.retries += 1;
+                unacked.sent_at = now;
+                let mut segment = unacked.segment.clone();
+                segment.ack = ack;
+                segment.window = window;
+                Some(segment)
+            }
+            _ => None,
+        };
+
+        if let Some(segment) = resend {
+            self.emit(segment);
+        }
+
+        if self.peer_window == 0 && self.writer.is_some() {
+            self.send(SegmentKind::Probe, Vec::new());
+        }
+    }
+}
+
+//=============================================================================
+// SlowStream
+//=============================================================================
+/// A reliable, bidirectional byte stream to another junction across the mesh.
+///
+/// Data is split into segments that are resent until acknowledged and delivered
+/// in order. The receiver advertises how many segments it can buffer, so a
+/// writer waits instead of overrunning a slow reader. Shutting down the stream
+/// closes the writing half; the peer reads EOF once everything before it has
+/// arrived. Dropping the stream shuts it down.
+pub struct SlowStream {
+    /// The state shared with the `StreamMux`.
+    state: Arc<Mutex<StreamState>>,
+}
+
+impl SlowStream {
+    /// Returns the ID of the junction at the other end of the stream.
+    pub fn peer_id(&self) -> JunctionId {
+        self.state.lock().unwrap().peer_id.clone()
+    }
+
+    /// Returns the stream ID.
+    pub fn stream_id(&self) -> u32 {
+        self.state.lock().unwrap().stream_id
+    }
+
+    /// Returns the service the stream was opened for, empty for plain streams.
+    pub fn service(&self) -> String {
+        self.state.lock().unwrap().service.clone()
+    }
+}
+
+impl AsyncRead for SlowStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut state = self.state.lock().unwrap();
+
+        if !state.readable.is_empty() {
+            let count = buf.remaining().min(state.readable.len());
+            let data: Vec<u8> = state.readable.drain(..count).collect();
+            buf.put_slice(&data);
+
+            // Let the peer know once enough of its window has opened up again
+            if state.advertised_window < STREAM_WINDOW / 2 && state.window() >= STREAM_WINDOW / 2 {
+                state.send(SegmentKind::Ack, Vec::new());
+            }
+            return Poll::Ready(Ok(()));
+        }
+
+        if let Some(kind) = state.error {
+            return Poll::Ready(Err(kind.into()));
+        }
+
+        if state.fin_received {
+            return Poll::Ready(Ok(()));
+        }
+
+        state.reader = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl AsyncWrite for SlowStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(kind) = state.error {
+            return Poll::Ready(Err(kind.into()));
+        }
+        if state.fin_sent {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        if !state.can_send() {
+            state.writer = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        let count = buf.len().min(MAX_SEGMENT_SIZE);
+        state.send(SegmentKind::Data, buf[..count].to_vec());
+        Poll::Ready(Ok(count))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(kind) = state.error {
+            return Poll::Ready(Err(kind.into()));
+        }
+        if state.unacked.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+
+        state.writer = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(kind) = state.error {
+            return Poll::Ready(Err(kind.into()));
+        }
+        if !state.fin_sent {
+            state.fin_sent = true;
+            state.send(SegmentKind::Fin, Vec::new());
+        }
+        if state.unacked.is_empty() {
+            state.check_closed();
+            return Poll::Ready(Ok(()));
+        }
+
+        state.writer = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for SlowStream {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        if !state.fin_sent && state.error.is_none() {
+            state.fin_sent = true;
+            state.send(SegmentKind::Fin, Vec::new());
+        }
+    }
+}
+
+//=============================================================================
+// StreamMux
+//=============================================================================
+/// Identifies a stream by its peer, its stream ID and whether this junction opened it.
+type StreamKey = (JunctionId, u32, bool);
+
+/// Multiplexes the streams of a junction over its packages.
+///
+/// The junction passes received segments to `handle` and calls `tick`
+/// periodically to drive retransmissions. Segments to send are handed to the
+/// junction through the `outgoing` channel.
+pub struct StreamMux {
+    /// The open streams.
+    streams: HashMap<StreamKey, Arc<Mutex<StreamState>>>,
+
+    /// The stream ID given to the most recently opened stream.
+    last_stream_id: u32,
+
+    /// Hands segments to the junction for sending.
+    outgoing: mpsc::UnboundedSender<OutgoingSegment>,
+}
+
+impl StreamMux {
+    /// Creates a new `StreamMux`.
+    ///
+    /// # Arguments
+    ///
+    /// * `outgoing` - Receives the segments the junction should send.
+    pub fn new(outgoing: mpsc::UnboundedSender<OutgoingSegment>) -> Self {
+        StreamMux {
+            streams: HashMap::new(),
+            last_stream_id: 0,
+            outgoing,
+        }
+    }
+
+    /// Opens a stream to another junction.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_id` - The junction at the other end of the stream.
+    /// * `service` - The service the stream is for, or an empty string for a plain stream.
+    ///
+    /// # Returns
+    ///
+    /// * `SlowStream` - The stream, usable right away.
+    pub fn open(&mut self, peer_id: &JunctionId, service: &str) -> SlowStream {
+        self.last_stream_id += 1;
+        let mut state = StreamState::new(
+            peer_id.clone(),
+            self.last_stream_id,
+            service.to_string(),
+            true,
+            self.outgoing.clone(),
+        );
+        state.send(SegmentKind::Open, service.as_bytes().to_vec());
+
+        let state = Arc::new(Mutex::new(state));
+        self.streams
+            .insert((peer_id.clone(), self.last_stream_id, true), state.clone());
+        SlowStream { state }
+    }
+
+    /// Handles a segment received from another junction.
+    ///
+    /// Segments for unknown streams are ignored unless they open a new stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_id` - The junction that sent the segment.
+    /// * `segment` - The segment.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<SlowStream>` - The stream, if the segment opened a new one.
+    pub fn handle(&mut self, peer_id: &JunctionId, segment: StreamSegment) -> Option<SlowStream> {
+        let key = (peer_id.clone(), segment.stream_id, !segment.initiator);
+        if let Some(state) = self.streams.get(&key) {
+            state.lock().unwrap().on_segment(segment);
+            return None;
+        }
+
+        if segment.kind != SegmentKind::Open || !segment.initiator {
+            return None;
+        }
+
+        let mut state = StreamState::new(
+            peer_id.clone(),
+            segment.stream_id,
+            String::from_utf8_lossy(&segment.data).into_owned(),
+            false,
+            self.outgoing.clone(),
+        );
+        state.on_segment(segment);
+
+        let state = Arc::new(Mutex::new(state));
+        self.streams.insert(key, state.clone());
+        Some(SlowStream { state })
+    }
+
+    /// Resends overdue segments and forgets streams that closed a while ago.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        self.streams.retain(|_, state| {
+            let mut state = state.lock().unwrap();
+            state.tick(now);
+            match state.closed_at {
+                Some(closed_at) => now.duration_since(closed_at) < STREAM_LINGER,
+                None => true,
+            }
+        });
+    }
+
+    /// Returns the number of streams being tracked.
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Returns `true` if no streams are being tracked.
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+}
@@ -1,6 +1,8 @@
 pub mod tcp_frame;
+pub mod tcp_handshake;
 pub mod tcp_junction;
 pub mod tcp_link;
 pub mod tcp_listener;
+pub mod tcp_peer;
 pub mod tcp_router;
 pub mod tcp_stream;
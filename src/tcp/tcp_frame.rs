@@ -3,105 +3,344 @@ use std::io;
 
 const MAX_FRAME_SIZE: usize = 1024 * 1024; // 1MB limit
 
+/// The size of the header before the data: the length, the frame type and the flags.
+pub const FRAME_HEADER_SIZE: usize = 6;
+
+/// The CRC-32 (IEEE) lookup table, built at compile time.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Returns the CRC-32 (IEEE) checksum of the data.
+///
+/// # Arguments
+/// * `data` - The bytes to checksum
+pub fn crc32(data: &[u8]) -> u32 {
+    let crc = data.iter().fold(0xFFFF_FFFFu32, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    });
+    !crc
+}
+
+//=============================================================================
+// FrameType
+//=============================================================================
+/// What a frame carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameType {
+    /// A link handshake message.
+    Handshake,
+    /// A packed SlowPackage.
+    Package,
+    /// An empty frame that only shows the link is alive.
+    Heartbeat,
+    /// Tells the remote end the link is being closed.
+    Close,
+    /// A type this version does not know, which receivers skip.
+    Unknown(u8),
+}
+
+impl FrameType {
+    /// Returns the frame type for its value on the wire.
+    ///
+    /// # Arguments
+    /// * `value` - The type byte of a frame header
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => FrameType::Handshake,
+            2 => FrameType::Package,
+            3 => FrameType::Heartbeat,
+            4 => FrameType::Close,
+            other => FrameType::Unknown(other),
+        }
+    }
+
+    /// Returns the value of the frame type on the wire.
+    pub fn to_u8(self) -> u8 {
+        match self {
+            FrameType::Handshake => 1,
+            FrameType::Package => 2,
+            FrameType::Heartbeat => 3,
+            FrameType::Close => 4,
+            FrameType::Unknown(value) => value,
+        }
+    }
+}
+
+//=============================================================================
+// FrameFlags
+//=============================================================================
+/// Options set on a single frame, one bit per option.
+///
+/// Receivers ignore bits they do not know.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FrameFlags(u8);
+
+impl FrameFlags {
+    /// No options.
+    pub const NONE: FrameFlags = FrameFlags(0);
+
+    /// A CRC-32 of the data follows it, and the receiver checks it.
+    pub const CHECKSUM: FrameFlags = FrameFlags(1 << 0);
+
+    /// The frame is a chunk of a larger message, and more chunks follow it.
+    pub const MORE: FrameFlags = FrameFlags(1 << 1);
+
+    /// Creates a set of flags from its bits, as sent in a frame header.
+    ///
+    /// # Arguments
+    /// * `bits` - One bit per option
+    pub fn from_bits(bits: u8) -> Self {
+        FrameFlags(bits)
+    }
+
+    /// Returns the bits of the flags, as sent in a frame header.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Returns `true` if every flag in `other` is set.
+    ///
+    /// # Arguments
+    /// * `other` - The flags to look for
+    pub fn contains(self, other: FrameFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the flags set in either.
+    ///
+    /// # Arguments
+    /// * `other` - The other flags
+    pub fn union(self, other: FrameFlags) -> Self {
+        FrameFlags(self.0 | other.0)
+    }
+
+    /// Returns these flags without the ones in `other`.
+    ///
+    /// # Arguments
+    /// * `other` - The flags to clear
+    pub fn difference(self, other: FrameFlags) -> Self {
+        FrameFlags(self.0 & !other.0)
+    }
+}
+
+//=============================================================================
+// FrameHeader
+//=============================================================================
+/// The header of a received frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// What the frame carries.
+    pub frame_type: FrameType,
+
+    /// The options set on the frame.
+    pub flags: FrameFlags,
+
+    /// The number of data bytes in the frame.
+    pub size: usize,
+}
+
+//=============================================================================
+// SlowTcpFrame
+//=============================================================================
 /// Represents a TCP frame in the slow network stack
+///
+/// A frame is the data length as u32, the frame type and the flags as u8, the data,
+/// the CRC-32 of the data as u32 if the `CHECKSUM` flag is set, and the data length
+/// again for validation. All integers are big-endian.
+///
+/// A message larger than a frame is sent as consecutive frames of the same type, each
+/// with the `MORE` flag set except the last.
 pub struct SlowTcpFrame;
 
 impl SlowTcpFrame {
-    /// Sends data over the link with length-prefix framing.
+    /// Checks that data of the given size fits in a single frame.
+    ///
+    /// # Arguments
+    /// * `size` - The number of bytes to send
+    ///
+    /// # Errors
+    /// Returns an `InvalidInput` error if the data is over the 1MB limit
+    pub fn check_size(size: usize) -> io::Result<()> {
+        if size > MAX_FRAME_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Data size exceeds 1MB limit: {} bytes", size),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sends data over the link in a typed frame.
     ///
     /// The data is wrapped with length prefixes at both the start and end for validation.
     /// There is a 1MB size limit for any single transmission.
     ///
     /// # Arguments
+    /// * `frame_type` - What the frame carries
+    /// * `flags` - The options to set on the frame
     /// * `data` - The byte slice to send
+    /// * `stream` - The stream to write the frame to
     ///
     /// # Returns
     /// The number of bytes sent (not including framing)
     ///
     /// # Errors
     /// Returns an error if the data is too large or if the transmission fails
-    pub async fn send(data: &[u8], stream: &SlowTcpStream) -> io::Result<usize> {
+    pub async fn send(
+        frame_type: FrameType,
+        flags: FrameFlags,
+        data: &[u8],
+        stream: &SlowTcpStream,
+    ) -> io::Result<usize> {
         // Ensure the data is not too large
-        if data.len() > MAX_FRAME_SIZE {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("Data size exceeds 1MB limit: {} bytes", data.len()),
-            ));
-        }
+        Self::check_size(data.len())?;
 
-        let len = data.len() as u32;
-        let len_bytes = len.to_be_bytes();
+        let len_bytes = (data.len() as u32).to_be_bytes();
 
-        // Send the length prefix
-        stream.write(&len_bytes).await?;
+        // Send the header: the length prefix, the type and the flags
+        let mut header = [0u8; FRAME_HEADER_SIZE];
+        header[..4].copy_from_slice(&len_bytes);
+        header[4] = frame_type.to_u8();
+        header[5] = flags.bits();
+        stream.write(&header).await?;
 
         // Send the actual data
         let bytes_sent = stream.write(data).await?;
 
-        // Send the length suffix (same as prefix for validation)
-        stream.write(&len_bytes).await?;
+        // Send the checksum if asked for, then the length suffix (same as prefix for validation)
+        let mut trailer = Vec::with_capacity(8);
+        if flags.contains(FrameFlags::CHECKSUM) {
+            trailer.extend_from_slice(&crc32(data).to_be_bytes());
+        }
+        trailer.extend_from_slice(&len_bytes);
+        stream.write(&trailer).await?;
 
-        // Return the number of data bytes sent (not including the length prefix/suffix)
+        // Return the number of data bytes sent (not including the framing)
         Ok(bytes_sent)
     }
 
-    /// Receives data from the link with length-prefix framing validation.
+    /// Sends data of any size as a message, split into frames of at most 1MB.
     ///
-    /// Reads a length prefix, the actual data, and a length suffix,
-    /// validating that the prefix and suffix match.
+    /// Every frame but the last has the `MORE` flag set, so the receiver can put the
+    /// message back together.
     ///
     /// # Arguments
-    /// * `buffer` - Buffer to store the received data
+    /// * `frame_type` - What the message carries
+    /// * `flags` - The options to set on every frame
+    /// * `data` - The byte slice to send
+    /// * `stream` - The stream to write the frames to
     ///
     /// # Returns
-    /// The number of bytes read into the buffer
+    /// The number of bytes sent (not including framing)
     ///
     /// # Errors
-    /// Returns an error if the buffer is too small, if reading fails,
-    /// or if the length prefix and suffix don't match
-    pub async fn receive(buffer: &mut [u8], stream: &SlowTcpStream) -> io::Result<usize> {
-        // First read the length prefix (4 bytes for u32)
-        let mut len_bytes = [0u8; 4];
-        stream.read_exact(&mut len_bytes).await?;
+    /// Returns an error if the transmission fails
+    pub async fn send_message(
+        frame_type: FrameType,
+        flags: FrameFlags,
+        data: &[u8],
+        stream: &SlowTcpStream,
+    ) -> io::Result<usize> {
+        let flags = flags.difference(FrameFlags::MORE);
+        if data.len() <= MAX_FRAME_SIZE {
+            return Self::send(frame_type, flags, data, stream).await;
+        }
 
-        // Convert bytes to u32 (from network byte order)
-        let expected_len = u32::from_be_bytes(len_bytes);
+        let mut bytes_sent = 0;
+        let mut chunks = data.chunks(MAX_FRAME_SIZE).peekable();
+        while let Some(chunk) = chunks.next() {
+            let flags = match chunks.peek() {
+                Some(_) => flags.union(FrameFlags::MORE),
+                None => flags,
+            };
+            bytes_sent += Self::send(frame_type, flags, chunk, stream).await?;
+        }
+        Ok(bytes_sent)
+    }
+
+    /// Receives a typed frame from the link with length-prefix framing validation.
+    ///
+    /// Reads the header, the actual data, the checksum if there is one, and a length
+    /// suffix, validating that the prefix and suffix match and that the checksum is right.
+    ///
+    /// # Arguments
+    /// * `buffer` - Buffer to store the received data
+    /// * `stream` - The stream to read the frame from
+    ///
+    /// # Returns
+    /// The header of the frame, whose data is at the start of the buffer
+    ///
+    /// # Errors
+    /// Returns an error if the buffer is too small, if reading fails, if the length
+    /// prefix and suffix don't match, or if the checksum is wrong
+    pub async fn receive(buffer: &mut [u8], stream: &SlowTcpStream) -> io::Result<FrameHeader> {
+        let header = Self::receive_header(stream).await?;
 
         // Ensure the buffer is large enough
-        if buffer.len() < expected_len as usize {
+        if buffer.len() < header.size {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 format!(
                     "SlowTcpLink: buffer too small: {} bytes needed but only {} bytes available.",
-                    expected_len,
+                    header.size,
                     buffer.len()
                 ),
             ));
         }
 
-        // Now read the actual data using read_exact to ensure we get all the expected bytes
-        stream
-            .read_exact(&mut buffer[..expected_len as usize])
-            .await?;
-
-        // Read and validate the length suffix
-        let mut suffix_len_bytes = [0u8; 4];
-        stream.read_exact(&mut suffix_len_bytes).await?;
-        let suffix_len = u32::from_be_bytes(suffix_len_bytes);
+        Self::receive_data(&header, &mut buffer[..header.size], stream).await?;
+        Ok(header)
+    }
 
-        // Ensure the length prefix matches the length suffix
-        if expected_len != suffix_len {
+    /// Receives a typed frame into a buffer sized to its data.
+    ///
+    /// # Arguments
+    /// * `max_size` - The most data accepted, so a bad header cannot make the receiver
+    ///   allocate more than it is willing to
+    /// * `stream` - The stream to read the frame from
+    ///
+    /// # Returns
+    /// The header of the frame and its data
+    ///
+    /// # Errors
+    /// Returns an `InvalidData` error if the frame is larger than `max_size`, or an error
+    /// if reading fails or the frame is invalid
+    pub async fn receive_owned(
+        max_size: usize,
+        stream: &SlowTcpStream,
+    ) -> io::Result<(FrameHeader, Vec<u8>)> {
+        let header = Self::receive_header(stream).await?;
+        if header.size > max_size.min(MAX_FRAME_SIZE) {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!(
-                    "Length mismatch: prefix {} bytes but suffix indicates {} bytes",
-                    expected_len, suffix_len
+                    "Frame of {} bytes exceeds the {} byte limit",
+                    header.size,
+                    max_size.min(MAX_FRAME_SIZE)
                 ),
             ));
         }
 
-        // Return the number of data bytes read
-        Ok(expected_len as usize)
+        let mut data = vec![0u8; header.size];
+        Self::receive_data(&header, &mut data, stream).await?;
+        Ok((header, data))
     }
 
     /// Returns the maximum allowed frame size for this link implementation.
@@ -111,4 +350,102 @@ impl SlowTcpFrame {
     pub fn max_frame_size() -> usize {
         MAX_FRAME_SIZE
     }
+
+    /// Reads the header of a frame: the length prefix (4 bytes for u32), the type and the flags.
+    ///
+    /// The rest of the frame must then be read with `receive_data` or discarded with `skip`.
+    ///
+    /// # Arguments
+    /// * `stream` - The stream to read the header from
+    pub async fn receive_header(stream: &SlowTcpStream) -> io::Result<FrameHeader> {
+        let mut header = [0u8; FRAME_HEADER_SIZE];
+        stream.read_exact(&mut header).await?;
+
+        // Convert bytes to u32 (from network byte order)
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        Ok(FrameHeader {
+            frame_type: FrameType::from_u8(header[4]),
+            flags: FrameFlags::from_bits(header[5]),
+            size: size as usize,
+        })
+    }
+
+    /// Reads the rest of a frame after its header: the data, the checksum if there is one,
+    /// and the length suffix.
+    ///
+    /// # Arguments
+    /// * `header` - The header already read
+    /// * `data` - Buffer exactly the size of the frame's data
+    /// * `stream` - The stream to read the frame from
+    pub async fn receive_data(
+        header: &FrameHeader,
+        data: &mut [u8],
+        stream: &SlowTcpStream,
+    ) -> io::Result<()> {
+        // Now read the actual data using read_exact to ensure we get all the expected bytes
+        stream.read_exact(data).await?;
+
+        // Read and verify the checksum
+        if header.flags.contains(FrameFlags::CHECKSUM) {
+            let mut checksum_bytes = [0u8; 4];
+            stream.read_exact(&mut checksum_bytes).await?;
+            let checksum = u32::from_be_bytes(checksum_bytes);
+            if checksum != crc32(data) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Checksum mismatch in {} byte frame", header.size),
+                ));
+            }
+        }
+
+        Self::receive_suffix(header, stream).await
+    }
+
+    /// Reads and drops the rest of a frame after its header, without buffering its data.
+    ///
+    /// The length suffix is still validated, but the checksum is not checked.
+    ///
+    /// # Arguments
+    /// * `header` - The header already read
+    /// * `stream` - The stream to read the frame from
+    pub async fn skip(header: &FrameHeader, stream: &SlowTcpStream) -> io::Result<()> {
+        let mut chunk = [0u8; 4096];
+        let mut remaining = header.size;
+        while remaining > 0 {
+            let len = remaining.min(chunk.len());
+            stream.read_exact(&mut chunk[..len]).await?;
+            remaining -= len;
+        }
+
+        if header.flags.contains(FrameFlags::CHECKSUM) {
+            let mut checksum_bytes = [0u8; 4];
+            stream.read_exact(&mut checksum_bytes).await?;
+        }
+
+        Self::receive_suffix(header, stream).await
+    }
+
+    /// Reads the length suffix of a frame and checks it against the length prefix.
+    ///
+    /// # Arguments
+    /// * `header` - The header already read
+    /// * `stream` - The stream to read the suffix from
+    async fn receive_suffix(header: &FrameHeader, stream: &SlowTcpStream) -> io::Result<()> {
+        // Read and validate the length suffix
+        let mut suffix_len_bytes = [0u8; 4];
+        stream.read_exact(&mut suffix_len_bytes).await?;
+        let suffix_len = u32::from_be_bytes(suffix_len_bytes) as usize;
+
+        // Ensure the length prefix matches the length suffix
+        if header.size != suffix_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Length mismatch: prefix {} bytes but suffix indicates {} bytes",
+                    header.size, suffix_len
+                ),
+            ));
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,121 @@
+use crate::capability::ProtocolOffer;
+use crate::junction::JunctionId;
+use std::net::SocketAddr;
+
+/// Starts the handshake message sent by the junction that dialed the link.
+pub const HELLO_MESSAGE: &[u8] = b"SLOW_HELLO";
+
+/// Starts the handshake message sent back by the junction that accepted the link.
+pub const HELLO_RESPONSE: &[u8] = b"SLOW_WELCOME";
+
+/// Starts the message sent back instead of a welcome when the junctions are incompatible,
+/// followed by the reason as UTF-8.
+pub const HELLO_REFUSED: &[u8] = b"SLOW_REFUSED";
+
+/// Starts the message sent back instead of a welcome when the accepting junction has no
+/// room for another link, followed by the reason as UTF-8.
+pub const HELLO_BUSY: &[u8] = b"SLOW_BUSY";
+
+/// The largest handshake message: the longest prefix, the fixed fields, and the longest junction ID.
+pub const MAX_HANDSHAKE_SIZE: usize =
+    12 + ProtocolOffer::PACKED_SIZE + 2 + 8 + 2 + u16::MAX as usize;
+
+/// Who is at one end of a link, as told in the link handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkIdentity {
+    /// The ID of the junction.
+    pub junction_id: JunctionId,
+
+    /// The protocol versions and features the junction offers.
+    pub offer: ProtocolOffer,
+
+    /// The port the junction accepts links on, or 0 if it does not listen.
+    pub listen_port: u16,
+
+    /// A random number drawn for each handshake, so both junctions can order the links
+    /// between them the same way.
+    pub nonce: u64,
+}
+
+impl LinkIdentity {
+    /// Creates the identity of a junction making the default protocol offer.
+    ///
+    /// # Arguments
+    /// * `junction_id` - The ID of the junction
+    /// * `listen_port` - The port the junction accepts links on
+    pub fn new(junction_id: JunctionId, listen_port: u16) -> Self {
+        LinkIdentity {
+            junction_id,
+            offer: ProtocolOffer::default(),
+            listen_port,
+            nonce: 0,
+        }
+    }
+
+    /// Returns a copy of this identity with a freshly drawn nonce, to send in a handshake.
+    pub fn with_fresh_nonce(&self) -> Self {
+        LinkIdentity {
+            nonce: rand::random(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the address the junction accepts links on, given the address it was seen at.
+    ///
+    /// # Arguments
+    /// * `peer_addr` - The remote address of the link
+    ///
+    /// # Returns
+    /// * `SocketAddr` - The peer's address with its listening port, or `peer_addr` if it does not listen
+    pub fn listen_addr(&self, peer_addr: SocketAddr) -> SocketAddr {
+        match self.listen_port {
+            0 => peer_addr,
+            port => SocketAddr::new(peer_addr.ip(), port),
+        }
+    }
+
+    /// Serializes a handshake message carrying this identity.
+    ///
+    /// The format is the prefix, then the packed protocol offer, the listening port as u16
+    /// and the nonce as u64, both in little-endian, and the packed junction ID.
+    ///
+    /// # Arguments
+    /// * `prefix` - `HELLO_MESSAGE` or `HELLO_RESPONSE`
+    pub fn pack(&self, prefix: &[u8]) -> Vec<u8> {
+        let junction_id = self.junction_id.pack();
+        let mut buffer = Vec::with_capacity(
+            prefix.len() + ProtocolOffer::PACKED_SIZE + 2 + 8 + junction_id.len(),
+        );
+        buffer.extend_from_slice(prefix);
+        buffer.extend_from_slice(&self.offer.pack());
+        buffer.extend_from_slice(&self.listen_port.to_le_bytes());
+        buffer.extend_from_slice(&self.nonce.to_le_bytes());
+        buffer.extend_from_slice(&junction_id);
+        buffer
+    }
+
+    /// Deserializes a handshake message written by `pack`.
+    ///
+    /// # Arguments
+    /// * `prefix` - The prefix the message must start with
+    /// * `data` - The received message
+    ///
+    /// # Returns
+    /// * `Option<Self>` - The identity, or None if the message is not a valid handshake
+    pub fn unpack(prefix: &[u8], data: &[u8]) -> Option<Self> {
+        let fields = data.strip_prefix(prefix)?;
+        let offer = ProtocolOffer::unpack(fields)?;
+        let fields = &fields[ProtocolOffer::PACKED_SIZE..];
+        let port = fields.get(..2)?;
+        let listen_port = u16::from_le_bytes([port[0], port[1]]);
+        let nonce = u64::from_le_bytes(fields.get(2..10)?.try_into().ok()?);
+        let junction_id = JunctionId::unpack(&fields[10..])?;
+
+        Some(LinkIdentity {
+            junction_id,
+            offer,
+            listen_port,
+            nonce,
+        })
+    }
+}
@@ -1,14 +1,44 @@
+use crate::ack::{AckPolicy, AckTracker, DeliveryHandle};
+use crate::capability::{Capabilities, ProtocolOffer};
+use crate::delivery::{DEFAULT_RECEIVE_QUEUE_CAPACITY, DeliveryError, DeliveryErrorKind};
+use crate::flood::{FloodMode, SeenPackageCache};
 use crate::junction::JunctionId;
+use crate::multipath::{MultipathPolicy, MultipathSelector};
 use crate::package::{PackageType, SlowPackage};
-use crate::tcp::tcp_link::{SlowLinkId, SlowTcpLink};
+use crate::priority::{Priority, PriorityWeights, SendScheduler};
+use crate::reorder::{ReorderBuffer, ReorderPolicy, SequenceCounter};
+use crate::rpc::{self, PendingCalls, RpcError, RpcRegistry, RpcRequest, RpcResponse};
+use crate::store::{PackageStore, StorePolicy};
+use crate::stream::{OutgoingSegment, SlowStream, StreamMux, StreamSegment};
+use crate::tcp::tcp_handshake::LinkIdentity;
+use crate::tcp::tcp_link::{
+    DEFAULT_MAX_MESSAGE_SIZE, HeartbeatPolicy, LinkDirection, SlowLinkId, SlowTcpLink,
+};
+use crate::tcp::tcp_listener::SlowTcpListener;
+use crate::tcp::tcp_peer::{
+    LinkLimits, MAX_PEER_EVENTS, PeerEvent, PeerEventKind, PeerStats, ReconnectPolicy,
+};
 use crate::tcp::tcp_router::SlowTcpRouter;
+use crate::traceroute::{TRACEROUTE_TIMEOUT, TracerouteHop, TracerouteRecord};
 use crate::tracker::UpdateResult;
+use crate::transfer::{CompletedTransfer, TransferHandle, TransferPolicy, Transfers};
+use crate::tunnel::{self, TunnelTargets};
+use serde_json::Value;
 use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use tokio::sync::{Mutex, Notify};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, Notify, mpsc, oneshot};
 use tokio::task;
+use tokio::time::{Duration, Instant};
+
+/// How often the junction runs its periodic maintenance, such as resending unacknowledged packages
+const MAINTENANCE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long to wait before retrying after the listener fails to bind or accept.
+const LISTEN_RETRY_DELAY: Duration = Duration::from_millis(100);
 
 /// A TCP-based junction that manages multiple TCP links.
 ///
@@ -21,7 +51,7 @@ pub struct SlowTcpJunction {
     /// A notification mechanism to signal when links are added/removed
     links_changed: Arc<Notify>,
 
-    /// The local socket address this junction is bound to
+    /// The local socket address this junction is bound to, with the port picked if 0 was asked for
     local_addr: SocketAddr,
 
     /// The unique identifier for this junction
@@ -36,14 +66,120 @@ pub struct SlowTcpJunction {
     /// Counter for the number of packages sent
     sent_package_count: AtomicUsize,
 
+    /// The last package ID handed out, so concurrent senders never share an ID
+    last_package_id: AtomicU32,
+
     /// Counter for the number of packages rejected (failed to unpack, duplicate, or old)
     rejected_package_count: AtomicUsize,
 
+    /// Counter for the number of packages dropped because they expired
+    expired_package_count: AtomicUsize,
+
     /// Queue of received packages meant for this junction
     received_packages: Mutex<VecDeque<SlowPackage>>,
 
+    /// A notification to signal when a package is added to the received queue
+    received_notify: Notify,
+
     /// Routes packages and tracks statistics for different links
     router: Mutex<SlowTcpRouter>,
+
+    /// Recently seen (sender, package ID) pairs, used to suppress repeat broadcasts
+    seen_packages: Mutex<SeenPackageCache>,
+
+    /// Acknowledged packages already delivered, keyed by their first package ID, so resent
+    /// copies are acked again without being delivered twice
+    delivered_packages: Mutex<SeenPackageCache>,
+
+    /// How broadcast packages are relayed to neighboring links
+    flood_mode: Mutex<FloodMode>,
+
+    /// Picks between the links available to a destination
+    multipath: Mutex<MultipathSelector>,
+
+    /// Counter used to identify traceroutes started by this junction
+    trace_count: AtomicU32,
+
+    /// Traceroutes waiting for a reply, keyed by trace ID
+    pending_traceroutes: Mutex<HashMap<u32, oneshot::Sender<Vec<TracerouteHop>>>>,
+
+    /// The maximum number of packages held in the received queue
+    receive_queue_capacity: AtomicUsize,
+
+    /// Queue of errors reported about packages this junction sent
+    delivery_errors: Mutex<VecDeque<DeliveryError>>,
+
+    /// Acknowledged sends waiting for their ack
+    acks: Mutex<AckTracker>,
+
+    /// The handlers serving RPC calls made to this junction
+    rpc_handlers: Mutex<RpcRegistry>,
+
+    /// RPC calls made by this junction that are waiting for a response
+    rpc_calls: Mutex<PendingCalls>,
+
+    /// The sequence numbers given to ordered packages, per recipient
+    sequences: Mutex<SequenceCounter>,
+
+    /// Ordered packages held back until the packages sent before them arrive
+    reorder_buffer: Mutex<ReorderBuffer<SlowPackage>>,
+
+    /// The byte streams to and from other junctions
+    streams: Mutex<StreamMux>,
+
+    /// Streams opened by other junctions that have not been accepted yet
+    incoming_streams: Mutex<mpsc::UnboundedReceiver<SlowStream>>,
+
+    /// Hands streams opened by other junctions to `accept_stream`
+    incoming_sender: mpsc::UnboundedSender<SlowStream>,
+
+    /// The tunnels this junction serves, keyed by name
+    tunnels: Mutex<TunnelTargets>,
+
+    /// The chunked transfers to and from other junctions
+    transfers: Mutex<Transfers>,
+
+    /// Packages held for unreachable recipients, or None when store-and-forward is off
+    store: Mutex<Option<PackageStore>>,
+
+    /// Decides which waiting send goes next, by priority
+    scheduler: SendScheduler,
+
+    /// The protocol versions and features offered in link handshakes
+    protocol_offer: Mutex<ProtocolOffer>,
+
+    /// The addresses dialed links were connected to, keyed by link ID
+    dialed: Mutex<HashMap<SlowLinkId, SocketAddr>>,
+
+    /// How dropped dialed links are redialed, or None when reconnecting is off
+    reconnect: Mutex<Option<ReconnectPolicy>>,
+
+    /// Events about dialed peers, oldest first
+    peer_events: Mutex<VecDeque<PeerEvent>>,
+
+    /// Counts of the events about dialed peers
+    peer_stats: Mutex<PeerStats>,
+
+    /// Set by close so that links dropping afterwards are not redialed
+    closed: AtomicBool,
+
+    /// How links are kept alive, or None when heartbeats are off
+    heartbeat: Mutex<Option<HeartbeatPolicy>>,
+
+    /// When heartbeats are next sent
+    next_heartbeat: Mutex<Instant>,
+
+    /// The most links held in each direction and in total
+    link_limits: Mutex<LinkLimits>,
+
+    /// Counter for links turned away by the link limits
+    rejected_link_count: AtomicUsize,
+
+    /// Whether package frames are sent with a checksum on every link
+    frame_checksums: AtomicBool,
+
+    /// The largest message received on any link, in bytes
+    max_message_size: AtomicUsize,
 }
 
 // ---
@@ -53,28 +189,77 @@ pub struct SlowTcpJunction {
 impl SlowTcpJunction {
     /// Creates a new `SlowTcpJunction` bound to the specified address.
     ///
+    /// If the address cannot be bound yet, the junction keeps retrying in the background.
+    ///
     /// # Arguments
-    /// * `addr` - The socket address to bind to
+    /// * `addr` - The socket address to bind to; port 0 picks a free port, see `local_addr`
     /// * `junction_id` - The unique identifier for this junction
     ///
     /// # Returns
     /// A new SlowTcpJunction instance
     pub fn new(addr: SocketAddr, junction_id: JunctionId) -> Arc<Self> {
+        let (outgoing_sender, outgoing_segments) = mpsc::unbounded_channel();
+        let (incoming_sender, incoming_streams) = mpsc::unbounded_channel();
+        let (transfer_sender, transfer_packages) = mpsc::unbounded_channel();
+        let transfers = Transfers::new(junction_id.clone(), transfer_sender);
+        let listener = SlowTcpListener::bind(addr);
+        let local_addr = match &listener {
+            Ok(listener) => listener.local_addr().unwrap_or(addr),
+            Err(_) => addr,
+        };
         let junction = SlowTcpJunction {
             links: Mutex::new(HashMap::new()),
             links_changed: Arc::new(Notify::new()),
-            local_addr: addr,
+            local_addr,
             junction_id,
             junction_map: Arc::new(Mutex::new(HashMap::new())),
             received_package_count: AtomicUsize::new(0),
             sent_package_count: AtomicUsize::new(0),
+            last_package_id: AtomicU32::new(0),
             rejected_package_count: AtomicUsize::new(0),
+            expired_package_count: AtomicUsize::new(0),
             received_packages: Mutex::new(VecDeque::new()),
+            received_notify: Notify::new(),
             router: Mutex::new(SlowTcpRouter::new()),
+            seen_packages: Mutex::new(SeenPackageCache::default()),
+            delivered_packages: Mutex::new(SeenPackageCache::default()),
+            flood_mode: Mutex::new(FloodMode::default()),
+            multipath: Mutex::new(MultipathSelector::default()),
+            trace_count: AtomicU32::new(0),
+            pending_traceroutes: Mutex::new(HashMap::new()),
+            receive_queue_capacity: AtomicUsize::new(DEFAULT_RECEIVE_QUEUE_CAPACITY),
+            delivery_errors: Mutex::new(VecDeque::new()),
+            acks: Mutex::new(AckTracker::default()),
+            rpc_handlers: Mutex::new(RpcRegistry::new()),
+            rpc_calls: Mutex::new(PendingCalls::new()),
+            sequences: Mutex::new(SequenceCounter::new()),
+            reorder_buffer: Mutex::new(ReorderBuffer::default()),
+            streams: Mutex::new(StreamMux::new(outgoing_sender)),
+            incoming_streams: Mutex::new(incoming_streams),
+            incoming_sender,
+            tunnels: Mutex::new(TunnelTargets::new()),
+            transfers: Mutex::new(transfers),
+            store: Mutex::new(None),
+            scheduler: SendScheduler::default(),
+            protocol_offer: Mutex::new(ProtocolOffer::default()),
+            dialed: Mutex::new(HashMap::new()),
+            reconnect: Mutex::new(None),
+            peer_events: Mutex::new(VecDeque::new()),
+            peer_stats: Mutex::new(PeerStats::default()),
+            closed: AtomicBool::new(false),
+            heartbeat: Mutex::new(None),
+            next_heartbeat: Mutex::new(Instant::now()),
+            link_limits: Mutex::new(LinkLimits::default()),
+            rejected_link_count: AtomicUsize::new(0),
+            frame_checksums: AtomicBool::new(false),
+            max_message_size: AtomicUsize::new(DEFAULT_MAX_MESSAGE_SIZE),
         };
 
         let junction = Arc::new(junction);
-        junction.clone().start_listening();
+        junction.start_listening(listener);
+        junction.start_maintenance();
+        junction.start_stream_sender(outgoing_segments);
+        junction.start_transfer_sender(transfer_packages);
         junction
     }
 }
@@ -86,17 +271,123 @@ impl SlowTcpJunction {
 impl SlowTcpJunction {
     /// Connects to a remote junction at the specified address.
     ///
+    /// The address is remembered, so if reconnecting is enabled the link is redialed
+    /// when it drops.
+    ///
     /// # Arguments
     /// * `addr` - The remote address to connect to
     ///
     /// # Returns
     /// Result indicating success or failure
     pub async fn connect(self: Arc<Self>, addr: SocketAddr) -> std::io::Result<()> {
-        let link = SlowTcpLink::connect(addr).await?;
-        let link = Arc::new(link);
-        self.add_link(link.clone()).await;
-        self.start_processing(link);
-        Ok(())
+        self.closed.store(false, Ordering::Relaxed);
+        self.dial(addr).await
+    }
+
+    /// Turns on reconnecting, so links made with `connect` are redialed when they drop.
+    ///
+    /// Attempts wait longer after each failure and stop when the policy's attempt limit
+    /// is reached or the peer refuses the handshake as incompatible. Each step is
+    /// reported through `receive_peer_event` and counted in `peer_stats`.
+    ///
+    /// # Arguments
+    /// * `policy` - The ReconnectPolicy to redial with
+    pub async fn enable_reconnect(&self, policy: ReconnectPolicy) {
+        *self.reconnect.lock().await = Some(policy);
+    }
+
+    /// Turns off reconnecting. Attempts already waiting stop before dialing.
+    pub async fn disable_reconnect(&self) {
+        *self.reconnect.lock().await = None;
+    }
+
+    /// Sets how links are kept alive, or turns heartbeats off with None.
+    ///
+    /// Heartbeats are off by default. Once they are on, a link that receives nothing, not
+    /// even a heartbeat, for the idle timeout is closed and removed.
+    ///
+    /// # Arguments
+    /// * `policy` - The HeartbeatPolicy to use, or None to stop sending heartbeats and closing quiet links
+    pub async fn set_heartbeat_policy(&self, policy: Option<HeartbeatPolicy>) {
+        *self.heartbeat.lock().await = policy;
+    }
+
+    /// Returns the current HeartbeatPolicy, or None if heartbeats are off.
+    pub async fn heartbeat_policy(&self) -> Option<HeartbeatPolicy> {
+        *self.heartbeat.lock().await
+    }
+
+    /// Sets the most links held in each direction and in total.
+    ///
+    /// There are no limits by default. Connections past a limit are turned away when they
+    /// are accepted, and `connect` fails with `ConnectionRefused` past the outbound or total
+    /// limit. Links already held are kept.
+    ///
+    /// # Arguments
+    /// * `limits` - The LinkLimits to enforce
+    pub async fn set_link_limits(&self, limits: LinkLimits) {
+        *self.link_limits.lock().await = limits;
+    }
+
+    /// Sets whether package frames are sent with a CRC-32 checksum on every link.
+    ///
+    /// Checksums are off by default, since TCP already checks what it carries. Links
+    /// always check the checksums they receive.
+    ///
+    /// # Arguments
+    /// * `enabled` - `true` to add checksums, `false` to stop adding them
+    pub async fn set_frame_checksums(&self, enabled: bool) {
+        self.frame_checksums.store(enabled, Ordering::Relaxed);
+        for link in self.links.lock().await.values() {
+            link.set_checksums(enabled);
+        }
+    }
+
+    /// Returns `true` if package frames are sent with a checksum.
+    pub fn frame_checksums(&self) -> bool {
+        self.frame_checksums.load(Ordering::Relaxed)
+    }
+
+    /// Sets the largest message accepted on any link, in bytes.
+    ///
+    /// Messages larger than a frame arrive in chunks and are buffered until complete;
+    /// a link whose message grows past this limit is closed instead.
+    ///
+    /// # Arguments
+    /// * `size` - The most bytes buffered for one message
+    pub async fn set_max_message_size(&self, size: usize) {
+        self.max_message_size.store(size, Ordering::Relaxed);
+        for link in self.links.lock().await.values() {
+            link.set_max_message_size(size);
+        }
+    }
+
+    /// Returns the largest message accepted on any link, in bytes.
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size.load(Ordering::Relaxed)
+    }
+
+    /// Returns the current LinkLimits.
+    pub async fn link_limits(&self) -> LinkLimits {
+        *self.link_limits.lock().await
+    }
+
+    /// Returns the current ReconnectPolicy, or None if reconnecting is off.
+    pub async fn reconnect_policy(&self) -> Option<ReconnectPolicy> {
+        *self.reconnect.lock().await
+    }
+
+    /// Retrieves the next event about a peer this junction dialed.
+    ///
+    /// # Returns
+    /// Option containing a peer event, or None if queue is empty
+    pub async fn receive_peer_event(&self) -> Option<PeerEvent> {
+        self.peer_events.lock().await.pop_front()
+    }
+
+    /// Returns the counts of what happened to the peers this junction dialed.
+    pub async fn peer_stats(&self) -> PeerStats {
+        *self.peer_stats.lock().await
     }
 
     /// Sends a SlowPackage to connected links.
@@ -110,32 +401,400 @@ impl SlowTcpJunction {
     /// # Returns
     /// * `std::io::Result<usize>` - The number of bytes sent or an IO error
     pub async fn send_package(&self, package: &SlowPackage) -> std::io::Result<usize> {
-        // Set the package ID to the current sent count before packaging
-        let package_id = self.sent_package_count.load(Ordering::Relaxed) as u32 + 1;
+        let package_id = self.next_package_id();
+        self.send_package_with_id(package, package_id).await
+    }
 
-        // Serialize the package to bytes
-        let data = package.pack(package_id);
+    /// Sends a SlowPackage and asks the recipient to acknowledge it.
+    ///
+    /// The package is resent each time the AckPolicy timeout passes without an ack.
+    /// Resent copies carry the ID of the first transmission, which the recipient acks
+    /// and uses to deliver the package only once.
+    ///
+    /// # Arguments
+    /// * `package` - The SlowPackage to send
+    ///
+    /// # Returns
+    /// * `std::io::Result<DeliveryHandle>` - A handle that resolves when the recipient acks the
+    ///   package, or an IO error if the first transmission failed
+    pub async fn send_package_acked(
+        &self,
+        package: &SlowPackage,
+    ) -> std::io::Result<DeliveryHandle> {
+        let package_id = self.next_package_id();
+        let mut package = package.clone();
+        package.request_ack();
+        package.set_package_id(package_id);
 
-        // Check router for best link first
-        let best_link = {
-            let router = self.router.lock().await;
-            router.get_best_link(package.recipient_id())
+        let handle = self.acks.lock().await.track(package.clone());
+        if let Err(e) = self.send_package_with_id(&package, package_id).await {
+            self.acks.lock().await.cancel(package_id);
+            return Err(e);
+        }
+
+        Ok(handle)
+    }
+
+    /// Sets how long acknowledged sends wait for an ack and how often they are retried.
+    ///
+    /// # Arguments
+    /// * `policy` - The AckPolicy to use for new sends
+    pub async fn set_ack_policy(&self, policy: AckPolicy) {
+        self.acks.lock().await.set_policy(policy);
+    }
+
+    /// Returns the current AckPolicy.
+    pub async fn ack_policy(&self) -> AckPolicy {
+        self.acks.lock().await.policy()
+    }
+
+    /// Sends a SlowPackage to be delivered in order with the other ordered packages sent to its recipient.
+    ///
+    /// The recipient holds back packages that arrive out of order until the packages sent
+    /// before them arrive, or until its ReorderPolicy gives up on them.
+    ///
+    /// # Arguments
+    /// * `package` - The SlowPackage to send
+    ///
+    /// # Returns
+    /// * `std::io::Result<usize>` - The number of bytes sent or an IO error
+    pub async fn send_package_ordered(&self, package: &SlowPackage) -> std::io::Result<usize> {
+        let mut package = package.clone();
+        let sequence = self.sequences.lock().await.next(package.recipient_id());
+        package.set_sequence(sequence);
+        self.send_package(&package).await
+    }
+
+    /// Sets how long ordered packages are held back waiting for missing packages.
+    ///
+    /// # Arguments
+    /// * `policy` - The ReorderPolicy to use
+    pub async fn set_reorder_policy(&self, policy: ReorderPolicy) {
+        self.reorder_buffer.lock().await.set_policy(policy);
+    }
+
+    /// Returns the current ReorderPolicy.
+    pub async fn reorder_policy(&self) -> ReorderPolicy {
+        self.reorder_buffer.lock().await.policy()
+    }
+
+    /// Opens a reliable byte stream to another junction.
+    ///
+    /// # Arguments
+    /// * `recipient_id` - The ID of the junction at the other end of the stream
+    ///
+    /// # Returns
+    /// * `SlowStream` - The stream, which implements AsyncRead and AsyncWrite
+    pub async fn open_stream(&self, recipient_id: &JunctionId) -> SlowStream {
+        self.streams.lock().await.open(recipient_id, "")
+    }
+
+    /// Waits for another junction to open a stream to this junction.
+    ///
+    /// # Returns
+    /// * `Option<SlowStream>` - The stream, or None if the junction is shutting down
+    pub async fn accept_stream(&self) -> Option<SlowStream> {
+        self.incoming_streams.lock().await.recv().await
+    }
+
+    /// Serves a tunnel, forwarding each connection made through it to a target address.
+    ///
+    /// # Arguments
+    /// * `tunnel` - The name other junctions use to reach the tunnel
+    /// * `target` - The address connections through the tunnel are forwarded to
+    pub async fn add_tunnel(&self, tunnel: &str, target: SocketAddr) {
+        self.tunnels.lock().await.add(tunnel, target);
+    }
+
+    /// Stops serving a tunnel. Connections already made through it stay open.
+    ///
+    /// # Arguments
+    /// * `tunnel` - The name of the tunnel
+    ///
+    /// # Returns
+    /// * `bool` - true if the tunnel was being served
+    pub async fn remove_tunnel(&self, tunnel: &str) -> bool {
+        self.tunnels.lock().await.remove(tunnel)
+    }
+
+    /// Listens on a local TCP port and forwards each connection to a tunnel served by another junction.
+    ///
+    /// # Arguments
+    /// * `listen_addr` - The address to listen on; port 0 picks a free port
+    /// * `remote_id` - The ID of the junction serving the tunnel
+    /// * `tunnel` - The name of the tunnel
+    ///
+    /// # Returns
+    /// * `std::io::Result<SocketAddr>` - The address being listened on, or an IO error
+    pub async fn forward_port(
+        self: &Arc<Self>,
+        listen_addr: SocketAddr,
+        remote_id: &JunctionId,
+        tunnel: &str,
+    ) -> std::io::Result<SocketAddr> {
+        let listener = TcpListener::bind(listen_addr).await?;
+        let local_addr = listener.local_addr()?;
+        let junction: Weak<Self> = Arc::downgrade(self);
+        let remote_id = remote_id.clone();
+        let service = tunnel::service_name(tunnel);
+
+        task::spawn(async move {
+            while let Ok((connection, _)) = listener.accept().await {
+                let junction = match junction.upgrade() {
+                    Some(junction) => junction,
+                    None => break,
+                };
+
+                let stream = junction.streams.lock().await.open(&remote_id, &service);
+                task::spawn(tunnel::splice(connection, stream));
+            }
+        });
+
+        Ok(local_addr)
+    }
+
+    /// Starts sending data to another junction in chunks, each acknowledged and checked by the recipient.
+    ///
+    /// Sending the same data under the same name again after an interruption resumes
+    /// the transfer from the last chunk the recipient confirmed.
+    ///
+    /// # Arguments
+    /// * `recipient_id` - The ID of the recipient
+    /// * `name` - The name of the transfer, such as a file name
+    /// * `data` - The data to send
+    ///
+    /// # Returns
+    /// * `TransferHandle` - A handle that reports progress and resolves when the transfer ends
+    pub async fn send_transfer(
+        &self,
+        recipient_id: &JunctionId,
+        name: &str,
+        data: Vec<u8>,
+    ) -> TransferHandle {
+        self.transfers.lock().await.start(recipient_id, name, data)
+    }
+
+    /// Starts sending a file to another junction, named after the file.
+    ///
+    /// # Arguments
+    /// * `recipient_id` - The ID of the recipient
+    /// * `path` - The path of the file
+    ///
+    /// # Returns
+    /// * `std::io::Result<TransferHandle>` - A handle to the transfer, or an IO error if the file could not be read
+    pub async fn send_file(
+        &self,
+        recipient_id: &JunctionId,
+        path: impl AsRef<Path>,
+    ) -> std::io::Result<TransferHandle> {
+        let path = path.as_ref();
+        let data = tokio::fs::read(path).await?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(self.send_transfer(recipient_id, &name, data).await)
+    }
+
+    /// Removes the next transfer received in full from another junction.
+    ///
+    /// # Returns
+    /// * `Option<CompletedTransfer>` - The transfer, if one has completed
+    pub async fn receive_transfer(&self) -> Option<CompletedTransfer> {
+        self.transfers.lock().await.pop_completed()
+    }
+
+    /// Sets how chunks of new outgoing transfers are acknowledged and resent, and which
+    /// transfers from other junctions are accepted.
+    ///
+    /// # Arguments
+    /// * `policy` - The transfer policy to use
+    pub async fn set_transfer_policy(&self, policy: TransferPolicy) {
+        self.transfers.lock().await.set_policy(policy);
+    }
+
+    /// Returns the current transfer policy.
+    pub async fn transfer_policy(&self) -> TransferPolicy {
+        self.transfers.lock().await.policy()
+    }
+
+    /// Sets how many packages of each priority are sent per round when sends compete for the links.
+    ///
+    /// # Arguments
+    /// * `weights` - The new scheduling weights
+    pub fn set_priority_weights(&self, weights: PriorityWeights) {
+        self.scheduler.set_weights(weights);
+    }
+
+    /// Returns the current scheduling weights.
+    pub fn priority_weights(&self) -> PriorityWeights {
+        self.scheduler.weights()
+    }
+
+    /// Turns on store-and-forward, holding packages for recipients with no working link
+    /// until a package from the recipient shows a way to reach it.
+    ///
+    /// While it is on, a package this junction cannot send is held and reported as 0 bytes sent.
+    ///
+    /// # Arguments
+    /// * `policy` - The store policy limiting what is held; packages saved in its file are loaded
+    ///
+    /// # Returns
+    /// * `std::io::Result<()>` - An IO error if the policy's file could not be read
+    pub async fn enable_store_and_forward(&self, policy: StorePolicy) -> std::io::Result<()> {
+        let store = PackageStore::open(policy)?;
+        *self.store.lock().await = Some(store);
+        Ok(())
+    }
+
+    /// Turns off store-and-forward, dropping the held packages. A policy file is left as it is.
+    pub async fn disable_store_and_forward(&self) {
+        *self.store.lock().await = None;
+    }
+
+    /// Returns the number of packages held for unreachable recipients.
+    pub async fn stored_package_count(&self) -> usize {
+        self.store
+            .lock()
+            .await
+            .as_ref()
+            .map_or(0, PackageStore::len)
+    }
+
+    /// Sends a SlowPackage over the two best links to its recipient at once.
+    ///
+    /// The copies leave through different links, so the package survives the loss of
+    /// either link. Only the first hop is chosen here: the two paths may meet again
+    /// further on, and a junction both pass through can still lose both copies. The
+    /// recipient rejects whichever copy arrives second. If fewer than two links to the
+    /// recipient are known the package is sent like `send_package`.
+    ///
+    /// # Arguments
+    /// * `package` - The SlowPackage to send
+    ///
+    /// # Returns
+    /// * `std::io::Result<usize>` - The number of bytes sent or an IO error
+    pub async fn send_package_critical(&self, package: &SlowPackage) -> std::io::Result<usize> {
+        let links = self.router.lock().await.get_links(package.recipient_id());
+        if links.len() < 2 {
+            return self.send_package(package).await;
+        }
+
+        let package_id = self.next_package_id();
+        let links: Vec<SlowLinkId> = links.into_iter().take(2).collect();
+        self.send_package_via(package, package_id, Some(&links))
+            .await
+    }
+
+    /// Traces the path packages take to a junction.
+    ///
+    /// A traceroute package is sent to the junction and every junction that forwards
+    /// it records itself along with a timestamp. The junction echoes the record back.
+    ///
+    /// # Arguments
+    /// * `junction_id` - The ID of the junction to trace the path to
+    ///
+    /// # Returns
+    /// * `Option<Vec<TracerouteHop>>` - The junctions on the path, starting with this one and
+    ///   ending with the traced junction, or None if no reply arrived within `TRACEROUTE_TIMEOUT`
+    pub async fn traceroute(&self, junction_id: &JunctionId) -> Option<Vec<TracerouteHop>> {
+        let trace_id = self.trace_count.fetch_add(1, Ordering::Relaxed) + 1;
+        let (sender, receiver) = oneshot::channel();
+        self.pending_traceroutes
+            .lock()
+            .await
+            .insert(trace_id, sender);
+
+        let record = TracerouteRecord::new(trace_id, &self.junction_id);
+        let package = SlowPackage::new_traceroute(
+            junction_id.clone(),
+            self.junction_id.clone(),
+            &record.pack(),
+        );
+
+        let result = match self.send_package(&package).await {
+            Ok(_) => tokio::time::timeout(TRACEROUTE_TIMEOUT, receiver)
+                .await
+                .ok(),
+            Err(e) => {
+                self.log(&format!("Failed to send traceroute: {}", e));
+                None
+            }
         };
 
-        let result = if let Some(link_id) = best_link {
-            self.log(&format!("Sending package through best link {}", link_id));
-            self.forward(&data, link_id).await
-        } else {
-            self.log("No best link found; broadcasting to all links");
-            self.broadcast(&data, None).await
+        self.pending_traceroutes.lock().await.remove(&trace_id);
+        result?.ok()
+    }
+
+    /// Registers the handler serving RPC calls to a method, replacing any previous handler.
+    ///
+    /// # Arguments
+    /// * `method` - The name of the method
+    /// * `handler` - A function that takes the call arguments and returns the result or an error message
+    pub async fn register_rpc_handler<F>(&self, method: &str, handler: F)
+    where
+        F: Fn(Value) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.rpc_handlers
+            .lock()
+            .await
+            .register(method, Arc::new(handler));
+    }
+
+    /// Removes the handler serving RPC calls to a method.
+    ///
+    /// # Arguments
+    /// * `method` - The name of the method
+    ///
+    /// # Returns
+    /// * `bool` - true if a handler was removed
+    pub async fn unregister_rpc_handler(&self, method: &str) -> bool {
+        self.rpc_handlers.lock().await.unregister(method)
+    }
+
+    /// Calls a method on another junction and waits for its response.
+    ///
+    /// # Arguments
+    /// * `recipient_id` - The ID of the junction serving the call
+    /// * `method` - The name of the method
+    /// * `args` - The arguments passed to the handler
+    /// * `timeout` - How long to wait for the response
+    ///
+    /// # Returns
+    /// * `Result<Value, RpcError>` - The value returned by the handler, or why there is none.
+    ///   `RpcError::SendFailed` is returned right away if the request cannot be sent.
+    pub async fn call(
+        &self,
+        recipient_id: &JunctionId,
+        method: &str,
+        args: Value,
+        timeout: Duration,
+    ) -> Result<Value, RpcError> {
+        let (call_id, receiver) = self.rpc_calls.lock().await.start();
+        let request = RpcRequest {
+            call_id,
+            method: method.to_string(),
+            args,
         };
+        let package = SlowPackage::new_rpc_request(
+            recipient_id.clone(),
+            self.junction_id.clone(),
+            &request.pack(),
+        );
 
-        // If send was successful, increment the sent package counter
-        if result.is_ok() {
-            self.sent_package_count.fetch_add(1, Ordering::Relaxed);
+        if let Err(e) = self.send_package(&package).await {
+            self.log(&format!("Failed to send RPC request: {}", e));
+            self.rpc_calls.lock().await.cancel(call_id);
+            return Err(RpcError::SendFailed(e.to_string()));
         }
 
-        result
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(result)) => result,
+            _ => {
+                self.rpc_calls.lock().await.cancel(call_id);
+                Err(RpcError::TimedOut)
+            }
+        }
     }
 
     /// Closes all active links in the junction.
@@ -147,6 +806,7 @@ impl SlowTcpJunction {
     /// # Returns
     /// * `std::io::Result<()>` - Ok if all links closed successfully, or the last error encountered
     pub async fn close(&self) -> std::io::Result<()> {
+        self.closed.store(true, Ordering::Relaxed);
         let links_org = self.links.lock().await;
         let mut links = links_org.clone();
         drop(links_org);
@@ -190,23 +850,68 @@ impl SlowTcpJunction {
         &self.junction_id
     }
 
-    /// Returns the count of packages that have been received.
-    pub fn received_package_count(&self) -> usize {
-        self.received_package_count.load(Ordering::Relaxed)
+    /// Returns the identity this junction gives in link handshakes.
+    pub async fn identity(&self) -> LinkIdentity {
+        LinkIdentity {
+            junction_id: self.junction_id.clone(),
+            offer: *self.protocol_offer.lock().await,
+            listen_port: self.local_addr.port(),
+            nonce: 0,
+        }
     }
 
-    /// Returns the count of packages that have been sent.
-    pub fn sent_package_count(&self) -> usize {
-        self.sent_package_count.load(Ordering::Relaxed)
+    /// Sets the protocol versions and features offered in link handshakes.
+    ///
+    /// Links already established keep what they agreed on; new links that cannot agree
+    /// with this offer are refused.
+    ///
+    /// # Arguments
+    /// * `offer` - The ProtocolOffer to make
+    pub async fn set_protocol_offer(&self, offer: ProtocolOffer) {
+        *self.protocol_offer.lock().await = offer;
     }
 
-    /// Returns the count of packages that have been rejected.
-    pub fn rejected_package_count(&self) -> usize {
+    /// Returns the current ProtocolOffer.
+    pub async fn protocol_offer(&self) -> ProtocolOffer {
+        *self.protocol_offer.lock().await
+    }
+
+    /// Returns the identities of the junctions at the other end of each link.
+    pub async fn peers(&self) -> Vec<LinkIdentity> {
+        let links = self.links.lock().await;
+        links.values().map(|link| link.peer().clone()).collect()
+    }
+
+    /// Returns the count of packages that have been received.
+    pub fn received_package_count(&self) -> usize {
+        self.received_package_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the count of packages that have been sent.
+    pub fn sent_package_count(&self) -> usize {
+        self.sent_package_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the count of packages that have been rejected.
+    pub fn rejected_package_count(&self) -> usize {
         self.rejected_package_count.load(Ordering::Relaxed)
     }
 
+    /// Returns the count of links turned away by the link limits.
+    pub fn rejected_link_count(&self) -> usize {
+        self.rejected_link_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the count of packages dropped because they expired before being sent,
+    /// forwarded or delivered.
+    pub fn expired_package_count(&self) -> usize {
+        self.expired_package_count.load(Ordering::Relaxed)
+    }
+
     /// Associates a junction ID with a socket address.
     ///
+    /// Junctions are registered automatically when a link to them is established.
+    ///
     /// # Arguments
     /// * `junction_id` - The junction ID to map
     /// * `addr` - The socket address to associate with the ID
@@ -227,13 +932,102 @@ impl SlowTcpJunction {
         map.get(junction_id).copied()
     }
 
+    /// Sets how broadcast packages are relayed to neighboring links.
+    ///
+    /// # Arguments
+    /// * `flood_mode` - The FloodMode to use when relaying
+    pub async fn set_flood_mode(&self, flood_mode: FloodMode) {
+        *self.flood_mode.lock().await = flood_mode;
+    }
+
+    /// Returns the current FloodMode.
+    pub async fn flood_mode(&self) -> FloodMode {
+        *self.flood_mode.lock().await
+    }
+
+    /// Sets how traffic is spread across the links available to a destination.
+    ///
+    /// # Arguments
+    /// * `policy` - The MultipathPolicy to use
+    pub async fn set_multipath_policy(&self, policy: MultipathPolicy) {
+        self.multipath.lock().await.set_policy(policy);
+    }
+
+    /// Returns the current MultipathPolicy.
+    pub async fn multipath_policy(&self) -> MultipathPolicy {
+        self.multipath.lock().await.policy()
+    }
+
     /// Retrieves the next package from the received packages queue.
     ///
+    /// Packages that expired while waiting in the queue are dropped.
+    ///
     /// # Returns
     /// Option containing a package, or None if queue is empty
     pub async fn receive_package(&self) -> Option<SlowPackage> {
         let mut packages = self.received_packages.lock().await;
-        packages.pop_front()
+        while let Some(package) = packages.pop_front() {
+            if !self.drop_if_expired(&package) {
+                return Some(package);
+            }
+        }
+        None
+    }
+
+    /// Waits for the next package for this junction and returns it.
+    ///
+    /// Returns at once if a package is already queued; otherwise the caller is woken
+    /// when one is delivered. Packages that expired while waiting in the queue are dropped.
+    ///
+    /// # Returns
+    /// The next package from the received packages queue
+    pub async fn recv(&self) -> SlowPackage {
+        loop {
+            // Register for the wakeup before checking, so a package delivered in between is not missed
+            let notified = self.received_notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(package) = self.receive_package().await {
+                return package;
+            }
+            notified.await;
+        }
+    }
+
+    /// Waits up to a given time for the next package for this junction.
+    ///
+    /// # Arguments
+    /// * `timeout` - The longest time to wait
+    ///
+    /// # Returns
+    /// Option containing a package, or None if none arrived in time
+    pub async fn recv_timeout(&self, timeout: Duration) -> Option<SlowPackage> {
+        tokio::time::timeout(timeout, self.recv()).await.ok()
+    }
+
+    /// Retrieves the next error reported about a package this junction sent.
+    ///
+    /// Junctions that drop a package because they have no link towards its recipient,
+    /// or because the recipient's receive queue is full, report the drop back to the sender.
+    ///
+    /// # Returns
+    /// Option containing a delivery error, or None if queue is empty
+    pub async fn receive_delivery_error(&self) -> Option<DeliveryError> {
+        let mut errors = self.delivery_errors.lock().await;
+        errors.pop_front()
+    }
+
+    /// Sets the maximum number of packages held in the received queue.
+    ///
+    /// Packages that arrive while the queue is full are dropped and reported to their
+    /// sender as `DeliveryErrorKind::QueueFull`.
+    ///
+    /// # Arguments
+    /// * `capacity` - The maximum number of packages to hold
+    pub fn set_receive_queue_capacity(&self, capacity: usize) {
+        self.receive_queue_capacity
+            .store(capacity, Ordering::Relaxed);
     }
 
     /// Returns the number of packages waiting in the received queue.
@@ -259,6 +1053,108 @@ impl SlowTcpJunction {
         println!("[{}]: {}", self.junction_id, message);
     }
 
+    /// Reserves the ID for the next package this junction sends.
+    ///
+    /// # Returns
+    /// * `u32` - An ID no other package from this junction has been given
+    fn next_package_id(&self) -> u32 {
+        self.last_package_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Sends a SlowPackage with a specific package ID.
+    ///
+    /// # Arguments
+    /// * `package` - The SlowPackage to send
+    /// * `package_id` - The package ID to send the package with
+    ///
+    /// # Returns
+    /// * `std::io::Result<usize>` - The number of bytes sent or an IO error
+    async fn send_package_with_id(
+        &self,
+        package: &SlowPackage,
+        package_id: u32,
+    ) -> std::io::Result<usize> {
+        self.send_package_via(package, package_id, None).await
+    }
+
+    /// Sends a SlowPackage with a specific package ID through the given links.
+    ///
+    /// Every send goes through here, so expiry, the priority scheduler and
+    /// store-and-forward apply to all of them.
+    ///
+    /// # Arguments
+    /// * `package` - The SlowPackage to send
+    /// * `package_id` - The package ID to send the package with
+    /// * `links` - The links to send a copy through each, or None for the best link
+    ///
+    /// # Returns
+    /// * `std::io::Result<usize>` - The number of bytes sent or an IO error
+    async fn send_package_via(
+        &self,
+        package: &SlowPackage,
+        package_id: u32,
+        links: Option<&[SlowLinkId]>,
+    ) -> std::io::Result<usize> {
+        if self.drop_if_expired(package) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "Package expired before it was sent",
+            ));
+        }
+
+        // Serialize the package to bytes
+        let data = package.pack(package_id);
+
+        // Remember our own package so echoes from the mesh are rejected
+        self.seen_packages
+            .lock()
+            .await
+            .insert(&self.junction_id, package_id);
+
+        // Check router for best link first
+        let links = match links {
+            Some(links) => links.to_vec(),
+            None => self.select_link(package).await.into_iter().collect(),
+        };
+
+        let permit = self.scheduler.acquire(package.priority()).await;
+        let result = if links.is_empty() {
+            self.log("No best link found; broadcasting to all links");
+            self.broadcast(&data, None).await
+        } else {
+            let mut result = Err(std::io::Error::other("Failed to send data on any link"));
+            for link_id in links {
+                self.log(&format!("Sending package through link {}", link_id));
+                match self.forward(&data, link_id).await {
+                    Ok(sent) => result = Ok(sent),
+                    Err(e) => self.log(&format!("Error sending data on link {}: {}", link_id, e)),
+                }
+            }
+            result
+        };
+        drop(permit);
+
+        let result = match result {
+            Err(e) => {
+                let mut held = package.clone();
+                held.set_package_id(package_id);
+                if self.hold_for_recipient(held).await {
+                    Ok(0)
+                } else {
+                    Err(e)
+                }
+            }
+            result => result,
+        };
+
+        // If send was successful, increment the sent package counter
+        if result.is_ok() {
+            self.sent_package_count.fetch_add(1, Ordering::Relaxed);
+        }
+
+        result
+    }
+
     /// Sends data through a specific link.
     ///
     /// # Arguments
@@ -268,9 +1164,9 @@ impl SlowTcpJunction {
     /// # Returns
     /// * `std::io::Result<usize>` - The number of bytes sent or an IO error
     async fn forward(&self, data: &[u8], link_id: SlowLinkId) -> std::io::Result<usize> {
-        let links = self.links.lock().await;
-        if let Some(link) = links.get(&link_id) {
-            link.send(data).await
+        let link = self.links.lock().await.get(&link_id).cloned();
+        if let Some(link) = link {
+            link.send_message(data).await
         } else {
             Err(std::io::Error::new(
                 std::io::ErrorKind::NotFound,
@@ -279,14 +1175,150 @@ impl SlowTcpJunction {
         }
     }
 
+    /// Picks the link to send a package through according to the MultipathPolicy.
+    ///
+    /// The router lock is released before the policy is consulted.
+    ///
+    /// # Arguments
+    /// * `package` - The package being sent
+    ///
+    /// # Returns
+    /// * `Option<SlowLinkId>` - The chosen link, or None if no link to the recipient is known
+    async fn select_link(&self, package: &SlowPackage) -> Option<SlowLinkId> {
+        let candidates = self
+            .router
+            .lock()
+            .await
+            .get_multipath_links(package.recipient_id());
+        self.multipath.lock().await.select(package, &candidates)
+    }
+
     /// Adds a TCP link to the junction.
     ///
+    /// If there is already a link to the same junction, only one of the two is kept and
+    /// the other is closed. Both junctions pick the same one: the link dialed by the
+    /// junction with the lower ID, or the link with the lower handshake nonces if the
+    /// same junction dialed both. A dialed link's address moves to the kept link, so it
+    /// is still redialed. Any other link is closed if the link limits leave no room for it.
+    ///
     /// # Arguments
     /// * `link` - The SlowTcpLink to add
-    async fn add_link(&self, link: Arc<SlowTcpLink>) {
+    ///
+    /// # Returns
+    /// `true` if the link was kept, `false` if it was closed as a duplicate
+    ///
+    /// # Errors
+    /// Returns an error of kind `ConnectionRefused` if the link limits leave no room for it
+    async fn add_link(&self, link: Arc<SlowTcpLink>) -> std::io::Result<bool> {
+        let peer_id = link.peer().junction_id.clone();
+        self.log(&format!("link {} is to {}", link.id(), peer_id));
+
+        let limits = *self.link_limits.lock().await;
         let mut links = self.links.lock().await;
-        links.insert(link.id(), link);
+        link.set_checksums(self.frame_checksums.load(Ordering::Relaxed));
+        link.set_max_message_size(self.max_message_size.load(Ordering::Relaxed));
+        let existing = links
+            .values()
+            .find(|existing| existing.peer().junction_id == peer_id && peer_id != self.junction_id)
+            .cloned();
+        if existing.is_none() {
+            let (inbound, outbound) = Self::count_links(&links);
+            if !limits.allows(link.direction(), inbound, outbound) {
+                drop(links);
+                self.rejected_link_count.fetch_add(1, Ordering::Relaxed);
+                self.log(&format!("Link limit reached, closing link {}", link.id()));
+                task::spawn(async move {
+                    let _ = link.close().await;
+                });
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionRefused,
+                    "Link limit reached",
+                ));
+            }
+        }
+        let (kept, dropped) = match existing {
+            Some(existing) if self.prefers(&link, &existing) => (link.clone(), Some(existing)),
+            Some(existing) => (existing, Some(link.clone())),
+            None => (link.clone(), None),
+        };
+        if let Some(dropped) = &dropped {
+            links.remove(&dropped.id());
+        }
+        links.insert(kept.id(), kept.clone());
         self.links_changed.notify_one();
+        drop(links);
+
+        if let Some(addr) = kept.peer_addr() {
+            self.register_junction(peer_id, addr).await;
+        }
+
+        let dropped = match dropped {
+            Some(dropped) => dropped,
+            None => return Ok(true),
+        };
+        self.log(&format!(
+            "link {} is a duplicate of link {}, closing it",
+            dropped.id(),
+            kept.id()
+        ));
+        {
+            let mut dialed = self.dialed.lock().await;
+            if let Some(addr) = dialed.remove(&dropped.id()) {
+                dialed.entry(kept.id()).or_insert(addr);
+            }
+        }
+        let kept_new = kept.id() == link.id();
+        task::spawn(async move {
+            let _ = dropped.close().await;
+        });
+        Ok(kept_new)
+    }
+
+    /// Decides which of two links to the same junction to keep.
+    ///
+    /// # Arguments
+    /// * `new` - The link being added
+    /// * `existing` - The link already held
+    ///
+    /// # Returns
+    /// `true` to keep the new link, `false` to keep the existing one
+    fn prefers(&self, new: &SlowTcpLink, existing: &SlowTcpLink) -> bool {
+        if new.direction() == existing.direction() {
+            // Both ends see the same nonces for a link, so they keep the same one
+            return new.handshake_nonces() < existing.handshake_nonces();
+        }
+
+        // Keep the link dialed by the lower ID, which both junctions agree on
+        let dialer = match new.direction() {
+            LinkDirection::Outbound => &self.junction_id,
+            LinkDirection::Inbound => &new.peer().junction_id,
+        };
+        *dialer == self.junction_id.clone().min(new.peer().junction_id.clone())
+    }
+
+    /// Returns `true` if the link limits leave room for another link.
+    ///
+    /// # Arguments
+    /// * `direction` - Which end dials the new link
+    async fn has_room(&self, direction: LinkDirection) -> bool {
+        let limits = *self.link_limits.lock().await;
+        let (inbound, outbound) = Self::count_links(&*self.links.lock().await);
+        limits.allows(direction, inbound, outbound)
+    }
+
+    /// Counts the links in each direction.
+    ///
+    /// # Arguments
+    /// * `links` - The links held by the junction
+    ///
+    /// # Returns
+    /// The number of inbound links and the number of outbound links
+    fn count_links(links: &HashMap<SlowLinkId, Arc<SlowTcpLink>>) -> (usize, usize) {
+        let outbound = links
+            .values()
+            .filter(|link| link.direction() == LinkDirection::Outbound)
+            .count();
+        (links.len() - outbound, outbound)
     }
 
     /// Removes a TCP link from the junction.
@@ -323,8 +1355,8 @@ impl SlowTcpJunction {
             exclude_link_id.unwrap_or(0)
         ));
 
-        // Get a reference to all active links
-        let links = self.links.lock().await;
+        // Take a snapshot of the active links, so sending never holds up adding or removing one
+        let links: Vec<Arc<SlowTcpLink>> = self.links.lock().await.values().cloned().collect();
 
         if links.is_empty() {
             return Err(std::io::Error::new(
@@ -337,15 +1369,13 @@ impl SlowTcpJunction {
         let mut bytes_sent = 0;
 
         // Send the data to all links except the excluded one
-        for link in links.values() {
+        for link in links {
             // Skip if this is the excluded link
-            if let Some(excluded_id) = exclude_link_id {
-                if link.id() == excluded_id {
-                    continue;
-                }
+            if Some(link.id()) == exclude_link_id {
+                continue;
             }
 
-            match link.send(data).await {
+            match link.send_message(data).await {
                 Ok(sent) => {
                     // Return the number of bytes sent on the first successful transmission
                     if bytes_sent == 0 {
@@ -365,38 +1395,400 @@ impl SlowTcpJunction {
             Ok(bytes_sent)
         } else {
             // If all links failed, return the last error
-            Err(last_error.unwrap_or_else(|| {
-                std::io::Error::new(std::io::ErrorKind::Other, "Failed to send data on any link")
-            }))
+            Err(last_error
+                .unwrap_or_else(|| std::io::Error::other("Failed to send data on any link")))
         }
     }
 
-    /// Starts listening for incoming connections on the local address.
+    /// Relays data to the links chosen by the current FloodMode.
     ///
-    /// This method spawns a background task that continuously listens for
-    /// incoming connections on the local address specified during junction creation.
-    /// Each accepted connection is added as a new link to the junction.
-    fn start_listening(self: Arc<Self>) {
-        // Spawn a tokio backgrond task to handle incoming connections
+    /// # Arguments
+    /// * `data` - The byte slice to send
+    /// * `exclude_link_id` - The ID of the link the data arrived on
+    async fn relay(&self, data: &[u8], exclude_link_id: SlowLinkId) {
+        let flood_mode = *self.flood_mode.lock().await;
+        let candidates: Vec<Arc<SlowTcpLink>> = {
+            let links = self.links.lock().await;
+            links
+                .values()
+                .filter(|link| link.id() != exclude_link_id)
+                .cloned()
+                .collect()
+        };
+
+        for link in flood_mode.select(candidates) {
+            if let Err(e) = link.send_message(data).await {
+                self.log(&format!("Failed to relay to link {}: {}", link.id(), e));
+            }
+        }
+    }
+
+    /// Starts accepting incoming connections on the local address.
+    ///
+    /// This method spawns a background task that accepts connections on one long-lived
+    /// listener. Each connection's welcome handshake runs in its own task, so a slow
+    /// peer never holds up the others, and each successful link is added to the junction.
+    /// The task only holds a weak reference, so it stops once the junction is dropped.
+    ///
+    /// # Arguments
+    /// * `listener` - The listener bound in `new`, or the error binding it
+    fn start_listening(self: &Arc<Self>, listener: std::io::Result<SlowTcpListener>) {
+        let junction: Weak<Self> = Arc::downgrade(self);
+        let addr = self.local_addr;
         task::spawn(async move {
+            let mut listener = listener;
+            let listener = loop {
+                match listener {
+                    Ok(listener) => break listener,
+                    Err(e) => match junction.upgrade() {
+                        Some(junction) => {
+                            junction.log(&format!("Error binding {}: {}", addr, e));
+                        }
+                        None => return,
+                    },
+                }
+                // Small delay to avoid tight loop on persistent errors
+                tokio::time::sleep(LISTEN_RETRY_DELAY).await;
+                listener = SlowTcpListener::new(addr).await;
+            };
+
             loop {
-                match SlowTcpLink::listen(self.local_addr).await {
-                    Ok(link) => {
-                        // Add the new link to the junction
-                        let link = Arc::new(link);
-                        self.add_link(link.clone()).await;
-                        self.clone().start_processing(link);
+                let accepted = listener.accept().await;
+                let junction = match junction.upgrade() {
+                    Some(junction) => junction,
+                    None => break,
+                };
+
+                match accepted {
+                    Ok(stream) => {
+                        task::spawn(async move {
+                            if !junction.has_room(LinkDirection::Inbound).await {
+                                junction.rejected_link_count.fetch_add(1, Ordering::Relaxed);
+                                junction.log("Link limit reached, turning connection away");
+                                if let Err(e) =
+                                    SlowTcpLink::refuse(stream, "link limit reached").await
+                                {
+                                    junction.log(&format!("Error refusing connection: {}", e));
+                                }
+                                return;
+                            }
+
+                            match SlowTcpLink::accept(stream, &junction.identity().await).await {
+                                Ok(link) => {
+                                    let link = Arc::new(link);
+                                    match junction.add_link(link.clone()).await {
+                                        Ok(true) => junction.start_processing(link),
+                                        Ok(false) => {}
+                                        Err(e) => {
+                                            junction.log(&format!("Error adding link: {}", e))
+                                        }
+                                    }
+                                }
+                                Err(e) => {
+                                    junction.log(&format!("Error accepting connection: {}", e));
+                                }
+                            }
+                        });
                     }
                     Err(e) => {
-                        self.log(&format!("Error accepting connection: {}", e));
-                        // Small delay to avoid tight loop on persistent errors
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                        junction.log(&format!("Error accepting connection: {}", e));
+                        drop(junction);
+                        tokio::time::sleep(LISTEN_RETRY_DELAY).await;
                     }
                 }
             }
         });
     }
 
+    /// Starts the periodic maintenance task.
+    ///
+    /// The task only holds a weak reference, so it stops once the junction is dropped.
+    fn start_maintenance(self: &Arc<Self>) {
+        let junction: Weak<Self> = Arc::downgrade(self);
+        task::spawn(async move {
+            let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+            loop {
+                interval.tick().await;
+                match junction.upgrade() {
+                    Some(junction) => junction.maintain().await,
+                    None => break,
+                }
+            }
+        });
+    }
+
+    /// Hands a stream opened by another junction to its tunnel, or to accept_stream.
+    ///
+    /// # Arguments
+    /// * `stream` - The new stream
+    async fn on_stream_opened(&self, stream: SlowStream) {
+        let service = stream.service();
+        let tunnel = match tunnel::tunnel_name(&service) {
+            Some(tunnel) => tunnel,
+            None => {
+                let _ = self.incoming_sender.send(stream);
+                return;
+            }
+        };
+
+        match self.tunnels.lock().await.get(tunnel) {
+            Some(target) => {
+                task::spawn(tunnel::serve(stream, target));
+            }
+            None => {
+                // Dropping the stream closes it
+                self.log(&format!("No tunnel named {}", tunnel));
+            }
+        }
+    }
+
+    /// Starts the task that sends the segments produced by this junction's streams.
+    ///
+    /// # Arguments
+    /// * `segments` - The channel the StreamMux hands segments to
+    fn start_stream_sender(
+        self: &Arc<Self>,
+        mut segments: mpsc::UnboundedReceiver<OutgoingSegment>,
+    ) {
+        let junction: Weak<Self> = Arc::downgrade(self);
+        task::spawn(async move {
+            while let Some((recipient_id, segment)) = segments.recv().await {
+                let junction = match junction.upgrade() {
+                    Some(junction) => junction,
+                    None => break,
+                };
+
+                let package = SlowPackage::new_stream_segment(
+                    recipient_id,
+                    junction.junction_id.clone(),
+                    &segment.pack(),
+                );
+                if let Err(e) = junction.send_package(&package).await {
+                    junction.log(&format!("Failed to send stream segment: {}", e));
+                }
+            }
+        });
+    }
+
+    /// Starts the task that sends the packages produced by this junction's transfers.
+    ///
+    /// # Arguments
+    /// * `packages` - The channel Transfers hands packages to
+    fn start_transfer_sender(self: &Arc<Self>, mut packages: mpsc::UnboundedReceiver<SlowPackage>) {
+        let junction: Weak<Self> = Arc::downgrade(self);
+        task::spawn(async move {
+            while let Some(package) = packages.recv().await {
+                let junction = match junction.upgrade() {
+                    Some(junction) => junction,
+                    None => break,
+                };
+
+                if let Err(e) = junction.send_package(&package).await {
+                    junction.log(&format!("Failed to send transfer message: {}", e));
+                }
+            }
+        });
+    }
+
+    /// Performs periodic maintenance, such as resending packages that were not acknowledged in time.
+    async fn maintain(&self) {
+        let released = self.reorder_buffer.lock().await.take_expired();
+        for package in released {
+            self.deliver(package).await;
+        }
+
+        self.streams.lock().await.tick();
+
+        let retries = self.acks.lock().await.take_due();
+        for mut pending in retries {
+            let package_id = self.next_package_id();
+            pending.package_mut().set_retransmit(package_id);
+            let package = pending.package().clone();
+            self.acks.lock().await.retry(pending);
+
+            self.log(&format!(
+                "Resending unacknowledged package as {}",
+                package_id
+            ));
+            if let Err(e) = self.send_package_with_id(&package, package_id).await {
+                self.log(&format!("Failed to resend package: {}", e));
+            }
+        }
+
+        self.release_routable().await;
+        self.check_heartbeats().await;
+    }
+
+    /// Sends heartbeats when they are due and closes links that have gone quiet.
+    ///
+    /// Only links whose peer agreed to heartbeats are checked, since a peer that does
+    /// not send them may be silent for a long time.
+    async fn check_heartbeats(&self) {
+        let policy = match *self.heartbeat.lock().await {
+            Some(policy) => policy,
+            None => return,
+        };
+
+        let send = {
+            let mut next_heartbeat = self.next_heartbeat.lock().await;
+            let now = Instant::now();
+            if now >= *next_heartbeat {
+                *next_heartbeat = now + policy.interval;
+                true
+            } else {
+                false
+            }
+        };
+
+        // Take the links out of the lock so a slow send does not hold up the junction
+        let links: Vec<Arc<SlowTcpLink>> = self
+            .links
+            .lock()
+            .await
+            .values()
+            .filter(|link| {
+                link.agreement()
+                    .capabilities
+                    .contains(Capabilities::HEARTBEAT)
+            })
+            .cloned()
+            .collect();
+
+        let mut idle = Vec::new();
+        for link in links {
+            if link.idle_time() >= policy.idle_timeout {
+                idle.push(link);
+            } else if send && let Err(e) = link.send_heartbeat().await {
+                self.log(&format!(
+                    "Failed to send heartbeat on link {}: {}",
+                    link.id(),
+                    e
+                ));
+            }
+        }
+
+        // Closing the link ends its processing task, which removes it
+        for link in idle {
+            self.log(&format!(
+                "Closing link {} after {:?} without a frame",
+                link.id(),
+                link.idle_time()
+            ));
+            if let Err(e) = link.close().await {
+                self.log(&format!("Error closing link: {}", e));
+            }
+        }
+    }
+
+    /// Holds a package that could not be sent, if store-and-forward is on.
+    ///
+    /// A held copy is dropped once the recipient acks the package.
+    ///
+    /// # Arguments
+    /// * `package` - The package, carrying the package ID it was sent with
+    ///
+    /// # Returns
+    /// * `bool` - true if the package is held
+    async fn hold_for_recipient(&self, package: SlowPackage) -> bool {
+        match self.store.lock().await.as_mut() {
+            Some(store) => store.hold(package),
+            None => false,
+        }
+    }
+
+    /// Drops the held copies of a package that an ack shows was delivered.
+    ///
+    /// # Arguments
+    /// * `ack` - An Ack package on its way back to the sender of the acked package
+    async fn discard_held(&self, ack: &SlowPackage) {
+        let package_id = match ack.acked_package_id() {
+            Some(package_id) => package_id,
+            None => return,
+        };
+        if let Some(store) = self.store.lock().await.as_mut()
+            && store.discard(ack.recipient_id(), package_id)
+        {
+            self.log(&format!(
+                "Dropping held package {} from {}, which was delivered",
+                package_id,
+                ack.recipient_id()
+            ));
+        }
+    }
+
+    /// Counts a package as expired if its deadline has passed.
+    ///
+    /// # Arguments
+    /// * `package` - The package about to be sent, forwarded or delivered
+    ///
+    /// # Returns
+    /// * `bool` - true if the package expired and must be dropped
+    fn drop_if_expired(&self, package: &SlowPackage) -> bool {
+        if !package.is_expired() {
+            return false;
+        }
+
+        self.log(&format!(
+            "Dropping expired package {} from {} for {}",
+            package.package_id(),
+            package.sender_id(),
+            package.recipient_id()
+        ));
+        self.expired_package_count.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Drops held packages that are too old and sends those whose recipient can now be reached.
+    async fn release_routable(&self) {
+        let (expired, recipients) = match self.store.lock().await.as_mut() {
+            Some(store) => (store.expire(), store.recipients()),
+            None => return,
+        };
+
+        for package in expired {
+            self.log(&format!(
+                "Held package for {} expired undelivered",
+                package.recipient_id()
+            ));
+            self.report_delivery_error(DeliveryErrorKind::DestinationUnreachable, &package)
+                .await;
+        }
+
+        for recipient_id in recipients {
+            // The router remembers links that have since closed, so only open ones count
+            let heard_on = self.router.lock().await.get_links(&recipient_id);
+            let best_link = {
+                let links = self.links.lock().await;
+                heard_on
+                    .into_iter()
+                    .find(|link_id| links.contains_key(link_id))
+            };
+            let link_id = match best_link {
+                Some(link_id) => link_id,
+                None => continue,
+            };
+
+            let packages = match self.store.lock().await.as_mut() {
+                Some(store) => store.take(&recipient_id),
+                None => return,
+            };
+            for package in packages {
+                if self.drop_if_expired(&package) {
+                    continue;
+                }
+                let data = package.pack(package.package_id());
+                let permit = self.scheduler.acquire(package.priority()).await;
+                let result = self.forward(&data, link_id).await;
+                drop(permit);
+                if result.is_err() {
+                    self.hold_for_recipient(package).await;
+                } else {
+                    self.log(&format!("Delivered held package to {}", recipient_id));
+                }
+            }
+        }
+    }
+
     /// Starts processing data from a newly established TCP link.
     ///
     /// This method spawns a background task to continuously receive and process data
@@ -408,16 +1800,10 @@ impl SlowTcpJunction {
     fn start_processing(self: Arc<Self>, link: Arc<SlowTcpLink>) {
         // Spawn a tokio background task to handle incoming connections
         task::spawn(async move {
-            let mut buffer = vec![0u8; SlowTcpLink::max_frame_size()];
             loop {
-                match link.receive(&mut buffer).await {
-                    Ok(size) => {
-                        if size == 0 {
-                            self.remove_link(link.id()).await;
-                            break;
-                        }
-                        let data = &buffer[..size];
-                        self.process(data, link.id()).await;
+                match link.receive_message().await {
+                    Ok(data) => {
+                        self.process(&data, link.id()).await;
                     }
                     Err(_) => {
                         self.log("Error receiving data from link");
@@ -428,9 +1814,162 @@ impl SlowTcpJunction {
 
             self.remove_link(link.id()).await;
             self.log("Link processing task finished");
+            self.on_link_lost(link.id()).await;
+        });
+    }
+
+    /// Dials a remote junction and adds the link, remembering the address for reconnects.
+    ///
+    /// # Arguments
+    /// * `addr` - The remote address to connect to
+    ///
+    /// # Returns
+    /// Result indicating success or failure
+    async fn dial(self: Arc<Self>, addr: SocketAddr) -> std::io::Result<()> {
+        if !self.has_room(LinkDirection::Outbound).await {
+            self.rejected_link_count.fetch_add(1, Ordering::Relaxed);
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                "Link limit reached",
+            ));
+        }
+
+        let link = SlowTcpLink::connect(addr, &self.identity().await).await?;
+        let link = Arc::new(link);
+        self.dialed.lock().await.insert(link.id(), addr);
+        match self.add_link(link.clone()).await {
+            Ok(true) => {}
+            Ok(false) => {
+                // Already linked to this junction, and the redial address moved to that link
+                return Ok(());
+            }
+            Err(e) => {
+                self.dialed.lock().await.remove(&link.id());
+                return Err(e);
+            }
+        }
+        self.record_peer_event(addr, PeerEventKind::Connected { link_id: link.id() })
+            .await;
+        self.start_processing(link);
+        Ok(())
+    }
+
+    /// Reports a dropped dialed link and starts redialing it if reconnecting is on.
+    ///
+    /// # Arguments
+    /// * `link_id` - The ID of the link that dropped
+    async fn on_link_lost(self: &Arc<Self>, link_id: SlowLinkId) {
+        let addr = match self.dialed.lock().await.remove(&link_id) {
+            Some(addr) => addr,
+            None => return,
+        };
+        self.record_peer_event(addr, PeerEventKind::Disconnected { link_id })
+            .await;
+
+        if self.closed.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(policy) = *self.reconnect.lock().await {
+            self.start_reconnecting(addr, policy);
+        }
+    }
+
+    /// Starts a task that redials a peer with backoff until it succeeds or gives up.
+    ///
+    /// The task only holds a weak reference, so it stops once the junction is dropped.
+    ///
+    /// # Arguments
+    /// * `addr` - The address the peer was dialed at
+    /// * `policy` - The ReconnectPolicy to redial with
+    fn start_reconnecting(self: &Arc<Self>, addr: SocketAddr, policy: ReconnectPolicy) {
+        let junction: Weak<Self> = Arc::downgrade(self);
+        task::spawn(async move {
+            let mut attempt = 0;
+            loop {
+                attempt += 1;
+                let delay = policy.delay(attempt);
+                match junction.upgrade() {
+                    Some(junction) => {
+                        let kind = match policy.max_attempts {
+                            Some(max_attempts) if attempt > max_attempts => PeerEventKind::GaveUp {
+                                attempts: max_attempts,
+                            },
+                            _ => PeerEventKind::Reconnecting { attempt, delay },
+                        };
+                        let gave_up = matches!(kind, PeerEventKind::GaveUp { .. });
+                        junction.record_peer_event(addr, kind).await;
+                        if gave_up {
+                            return;
+                        }
+                    }
+                    None => return,
+                }
+
+                tokio::time::sleep(delay).await;
+
+                let junction = match junction.upgrade() {
+                    Some(junction) => junction,
+                    None => return,
+                };
+                if junction.closed.load(Ordering::Relaxed)
+                    || junction.reconnect.lock().await.is_none()
+                {
+                    return;
+                }
+
+                let error = match junction.clone().dial(addr).await {
+                    Ok(()) => return,
+                    Err(e) => e,
+                };
+                junction
+                    .record_peer_event(
+                        addr,
+                        PeerEventKind::ReconnectFailed {
+                            attempt,
+                            error: error.to_string(),
+                        },
+                    )
+                    .await;
+
+                // Redialing an incompatible peer would only be refused again
+                if error.kind() == std::io::ErrorKind::Unsupported {
+                    junction
+                        .record_peer_event(addr, PeerEventKind::GaveUp { attempts: attempt })
+                        .await;
+                    return;
+                }
+            }
         });
     }
 
+    /// Counts an event about a dialed peer and queues it for `receive_peer_event`.
+    ///
+    /// # Arguments
+    /// * `addr` - The address the peer was dialed at
+    /// * `kind` - What happened
+    async fn record_peer_event(&self, addr: SocketAddr, kind: PeerEventKind) {
+        self.log(&format!("Peer {}: {:?}", addr, kind));
+        self.peer_stats.lock().await.record(&kind);
+
+        let mut events = self.peer_events.lock().await;
+        if events.len() >= MAX_PEER_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(PeerEvent { addr, kind });
+    }
+
+    /// Acknowledges a package that asked for it.
+    ///
+    /// # Arguments
+    /// * `recipient_id` - The junction that sent the package
+    /// * `package_id` - The ID of the first transmission of the package
+    async fn send_ack(&self, recipient_id: JunctionId, package_id: u32) {
+        let ack = SlowPackage::new_ack(recipient_id, self.junction_id.clone(), package_id);
+        if let Err(e) = self.send_package(&ack).await {
+            self.log(&format!("Failed to send ack: {}", e));
+        }
+    }
+
     /// Processes received data from a TCP link.
     ///
     /// This function unpacks the received data into a SlowPackage and checks if it's intended
@@ -441,7 +1980,7 @@ impl SlowTcpJunction {
     /// * `link_id` - The ID of the link that received the data
     async fn process(&self, data: &[u8], link_id: SlowLinkId) {
         // Try to unpack the data into a SlowPackage
-        let package = match SlowPackage::unpack(data) {
+        let mut package = match SlowPackage::unpack(data) {
             Some(package) => package,
             None => {
                 self.log(&format!(
@@ -456,67 +1995,321 @@ impl SlowTcpJunction {
         let package_type = package.package_type();
         let recipient_id = package.recipient_id();
 
+        // Reject packages that have already been seen, including our own echoes
+        let is_new = {
+            let mut seen_packages = self.seen_packages.lock().await;
+            seen_packages.insert(package.sender_id(), package.package_id())
+        };
+        if !is_new {
+            self.log(&format!(
+                "Received already seen package {} from {} for {}",
+                package.package_id(),
+                package.sender_id(),
+                recipient_id
+            ));
+            self.rejected_package_count.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
         // Check the package against the router with the link_id
-        let best_link = {
-            let mut router = self.router.lock().await;
-            match router.update(&package, link_id) {
-                UpdateResult::Duplicate | UpdateResult::Old => {
-                    self.log(&format!(
-                        "Received old or duplicate package {} from {} for {}",
-                        package.package_id(),
-                        package.sender_id(),
-                        recipient_id
-                    ));
-                    self.rejected_package_count.fetch_add(1, Ordering::Relaxed);
-                    return;
-                }
-                UpdateResult::Success => {
-                    // Package is new and valid, continue processing
-                    self.log(&format!(
-                        "Received new package {} from {} for {}",
-                        package.package_id(),
-                        package.sender_id(),
-                        recipient_id
-                    ));
-                }
+        let update = self.router.lock().await.update(&package, link_id);
+        match update {
+            UpdateResult::Duplicate | UpdateResult::Old => {
+                self.log(&format!(
+                    "Received old or duplicate package {} from {} for {}",
+                    package.package_id(),
+                    package.sender_id(),
+                    recipient_id
+                ));
+                self.rejected_package_count.fetch_add(1, Ordering::Relaxed);
+                return;
             }
-
-            // Only broadcast Hello packages to all links
-            if package_type == Ok(PackageType::Howdy) {
-                self.broadcast(&data, Some(link_id))
-                    .await
-                    .unwrap_or_else(|e| {
-                        self.log(&format!("Failed to broadcast: {}", e));
-                        0
-                    });
+            UpdateResult::Success => {
+                // Package is new and valid, continue processing
+                self.log(&format!(
+                    "Received new package {} from {} for {}",
+                    package.package_id(),
+                    package.sender_id(),
+                    recipient_id
+                ));
             }
+        }
 
-            router.get_best_link(recipient_id)
-        };
+        // Only relay Howdy packages to other links
+        if package_type == Ok(PackageType::Howdy) {
+            let _permit = self.scheduler.acquire(Priority::Control).await;
+            self.relay(data, link_id).await;
+        }
+
+        let best_link = self.select_link(&package).await;
 
         // Increment the received package counter
         self.received_package_count.fetch_add(1, Ordering::Relaxed);
 
+        if self.drop_if_expired(&package) {
+            return;
+        }
+
+        if package_type == Ok(PackageType::Ack) {
+            self.discard_held(&package).await;
+        }
+
         // Check if the package is intended for this junction
         if *recipient_id == self.junction_id {
-            // Lock the deque and add the package
-            let mut received_packages = self.received_packages.lock().await;
-            self.log("Package is for this junction, saving to queue");
-            received_packages.push_back(package);
-        } else {
-            if best_link.is_some() {
-                let best_link = best_link.unwrap();
+            let ack = package
+                .is_ack_requested()
+                .then(|| (package.sender_id().clone(), package.first_package_id()));
+
+            // A resent copy of a delivered package only needs to be acked again
+            if let Some((sender_id, package_id)) = &ack
+                && self
+                    .delivered_packages
+                    .lock()
+                    .await
+                    .contains(sender_id, *package_id)
+            {
                 self.log(&format!(
-                    "Forwarding package through best link {}",
-                    best_link
+                    "Received resent package {} from {} that was already delivered",
+                    package.package_id(),
+                    sender_id
                 ));
+                self.rejected_package_count.fetch_add(1, Ordering::Relaxed);
+                self.send_ack(sender_id.clone(), *package_id).await;
+                return;
+            }
+
+            match package_type {
+                Ok(PackageType::Ack) => {
+                    if let Some(package_id) = package.acked_package_id() {
+                        self.acks.lock().await.acknowledge(package_id);
+                    }
+                }
+                Ok(PackageType::Traceroute) => self.on_traceroute_received(package).await,
+                Ok(PackageType::TracerouteReply) => {
+                    self.on_traceroute_reply_received(package).await
+                }
+                Ok(PackageType::DeliveryError) => self.on_error_received(package).await,
+                Ok(PackageType::RpcRequest) => self.on_rpc_request_received(package).await,
+                Ok(PackageType::RpcResponse) => self.on_rpc_response_received(package).await,
+                Ok(PackageType::Stream) => {
+                    if let Some(segment) = StreamSegment::unpack(&package.payload) {
+                        let opened = self
+                            .streams
+                            .lock()
+                            .await
+                            .handle(package.sender_id(), segment);
+                        if let Some(stream) = opened {
+                            self.on_stream_opened(stream).await;
+                        }
+                    }
+                }
+                Ok(PackageType::Transfer) => {
+                    self.transfers
+                        .lock()
+                        .await
+                        .handle(package.sender_id(), &package.payload);
+                }
+                _ => {
+                    if let Some(sequence) = package.sequence() {
+                        let sender_id = package.sender_id().clone();
+                        let released = self
+                            .reorder_buffer
+                            .lock()
+                            .await
+                            .push(&sender_id, sequence, package);
+                        for package in released {
+                            self.deliver(package).await;
+                        }
+                    } else if !self.deliver(package).await {
+                        return;
+                    }
+                }
+            }
+
+            if let Some((sender_id, package_id)) = ack {
+                self.delivered_packages
+                    .lock()
+                    .await
+                    .insert(&sender_id, package_id);
+                self.send_ack(sender_id, package_id).await;
+            }
+        } else if let Some(best_link) = best_link {
+            self.log(&format!(
+                "Forwarding package through best link {}",
+                best_link
+            ));
 
-                let result = self.forward(&data, best_link).await;
-                if result.is_ok() {
+            let permit = self.scheduler.acquire(package.priority()).await;
+            let result = if package_type == Ok(PackageType::Traceroute) {
+                // Record this junction on the path before passing the traceroute on
+                TracerouteRecord::record_hop(&mut package, &self.junction_id);
+                let data = package.pack(package.package_id());
+                self.forward(&data, best_link).await
+            } else {
+                self.forward(data, best_link).await
+            };
+            drop(permit);
+            match result {
+                Ok(_) => {
                     self.sent_package_count.fetch_add(1, Ordering::Relaxed);
-                    return;
+                }
+                Err(e) => {
+                    self.log(&format!("Failed to forward package: {}", e));
+                    if !self.hold_for_recipient(package.clone()).await {
+                        self.report_delivery_error(
+                            DeliveryErrorKind::DestinationUnreachable,
+                            &package,
+                        )
+                        .await;
+                    }
                 }
             }
+        } else if self.hold_for_recipient(package.clone()).await {
+            self.log(&format!(
+                "No link towards {}; holding package",
+                package.recipient_id()
+            ));
+        } else if DeliveryError::is_reportable(&package) {
+            self.log(&format!(
+                "No link towards {}; dropping package",
+                package.recipient_id()
+            ));
+            self.report_delivery_error(DeliveryErrorKind::DestinationUnreachable, &package)
+                .await;
+        }
+    }
+
+    /// Handles a received traceroute by recording this junction and echoing the record to the origin.
+    ///
+    /// # Arguments
+    /// * `package` - The traceroute package that was received
+    async fn on_traceroute_received(&self, mut package: SlowPackage) {
+        if !TracerouteRecord::record_hop(&mut package, &self.junction_id) {
+            return;
+        }
+
+        let reply = SlowPackage::new_traceroute_reply(
+            package.sender_id().clone(),
+            self.junction_id.clone(),
+            &package.payload,
+        );
+        if let Err(e) = self.send_package(&reply).await {
+            self.log(&format!("Failed to send traceroute reply: {}", e));
+        }
+    }
+
+    /// Handles a received traceroute reply by completing the traceroute waiting on it.
+    ///
+    /// # Arguments
+    /// * `package` - The traceroute reply package that was received
+    async fn on_traceroute_reply_received(&self, package: SlowPackage) {
+        let record = match TracerouteRecord::unpack(&package.payload) {
+            Some(record) => record,
+            None => return,
+        };
+
+        let pending = self
+            .pending_traceroutes
+            .lock()
+            .await
+            .remove(&record.trace_id);
+        if let Some(sender) = pending {
+            let _ = sender.send(record.hops);
+        }
+    }
+
+    /// Handles a received delivery error package by queuing the delivery error it reports.
+    ///
+    /// # Arguments
+    /// * `package` - The delivery error package that was received
+    async fn on_error_received(&self, package: SlowPackage) {
+        if let Some(error) = DeliveryError::from_package(&package) {
+            self.log(&format!(
+                "Package {} to {} was dropped by {}: {:?}",
+                error.package_id, error.recipient_id, error.reporter_id, error.kind
+            ));
+            self.delivery_errors.lock().await.push_back(error);
+        }
+    }
+
+    /// Handles a received RPC request by running its handler and sending the response to the caller.
+    ///
+    /// # Arguments
+    /// * `package` - The RPC request package that was received
+    async fn on_rpc_request_received(&self, package: SlowPackage) {
+        let request = match RpcRequest::unpack(&package.payload) {
+            Some(request) => request,
+            None => return,
+        };
+
+        let handler = self.rpc_handlers.lock().await.get(&request.method);
+        let response = rpc::serve(handler, request);
+
+        let reply = SlowPackage::new_rpc_response(
+            package.sender_id().clone(),
+            self.junction_id.clone(),
+            &response.pack(),
+        );
+        if let Err(e) = self.send_package(&reply).await {
+            self.log(&format!("Failed to send RPC response: {}", e));
+        }
+    }
+
+    /// Handles a received RPC response by completing the call waiting on it.
+    ///
+    /// # Arguments
+    /// * `package` - The RPC response package that was received
+    async fn on_rpc_response_received(&self, package: SlowPackage) {
+        if let Some(response) = RpcResponse::unpack(&package.payload) {
+            self.rpc_calls.lock().await.complete(response);
+        }
+    }
+
+    /// Adds a package for this junction to the received queue.
+    ///
+    /// # Arguments
+    /// * `package` - The package to deliver
+    ///
+    /// # Returns
+    /// * `bool` - false if the received queue was full and the package was dropped
+    async fn deliver(&self, package: SlowPackage) -> bool {
+        // Ordered packages may have expired while waiting in the reorder buffer
+        if self.drop_if_expired(&package) {
+            return true;
+        }
+
+        // Lock the deque and add the package
+        let mut received_packages = self.received_packages.lock().await;
+        if received_packages.len() >= self.receive_queue_capacity.load(Ordering::Relaxed) {
+            drop(received_packages);
+            self.log("Received queue is full, dropping package");
+            self.report_delivery_error(DeliveryErrorKind::QueueFull, &package)
+                .await;
+            return false;
+        }
+        self.log("Package is for this junction, saving to queue");
+        received_packages.push_back(package);
+        self.received_notify.notify_one();
+        true
+    }
+
+    /// Reports a dropped package back to its sender.
+    ///
+    /// Nothing is sent if the package is not reportable, such as a delivery error package itself.
+    /// A package this junction sent is reported to it directly.
+    ///
+    /// # Arguments
+    /// * `kind` - Why the package was dropped
+    /// * `package` - The dropped package
+    async fn report_delivery_error(&self, kind: DeliveryErrorKind, package: &SlowPackage) {
+        let error = match DeliveryError::report(kind, package, &self.junction_id) {
+            Some(error) => error,
+            None => return,
+        };
+        if *error.recipient_id() == self.junction_id {
+            self.on_error_received(error).await;
+        } else if let Err(e) = self.send_package(&error).await {
+            self.log(&format!("Failed to report delivery error: {}", e));
         }
     }
 }
@@ -1,14 +1,32 @@
-use super::tcp_frame::SlowTcpFrame;
+use super::tcp_frame::{FrameFlags, FrameType, SlowTcpFrame};
+use super::tcp_handshake::{
+    HELLO_BUSY, HELLO_MESSAGE, HELLO_REFUSED, HELLO_RESPONSE, LinkIdentity, MAX_HANDSHAKE_SIZE,
+};
 use super::tcp_listener::SlowTcpListener;
 use super::tcp_stream::SlowTcpStream;
+use crate::capability::Agreement;
 use std::io;
 use std::net::SocketAddr;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::timeout;
 
-const HELLO_MESSAGE: &[u8] = b"SLOW_HELLO";
-const HELLO_RESPONSE: &[u8] = b"SLOW_WELCOME";
+/// How often a heartbeat is sent on an otherwise quiet link by default.
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a link may go without receiving anything before it is closed by default.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The most frames waiting to be written on a link before sends to it fail.
+pub const LINK_QUEUE_CAPACITY: usize = 1024;
+
+/// The largest message a link puts back together from chunks by default.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// How long closing a link waits for its queued frames to be written.
+const LINK_CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
 
 // Static counter for assigning unique IDs to each SlowTcpLink
 static NEXT_ID: AtomicU32 = AtomicU32::new(0);
@@ -16,13 +34,75 @@ static NEXT_ID: AtomicU32 = AtomicU32::new(0);
 /// Unique identifier for a SlowTcpLink instance
 pub type SlowLinkId = u32;
 
+/// How often heartbeats are sent and how long a link may stay silent.
+///
+/// Heartbeats are frames of their own type, so they are consumed by the link and never
+/// reach the junction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatPolicy {
+    /// How often a heartbeat is sent on each link.
+    pub interval: Duration,
+    /// How long a link may go without receiving anything before it is closed.
+    pub idle_timeout: Duration,
+}
+
+impl Default for HeartbeatPolicy {
+    fn default() -> Self {
+        HeartbeatPolicy {
+            interval: DEFAULT_HEARTBEAT_INTERVAL,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+}
+
+/// Which end of a link dialed the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkDirection {
+    /// The remote junction dialed this one.
+    Inbound,
+    /// This junction dialed the remote one.
+    Outbound,
+}
+
+/// An item in a link's outgoing queue.
+enum Outgoing {
+    /// A frame to write, or a message to write as chunks if it is larger than a frame.
+    Frame(FrameType, FrameFlags, Vec<u8>),
+    /// Signalled once every frame queued before it has been written.
+    Flush(oneshot::Sender<()>),
+}
+
 /// A TCP-based link for the SLOW protocol that handles connection establishment
 /// and data transfer with length-prefixed framing.
+///
+/// Every frame is typed, so handshakes, packages, heartbeats and the close notice share
+/// the stream, and frames of types this version does not know are skipped.
+///
+/// Frames are sent through a bounded queue drained by the link's own writer task, so
+/// a peer that reads slowly only holds up sends to itself.
 pub struct SlowTcpLink {
     /// The underlying TCP stream for this link
-    stream: SlowTcpStream,
+    stream: Arc<SlowTcpStream>,
+    /// Frames waiting to be written by the writer task
+    outgoing: mpsc::Sender<Outgoing>,
     /// Unique identifier for this link instance
     id: SlowLinkId,
+    /// The junction at the other end, as told in the handshake
+    peer: LinkIdentity,
+    /// The nonce this end sent in the handshake
+    nonce: u64,
+    /// The protocol version and features settled on in the handshake
+    agreement: Agreement,
+    /// Which end dialed the other
+    direction: LinkDirection,
+    /// When the link was established
+    created: Instant,
+    /// When a frame last arrived, in milliseconds since the link was established
+    last_received: AtomicU64,
+    /// Whether package frames are sent with a checksum
+    checksums: AtomicBool,
+    /// The largest message received, in bytes
+    max_message_size: AtomicUsize,
 }
 
 // ---
@@ -36,57 +116,123 @@ impl SlowTcpLink {
     ///
     /// # Arguments
     /// * `stream` - The TCP stream for this link
+    /// * `nonce` - The nonce this end sent in the handshake
+    /// * `peer` - The identity the remote junction gave in the handshake
+    /// * `agreement` - The protocol version and features settled on in the handshake
+    /// * `direction` - Which end dialed the other
     ///
     /// # Returns
     /// A new SlowTcpLink instance
-    fn new(stream: SlowTcpStream) -> Self {
+    fn new(
+        stream: SlowTcpStream,
+        nonce: u64,
+        peer: LinkIdentity,
+        agreement: Agreement,
+        direction: LinkDirection,
+    ) -> Self {
         let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
-        Self { stream, id }
+        let stream = Arc::new(stream);
+        let (outgoing, queue) = mpsc::channel(LINK_QUEUE_CAPACITY);
+        Self::start_writer(stream.clone(), queue);
+        Self {
+            stream,
+            outgoing,
+            id,
+            peer,
+            nonce,
+            agreement,
+            direction,
+            created: Instant::now(),
+            last_received: AtomicU64::new(0),
+            checksums: AtomicBool::new(false),
+            max_message_size: AtomicUsize::new(DEFAULT_MAX_MESSAGE_SIZE),
+        }
     }
 
     /// Connects to a remote SLOW endpoint and performs a handshake.
     ///
     /// # Arguments
     /// * `addr` - The socket address to connect to
+    /// * `identity` - The identity of the local junction, sent to the remote end
     ///
     /// # Returns
     /// A new SlowTcpLink if connection and handshake succeed
     ///
     /// # Errors
-    /// Returns an error if connection fails or handshake is unsuccessful
-    pub async fn connect(addr: SocketAddr) -> io::Result<Self> {
+    /// Returns an error if connection fails or handshake is unsuccessful, with kind
+    /// `Unsupported` if either junction refused the other as incompatible, or
+    /// `ConnectionRefused` if the remote junction had no room for the link
+    pub async fn connect(addr: SocketAddr, identity: &LinkIdentity) -> io::Result<Self> {
         let stream = SlowTcpStream::connect(addr).await?;
-        let slow_link = Self::new(stream);
-        if !slow_link.hello().await {
-            return Err(io::Error::new(
-                io::ErrorKind::ConnectionRefused,
-                "Hello handshake failed",
-            ));
-        }
-        Ok(slow_link)
+        let identity = identity.with_fresh_nonce();
+        let (peer, agreement) = Self::hello(&stream, &identity).await?;
+        Ok(Self::new(
+            stream,
+            identity.nonce,
+            peer,
+            agreement,
+            LinkDirection::Outbound,
+        ))
     }
 
     /// Listens for an incoming SLOW connection and performs a welcome handshake.
     ///
     /// # Arguments
     /// * `addr` - The socket address to listen on
+    /// * `identity` - The identity of the local junction, sent to the remote end
     ///
     /// # Returns
     /// A new SlowTcpLink if connection and handshake succeed
     ///
     /// # Errors
     /// Returns an error if listening fails or handshake is unsuccessful
-    pub async fn listen(addr: SocketAddr) -> io::Result<Self> {
+    pub async fn listen(addr: SocketAddr, identity: &LinkIdentity) -> io::Result<Self> {
         let listener = SlowTcpListener::new(addr).await?;
         let stream = listener.accept().await?;
-        let slow_link = Self::new(stream);
-        if !slow_link.welcome().await {
-            return Err(io::Error::new(
-                io::ErrorKind::ConnectionRefused,
-                "Welcome handshake failed",
-            ));
-        }
-        Ok(slow_link)
+        Self::accept(stream, identity).await
+    }
+
+    /// Performs the welcome handshake on a connection accepted by a listener.
+    ///
+    /// # Arguments
+    /// * `stream` - The accepted TCP stream
+    /// * `identity` - The identity of the local junction, sent to the remote end
+    ///
+    /// # Returns
+    /// A new SlowTcpLink if the handshake succeeds
+    ///
+    /// # Errors
+    /// Returns an error if the handshake is unsuccessful, with kind `Unsupported` if the
+    /// remote junction is incompatible
+    pub async fn accept(stream: SlowTcpStream, identity: &LinkIdentity) -> io::Result<Self> {
+        let identity = identity.with_fresh_nonce();
+        let (peer, agreement) = Self::welcome(&stream, &identity).await?;
+        Ok(Self::new(
+            stream,
+            identity.nonce,
+            peer,
+            agreement,
+            LinkDirection::Inbound,
+        ))
+    }
+
+    /// Turns away a connection accepted by a listener because there is no room for it.
+    ///
+    /// The remote junction's hello is answered with a busy message, so its connect fails
+    /// with `ConnectionRefused` and may be retried later.
+    ///
+    /// # Arguments
+    /// * `stream` - The accepted TCP stream
+    /// * `reason` - Why the connection is turned away, sent to the remote end
+    ///
+    /// # Errors
+    /// Returns an error if the hello could not be received or the answer could not be sent
+    pub async fn refuse(stream: SlowTcpStream, reason: &str) -> io::Result<()> {
+        Self::receive_handshake(&stream).await?;
+        let mut busy = HELLO_BUSY.to_vec();
+        busy.extend_from_slice(reason.as_bytes());
+        SlowTcpFrame::send(FrameType::Handshake, FrameFlags::NONE, &busy, &stream).await?;
+        stream.close().await
     }
 
     /// Returns the maximum allowed frame size for this link implementation.
@@ -111,22 +257,133 @@ impl SlowTcpLink {
         self.id
     }
 
-    /// Sends data over the link with length-prefix framing.
+    /// Returns the identity of the junction at the other end of this link
+    ///
+    /// # Returns
+    /// The identity the remote junction gave in the handshake
+    pub fn peer(&self) -> &LinkIdentity {
+        &self.peer
+    }
+
+    /// Returns what the two junctions settled on in the handshake
+    ///
+    /// # Returns
+    /// The protocol version and the optional features both junctions support
+    pub fn agreement(&self) -> Agreement {
+        self.agreement
+    }
+
+    /// Returns which end of this link dialed the other
+    ///
+    /// # Returns
+    /// `Outbound` if this junction dialed the link, `Inbound` if it accepted it
+    pub fn direction(&self) -> LinkDirection {
+        self.direction
+    }
+
+    /// Returns the nonces the two ends sent in the handshake, the dialing end's first
+    ///
+    /// Both junctions see the same pair for a link, so it orders the links between
+    /// them the same way at both ends.
+    ///
+    /// # Returns
+    /// The dialing junction's nonce and the accepting junction's nonce
+    pub fn handshake_nonces(&self) -> (u64, u64) {
+        match self.direction {
+            LinkDirection::Outbound => (self.nonce, self.peer.nonce),
+            LinkDirection::Inbound => (self.peer.nonce, self.nonce),
+        }
+    }
+
+    /// Returns the address the remote junction accepts links on
+    ///
+    /// # Returns
+    /// The remote address with the listening port from the handshake, or None if the
+    /// remote address could not be determined
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.stream
+            .peer_addr()
+            .map(|addr| self.peer.listen_addr(addr))
+    }
+
+    /// Queues data to be sent over the link with length-prefix framing.
+    ///
+    /// This never waits for the peer: the frame is written by the link's writer task.
     ///
     /// # Arguments
     /// * `data` - The byte slice to send
     ///
     /// # Returns
-    /// The number of bytes sent (not including framing)
+    /// The number of bytes queued (not including framing)
     ///
     /// # Errors
-    /// Returns an error if the data is too large or if the transmission fails
+    /// Returns an error if the data is too large, `WouldBlock` if the link's queue is
+    /// full, or `BrokenPipe` if the link can no longer write
     pub async fn send(&self, data: &[u8]) -> io::Result<usize> {
-        SlowTcpFrame::send(data, &self.stream).await
+        SlowTcpFrame::check_size(data.len())?;
+        self.enqueue(FrameType::Package, self.package_flags(), data.to_vec())?;
+        Ok(data.len())
+    }
+
+    /// Queues a message of any size to be sent over the link.
+    ///
+    /// A message larger than a frame is written as consecutive chunks, which the remote
+    /// link puts back together. Like `send`, this never waits for the peer.
+    ///
+    /// # Arguments
+    /// * `data` - The message to send
+    ///
+    /// # Returns
+    /// The number of bytes queued (not including framing)
+    ///
+    /// # Errors
+    /// Returns `WouldBlock` if the link's queue is full, or `BrokenPipe` if the link
+    /// can no longer write
+    pub async fn send_message(&self, data: &[u8]) -> io::Result<usize> {
+        self.enqueue(FrameType::Package, self.package_flags(), data.to_vec())?;
+        Ok(data.len())
+    }
+
+    /// Sets whether package frames are sent with a checksum.
+    ///
+    /// The remote link checks any checksum it receives, and closes the link on a mismatch.
+    ///
+    /// # Arguments
+    /// * `enabled` - `true` to add a CRC-32 to every package frame sent from now on
+    pub fn set_checksums(&self, enabled: bool) {
+        self.checksums.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if package frames are sent with a checksum.
+    pub fn checksums(&self) -> bool {
+        self.checksums.load(Ordering::Relaxed)
+    }
+
+    /// Sets the largest message this link accepts, in bytes.
+    ///
+    /// A message that grows past the limit is not buffered; the link fails instead.
+    ///
+    /// # Arguments
+    /// * `size` - The most bytes in a received message
+    pub fn set_max_message_size(&self, size: usize) {
+        self.max_message_size.store(size, Ordering::Relaxed);
+    }
+
+    /// Returns the largest message this link accepts, in bytes.
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of frames waiting to be written, counting a chunked message once.
+    pub fn queued_frames(&self) -> usize {
+        LINK_QUEUE_CAPACITY - self.outgoing.capacity()
     }
 
     /// Receives data from the link with length-prefix framing validation.
     ///
+    /// Only package messages are returned, with their chunks put back together;
+    /// heartbeats and frames of unknown types are consumed here.
+    ///
     /// # Arguments
     /// * `buffer` - Buffer to store the received data
     ///
@@ -134,21 +391,103 @@ impl SlowTcpLink {
     /// The number of bytes read into the buffer
     ///
     /// # Errors
-    /// Returns an error if the buffer is too small, if reading fails,
-    /// or if the frame is invalid.
+    /// Returns an error if the buffer is too small, if reading fails, if the frame
+    /// is invalid, or `ConnectionReset` if the remote end closed the link.
     pub async fn receive(&self, buffer: &mut [u8]) -> io::Result<usize> {
-        SlowTcpFrame::receive(buffer, &self.stream).await
+        let mut size = 0;
+        loop {
+            let header = SlowTcpFrame::receive(&mut buffer[size..], &self.stream).await?;
+            self.mark_received();
+
+            match header.frame_type {
+                FrameType::Package => {
+                    size += header.size;
+                    if !header.flags.contains(FrameFlags::MORE) {
+                        return Ok(size);
+                    }
+                }
+                FrameType::Close => return Err(self.closed_by_peer()),
+                // Heartbeats only show the link is alive, and newer frame types are skipped
+                _ => continue,
+            }
+        }
+    }
+
+    /// Receives the next package message, in a buffer sized to the message.
+    ///
+    /// Unlike `receive`, nothing is allocated up front: each chunk is read into memory
+    /// only as it arrives, and only up to `max_message_size` in total.
+    ///
+    /// # Returns
+    /// The received message
+    ///
+    /// # Errors
+    /// Returns `InvalidData` if the message is over `max_message_size` or a frame is
+    /// invalid, `ConnectionReset` if the remote end closed the link, or an error if
+    /// reading fails.
+    pub async fn receive_message(&self) -> io::Result<Vec<u8>> {
+        let mut message = Vec::new();
+        loop {
+            let remaining = self.max_message_size().saturating_sub(message.len());
+            let (header, data) = SlowTcpFrame::receive_owned(remaining, &self.stream).await?;
+            self.mark_received();
+
+            match header.frame_type {
+                FrameType::Package => {
+                    if message.is_empty() {
+                        message = data;
+                    } else {
+                        message.extend_from_slice(&data);
+                    }
+                    if !header.flags.contains(FrameFlags::MORE) {
+                        return Ok(message);
+                    }
+                }
+                FrameType::Close => return Err(self.closed_by_peer()),
+                // Heartbeats only show the link is alive, and newer frame types are skipped
+                _ => continue,
+            }
+        }
+    }
+
+    /// Sends a heartbeat frame, which the remote link consumes.
+    ///
+    /// # Returns
+    /// * `io::Result<()>` - Ok if the heartbeat was sent, or an IO error
+    pub async fn send_heartbeat(&self) -> io::Result<()> {
+        self.enqueue(FrameType::Heartbeat, FrameFlags::NONE, Vec::new())
+    }
+
+    /// Returns how long it has been since a frame arrived on the link.
+    ///
+    /// # Returns
+    /// The time since the last frame, or since the link was established if none has arrived
+    pub fn idle_time(&self) -> Duration {
+        let last_received = Duration::from_millis(self.last_received.load(Ordering::Relaxed));
+        self.created.elapsed().saturating_sub(last_received)
     }
 
     /// Closes the TCP connection.
     ///
-    /// This method will shut down the underlying TCP stream,
-    /// preventing further communication on this link.
+    /// This method waits briefly for the queued frames and a close frame to be written,
+    /// then shuts down the underlying TCP stream, preventing further communication on
+    /// this link.
     ///
     /// # Returns
-    /// * `io::Result<()>` - Ok if the shutdown was successful, or an IO error
+    /// * `io::Result<()>` - Ok if the shutdown was successful or the remote end had already
+    ///   dropped the connection, or an IO error
     pub async fn close(&self) -> io::Result<()> {
-        self.stream.close().await
+        let _ = self.enqueue(FrameType::Close, FrameFlags::NONE, Vec::new());
+        let (flushed, written) = oneshot::channel();
+        if self.outgoing.try_send(Outgoing::Flush(flushed)).is_ok() {
+            let _ = timeout(LINK_CLOSE_TIMEOUT, written).await;
+        }
+
+        // The remote end may act on the close frame and drop the connection first
+        match self.stream.close().await {
+            Err(e) if e.kind() == io::ErrorKind::NotConnected => Ok(()),
+            result => result,
+        }
     }
 }
 
@@ -157,54 +496,194 @@ impl SlowTcpLink {
 // ---
 
 impl SlowTcpLink {
+    /// Returns the flags set on outgoing package frames.
+    fn package_flags(&self) -> FrameFlags {
+        match self.checksums.load(Ordering::Relaxed) {
+            true => FrameFlags::CHECKSUM,
+            false => FrameFlags::NONE,
+        }
+    }
+
+    /// Records that a frame arrived, for `idle_time`.
+    fn mark_received(&self) {
+        let elapsed = self.created.elapsed().as_millis() as u64;
+        self.last_received.store(elapsed, Ordering::Relaxed);
+    }
+
+    /// Returns the error reported once the remote end has sent a close frame.
+    fn closed_by_peer(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::ConnectionReset,
+            format!("Link {} closed by peer", self.id),
+        )
+    }
+
+    /// Adds a frame to the outgoing queue.
+    ///
+    /// # Arguments
+    /// * `frame_type` - What the frame carries
+    /// * `flags` - The options to set on the frame
+    /// * `data` - The data of the frame
+    ///
+    /// # Errors
+    /// Returns `WouldBlock` if the queue is full, or `BrokenPipe` if the writer task has stopped
+    fn enqueue(&self, frame_type: FrameType, flags: FrameFlags, data: Vec<u8>) -> io::Result<()> {
+        self.outgoing
+            .try_send(Outgoing::Frame(frame_type, flags, data))
+            .map_err(|e| match e {
+                mpsc::error::TrySendError::Full(_) => io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!("Link {} send queue is full", self.id),
+                ),
+                mpsc::error::TrySendError::Closed(_) => io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    format!("Link {} can no longer write", self.id),
+                ),
+            })
+    }
+
+    /// Starts the task that writes queued frames to the stream.
+    ///
+    /// The task stops when the link is dropped or a write fails.
+    ///
+    /// # Arguments
+    /// * `stream` - The stream to write to
+    /// * `queue` - The link's outgoing queue
+    fn start_writer(stream: Arc<SlowTcpStream>, mut queue: mpsc::Receiver<Outgoing>) {
+        tokio::spawn(async move {
+            while let Some(item) = queue.recv().await {
+                match item {
+                    Outgoing::Frame(frame_type, flags, data) => {
+                        if SlowTcpFrame::send_message(frame_type, flags, &data, &stream)
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Outgoing::Flush(flushed) => {
+                        let _ = flushed.send(());
+                    }
+                }
+            }
+        });
+    }
+
     /// Performs the client side of the handshake by sending a hello message
     /// and verifying the response.
     ///
+    /// # Arguments
+    /// * `stream` - The newly connected stream
+    /// * `identity` - The identity of the local junction
+    ///
     /// # Returns
-    /// `true` if the handshake was successful, `false` otherwise
-    async fn hello(&self) -> bool {
+    /// The identity of the remote junction and the agreement reached with it
+    ///
+    /// # Errors
+    /// Returns an error if the handshake fails or either junction refuses the other
+    async fn hello(
+        stream: &SlowTcpStream,
+        identity: &LinkIdentity,
+    ) -> io::Result<(LinkIdentity, Agreement)> {
         // Send the hello message
-        let send_result = self.send(HELLO_MESSAGE).await;
-        if send_result.is_err() {
-            return false;
-        }
+        let hello = identity.pack(HELLO_MESSAGE);
+        SlowTcpFrame::send(FrameType::Handshake, FrameFlags::NONE, &hello, stream).await?;
 
         // Wait for response with 5 second timeout
-        let mut buffer = [0u8; 32];
-        let receive_result = timeout(Duration::from_secs(5), self.receive(&mut buffer)).await;
-
-        match receive_result {
-            Ok(Ok(bytes_read)) => {
-                // Check if the response matches what we expect
-                bytes_read == HELLO_RESPONSE.len() && &buffer[..bytes_read] == HELLO_RESPONSE
-            }
-            _ => false,
+        let response = Self::receive_handshake(stream).await?;
+        if let Some(reason) = response.strip_prefix(HELLO_REFUSED) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Refused by peer: {}", String::from_utf8_lossy(reason)),
+            ));
+        }
+        if let Some(reason) = response.strip_prefix(HELLO_BUSY) {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("Peer is busy: {}", String::from_utf8_lossy(reason)),
+            ));
         }
+
+        let peer = LinkIdentity::unpack(HELLO_RESPONSE, &response).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::ConnectionRefused, "Hello handshake failed")
+        })?;
+        let agreement = identity
+            .offer
+            .negotiate(&peer.offer)
+            .map_err(|e| io::Error::new(io::ErrorKind::Unsupported, e))?;
+        Ok((peer, agreement))
     }
 
     /// Performs the server side of the handshake by receiving a hello message
-    /// and sending back a welcome response.
+    /// and sending back a welcome response, or a refusal if the junctions are incompatible.
+    ///
+    /// # Arguments
+    /// * `stream` - The newly accepted stream
+    /// * `identity` - The identity of the local junction
     ///
     /// # Returns
-    /// `true` if the handshake was successful, `false` otherwise
-    async fn welcome(&self) -> bool {
+    /// The identity of the remote junction and the agreement reached with it
+    ///
+    /// # Errors
+    /// Returns an error if the handshake fails or the remote junction is refused
+    async fn welcome(
+        stream: &SlowTcpStream,
+        identity: &LinkIdentity,
+    ) -> io::Result<(LinkIdentity, Agreement)> {
         // Read and verify the hello message with 5 second timeout
-        let mut buffer = [0u8; 32];
-        let receive_result = timeout(Duration::from_secs(5), self.receive(&mut buffer)).await;
-        let bytes_read = match receive_result {
-            Ok(Ok(n)) => n,
-            _ => return false,
-        };
+        let hello = Self::receive_handshake(stream).await?;
+        let peer = LinkIdentity::unpack(HELLO_MESSAGE, &hello).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::ConnectionRefused, "Welcome handshake failed")
+        })?;
 
-        if bytes_read != HELLO_MESSAGE.len() || &buffer[..bytes_read] != HELLO_MESSAGE {
-            return false;
-        }
+        let agreement = match identity.offer.negotiate(&peer.offer) {
+            Ok(agreement) => agreement,
+            Err(e) => {
+                // Tell the remote junction why before giving up on the link
+                let mut refusal = HELLO_REFUSED.to_vec();
+                refusal.extend_from_slice(e.to_string().as_bytes());
+                let _ =
+                    SlowTcpFrame::send(FrameType::Handshake, FrameFlags::NONE, &refusal, stream)
+                        .await;
+                return Err(io::Error::new(io::ErrorKind::Unsupported, e));
+            }
+        };
 
         // Send the welcome response
-        if let Err(_) = self.send(HELLO_RESPONSE).await {
-            return false;
-        }
+        let welcome = identity.pack(HELLO_RESPONSE);
+        SlowTcpFrame::send(FrameType::Handshake, FrameFlags::NONE, &welcome, stream).await?;
+
+        Ok((peer, agreement))
+    }
 
-        true
+    /// Receives one handshake message, waiting at most 5 seconds.
+    ///
+    /// # Arguments
+    /// * `stream` - The stream the handshake is running on
+    ///
+    /// # Returns
+    /// The received message
+    async fn receive_handshake(stream: &SlowTcpStream) -> io::Result<Vec<u8>> {
+        timeout(Duration::from_secs(5), async {
+            // Skip any other frames a newer junction sends before its handshake, whatever their size
+            loop {
+                let header = SlowTcpFrame::receive_header(stream).await?;
+                if header.frame_type != FrameType::Handshake {
+                    SlowTcpFrame::skip(&header, stream).await?;
+                    continue;
+                }
+                if header.size > MAX_HANDSHAKE_SIZE {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Handshake of {} bytes is too large", header.size),
+                    ));
+                }
+                let mut buffer = vec![0u8; header.size];
+                SlowTcpFrame::receive_data(&header, &mut buffer, stream).await?;
+                return Ok(buffer);
+            }
+        })
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Handshake timed out"))?
     }
 }
@@ -1,6 +1,9 @@
 use super::tcp_stream::SlowTcpStream;
 use std::net::SocketAddr;
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpSocket};
+
+/// The most connections waiting to be accepted before new ones are refused.
+const LISTEN_BACKLOG: u32 = 1024;
 
 pub struct SlowTcpListener {
     listener: TcpListener,
@@ -12,6 +15,26 @@ impl SlowTcpListener {
         Ok(Self { listener })
     }
 
+    /// Binds a listener without waiting, so it can be created outside of an async function.
+    ///
+    /// Must be called from within a tokio runtime.
+    ///
+    /// # Arguments
+    /// * `addr` - The socket address to listen on; port 0 picks a free port
+    ///
+    /// # Returns
+    /// * `std::io::Result<Self>` - The listener, or an IO error if the address could not be bound
+    pub fn bind(addr: SocketAddr) -> std::io::Result<Self> {
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        socket.set_reuseaddr(true)?;
+        socket.bind(addr)?;
+        let listener = socket.listen(LISTEN_BACKLOG)?;
+        Ok(Self { listener })
+    }
+
     pub async fn accept(&self) -> std::io::Result<SlowTcpStream> {
         let (tokio_stream, _addr) = self.listener.accept().await?;
         let slow_stream = SlowTcpStream::new(tokio_stream);
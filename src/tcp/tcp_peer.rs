@@ -0,0 +1,183 @@
+use super::tcp_link::{LinkDirection, SlowLinkId};
+use rand::Rng;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// How long to wait before the first reconnect attempt.
+pub const DEFAULT_RECONNECT_INITIAL_DELAY: Duration = Duration::from_millis(100);
+
+/// The longest wait between reconnect attempts.
+pub const DEFAULT_RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// How far each wait is randomly moved, as a fraction of the wait.
+pub const DEFAULT_RECONNECT_JITTER: f64 = 0.2;
+
+/// The most peer events held before the oldest are dropped.
+pub const MAX_PEER_EVENTS: usize = 1024;
+
+/// How a junction redials peers it connected to after their link drops.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+    /// The wait before the first attempt; it doubles with every failed attempt.
+    pub initial_delay: Duration,
+
+    /// The longest wait between attempts.
+    pub max_delay: Duration,
+
+    /// How far each wait is randomly moved, as a fraction of the wait, so peers
+    /// that lost their links together do not all redial at once.
+    pub jitter: f64,
+
+    /// The most attempts before giving up, or None to keep trying.
+    pub max_attempts: Option<u32>,
+}
+
+impl ReconnectPolicy {
+    /// Returns the wait before an attempt, without jitter.
+    ///
+    /// # Arguments
+    /// * `attempt` - The attempt number, starting at 1
+    pub fn base_delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(31);
+        self.initial_delay
+            .saturating_mul(factor)
+            .min(self.max_delay)
+    }
+
+    /// Returns the wait before an attempt, with jitter.
+    ///
+    /// # Arguments
+    /// * `attempt` - The attempt number, starting at 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let base = self.base_delay(attempt);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return base;
+        }
+
+        let scale = rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter);
+        base.mul_f64(scale)
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: DEFAULT_RECONNECT_INITIAL_DELAY,
+            max_delay: DEFAULT_RECONNECT_MAX_DELAY,
+            jitter: DEFAULT_RECONNECT_JITTER,
+            max_attempts: None,
+        }
+    }
+}
+
+/// What happened to a peer the junction dialed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerEventKind {
+    /// A link to the peer was established.
+    Connected {
+        /// The ID of the new link.
+        link_id: SlowLinkId,
+    },
+    /// The link to the peer dropped.
+    Disconnected {
+        /// The ID of the link that dropped.
+        link_id: SlowLinkId,
+    },
+    /// A reconnect attempt is about to be made after a wait.
+    Reconnecting {
+        /// The attempt number, starting at 1.
+        attempt: u32,
+        /// How long the junction waits before dialing.
+        delay: Duration,
+    },
+    /// A reconnect attempt failed.
+    ReconnectFailed {
+        /// The attempt number, starting at 1.
+        attempt: u32,
+        /// Why the attempt failed.
+        error: String,
+    },
+    /// The junction stopped trying to reconnect.
+    GaveUp {
+        /// The number of attempts made.
+        attempts: u32,
+    },
+}
+
+/// Something that happened to a peer the junction dialed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerEvent {
+    /// The address the peer was dialed at.
+    pub addr: SocketAddr,
+
+    /// What happened.
+    pub kind: PeerEventKind,
+}
+
+/// Counts of what happened to the peers a junction dialed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PeerStats {
+    /// Links established, including reconnects.
+    pub connects: usize,
+
+    /// Links that dropped.
+    pub disconnects: usize,
+
+    /// Reconnect attempts made.
+    pub reconnect_attempts: usize,
+
+    /// Reconnect attempts that failed.
+    pub reconnect_failures: usize,
+
+    /// Peers the junction gave up on.
+    pub gave_up: usize,
+}
+
+impl PeerStats {
+    /// Counts an event.
+    ///
+    /// # Arguments
+    /// * `kind` - What happened
+    pub fn record(&mut self, kind: &PeerEventKind) {
+        match kind {
+            PeerEventKind::Connected { .. } => self.connects += 1,
+            PeerEventKind::Disconnected { .. } => self.disconnects += 1,
+            PeerEventKind::Reconnecting { .. } => self.reconnect_attempts += 1,
+            PeerEventKind::ReconnectFailed { .. } => self.reconnect_failures += 1,
+            PeerEventKind::GaveUp { .. } => self.gave_up += 1,
+        }
+    }
+}
+
+/// The most links a junction holds, or None for no limit.
+///
+/// Links past a limit are turned away when they are accepted or dialed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkLimits {
+    /// The most links dialed by other junctions.
+    pub max_inbound: Option<usize>,
+
+    /// The most links dialed by this junction.
+    pub max_outbound: Option<usize>,
+
+    /// The most links of either kind.
+    pub max_total: Option<usize>,
+}
+
+impl LinkLimits {
+    /// Returns `true` if another link can be added in the given direction.
+    ///
+    /// # Arguments
+    /// * `direction` - Which end dials the new link
+    /// * `inbound` - The number of inbound links already held
+    /// * `outbound` - The number of outbound links already held
+    pub fn allows(&self, direction: LinkDirection, inbound: usize, outbound: usize) -> bool {
+        let (held, max) = match direction {
+            LinkDirection::Inbound => (inbound, self.max_inbound),
+            LinkDirection::Outbound => (outbound, self.max_outbound),
+        };
+        let under = |count: usize, max: Option<usize>| max.is_none_or(|max| count < max);
+        under(held, max) && under(inbound + outbound, self.max_total)
+    }
+}
@@ -61,6 +61,22 @@ impl SlowTcpRouteStats {
             .map(|(link_id, _)| *link_id)
     }
 
+    /// Returns the link IDs that have delivered valid packets, most valid packets first
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(u32, u64)>` - The link IDs and their valid_packet_count
+    fn get_links(&self) -> Vec<(u32, u64)> {
+        let mut links: Vec<(u32, u64)> = self
+            .link_stats
+            .iter()
+            .filter(|&(_, stats)| stats.valid_packet_count > 0)
+            .map(|(&link_id, stats)| (link_id, stats.valid_packet_count))
+            .collect();
+        links.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        links
+    }
+
     /// Updates the packet tracker with the package ID from the provided SlowPackage
     ///
     /// # Arguments
@@ -150,4 +166,59 @@ impl SlowTcpRouter {
             .get(junction_id)
             .and_then(|stats| stats.get_best_link())
     }
+
+    /// Returns every link a junction has been heard on, best first.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The JunctionId to get the links for
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<u32>` - The IDs of the links, ordered by valid packets received
+    pub fn get_links(&self, junction_id: &JunctionId) -> Vec<u32> {
+        self.route_stats
+            .get(junction_id)
+            .map(|stats| stats.get_links())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(link_id, _)| link_id)
+            .collect()
+    }
+
+    /// Returns the links eligible for multipath forwarding to a junction along with their weights.
+    ///
+    /// A link is eligible if it has delivered at least half as many valid packets from the
+    /// junction as the best link. Each link is weighted by its valid packet count.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The JunctionId to get the links for
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(u32, u32)>` - The IDs and weights of the eligible links, best first
+    pub fn get_multipath_links(&self, junction_id: &JunctionId) -> Vec<(u32, u32)> {
+        let links = self
+            .route_stats
+            .get(junction_id)
+            .map(|stats| stats.get_links())
+            .unwrap_or_default();
+        let best_count = match links.first() {
+            Some(&(_, count)) => count,
+            None => return Vec::new(),
+        };
+
+        links
+            .into_iter()
+            .filter(|&(_, count)| count * 2 >= best_count)
+            .map(|(link_id, count)| (link_id, count.min(u32::MAX as u64) as u32))
+            .collect()
+    }
+}
+
+impl Default for SlowTcpRouter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
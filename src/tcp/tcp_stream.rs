@@ -19,6 +19,8 @@ pub struct SlowTcpStream {
     writer: Mutex<OwnedWriteHalf>,
     /// Used to notify the read() functions to return EOF
     close_notify: Notify,
+    /// The address of the remote end, if it could be determined
+    peer_addr: Option<SocketAddr>,
 }
 
 impl SlowTcpStream {
@@ -42,6 +44,10 @@ impl SlowTcpStream {
     /// # Returns
     /// * `Self` - A new SlowTcpStream instance
     pub fn new(stream: TcpStream) -> Self {
+        // Frames are written in several small pieces, so don't let Nagle's algorithm hold them back
+        let _ = stream.set_nodelay(true);
+        let peer_addr = stream.peer_addr().ok();
+
         // Split the stream into read and write halves
         let (reader, writer) = stream.into_split();
 
@@ -49,9 +55,18 @@ impl SlowTcpStream {
             reader: Mutex::new(reader),
             writer: Mutex::new(writer),
             close_notify: Notify::new(),
+            peer_addr,
         }
     }
 
+    /// Returns the address of the remote end of the stream
+    ///
+    /// # Returns
+    /// * `Option<SocketAddr>` - The remote address, or None if it could not be determined
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
     /// Sends data over the TCP stream
     ///
     /// This method ensures that all data is written to the stream.
@@ -65,8 +80,16 @@ impl SlowTcpStream {
         let mut writer = self.writer.lock().await;
         let data_len = data.len();
 
-        // Use write_all to ensure all bytes are written
-        writer.write_all(data).await?;
+        // Use write_all to ensure all bytes are written, giving up if the stream is closed
+        // so a peer that stopped reading cannot hold the writer forever
+        select! {
+            result = writer.write_all(data) => {
+                result?;
+            }
+            _ = self.close_notify.notified() => {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "Connection closed"));
+            }
+        }
 
         Ok(data_len)
     }
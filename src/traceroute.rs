@@ -0,0 +1,157 @@
+use crate::junction_id::JunctionId;
+use crate::package::SlowPackage;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long `traceroute` waits for the destination to echo the recorded path back.
+pub const TRACEROUTE_TIMEOUT: Duration = Duration::from_secs(2);
+
+//=============================================================================
+// TracerouteHop
+//=============================================================================
+/// A junction a traceroute passed through and when it got there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TracerouteHop {
+    /// The ID of the junction.
+    pub junction_id: JunctionId,
+
+    /// When the junction handled the traceroute, in milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+}
+
+impl TracerouteHop {
+    /// Creates a new `TracerouteHop` stamped with the current time.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The ID of the junction.
+    pub fn now(junction_id: &JunctionId) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+
+        TracerouteHop {
+            junction_id: junction_id.clone(),
+            timestamp_ms,
+        }
+    }
+}
+
+//=============================================================================
+// TracerouteRecord
+//=============================================================================
+/// The payload of a traceroute, accumulating every junction it passes through.
+///
+/// The origin records itself when the traceroute is sent, each forwarding junction
+/// appends itself, and the destination appends itself before echoing the record
+/// back to the origin in a traceroute reply.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TracerouteRecord {
+    /// Identifies the traceroute among those started by the origin.
+    pub trace_id: u32,
+
+    /// The junctions passed through so far, origin first.
+    pub hops: Vec<TracerouteHop>,
+}
+
+impl TracerouteRecord {
+    /// Creates a new `TracerouteRecord` holding only the origin.
+    ///
+    /// # Arguments
+    ///
+    /// * `trace_id` - Identifies the traceroute among those started by the origin.
+    /// * `origin_id` - The ID of the junction starting the traceroute.
+    pub fn new(trace_id: u32, origin_id: &JunctionId) -> Self {
+        TracerouteRecord {
+            trace_id,
+            hops: vec![TracerouteHop::now(origin_id)],
+        }
+    }
+
+    /// Appends a junction to the record of a traceroute package.
+    ///
+    /// Packages that do not carry a valid record are left untouched.
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The traceroute package.
+    /// * `junction_id` - The ID of the junction handling the package.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the junction was recorded, `false` otherwise.
+    pub fn record_hop(package: &mut SlowPackage, junction_id: &JunctionId) -> bool {
+        match TracerouteRecord::unpack(&package.payload) {
+            Some(mut record) => {
+                record.hops.push(TracerouteHop::now(junction_id));
+                package.set_payload(record.pack());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Serializes the record into a byte vector.
+    ///
+    /// The format is:
+    /// - 4 bytes: Trace ID as u32 in little-endian
+    /// - 2 bytes: Number of hops as u16 in little-endian
+    /// - For each hop: the packed `JunctionId` followed by 8 bytes of timestamp as u64 in little-endian
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<u8>` - The serialized record.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.trace_id.to_le_bytes());
+        buffer.extend_from_slice(&(self.hops.len() as u16).to_le_bytes());
+
+        for hop in &self.hops {
+            buffer.extend_from_slice(&hop.junction_id.pack());
+            buffer.extend_from_slice(&hop.timestamp_ms.to_le_bytes());
+        }
+
+        buffer
+    }
+
+    /// Deserializes a byte slice into a `TracerouteRecord`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A byte slice containing the serialized record.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Self>` - The record if deserialization is successful, None otherwise.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() < 6 {
+            return None;
+        }
+
+        let trace_id = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let count = u16::from_le_bytes([data[4], data[5]]) as usize;
+        let mut pos = 6;
+        let mut hops = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let junction_id = JunctionId::unpack(&data[pos..])?;
+            let id_len = u16::from_le_bytes([data[pos], data[pos + 1]]) as usize;
+            pos += 2 + id_len;
+
+            let timestamp_bytes = data.get(pos..pos + 8)?;
+            let timestamp_ms = u64::from_le_bytes(timestamp_bytes.try_into().ok()?);
+            pos += 8;
+
+            hops.push(TracerouteHop {
+                junction_id,
+                timestamp_ms,
+            });
+        }
+
+        if pos != data.len() {
+            return None;
+        }
+
+        Some(TracerouteRecord { trace_id, hops })
+    }
+}
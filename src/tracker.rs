@@ -103,3 +103,9 @@ impl PacketTracker {
         self.packet_bitfield
     }
 }
+
+impl Default for PacketTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
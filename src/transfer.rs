@@ -0,0 +1,842 @@
+use crate::junction_id::JunctionId;
+use crate::package::SlowPackage;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::{Instant, timeout_at};
+
+/// The number of bytes carried by each chunk; only the last chunk of a transfer may be shorter.
+pub const CHUNK_SIZE: usize = 1024;
+
+/// How long a chunk waits for its ack before it is resent.
+pub const DEFAULT_CHUNK_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How many times a chunk is resent before the transfer is given up on.
+pub const DEFAULT_CHUNK_RETRIES: u32 = 5;
+
+/// How many chunks may wait for an ack at once.
+pub const DEFAULT_CHUNK_WINDOW: usize = 16;
+
+/// The largest transfer accepted from another junction, in bytes.
+pub const DEFAULT_MAX_TRANSFER_SIZE: u64 = 64 * 1024 * 1024;
+
+/// How many unfinished transfers from other junctions are kept at once.
+pub const DEFAULT_MAX_INCOMING_TRANSFERS: usize = 16;
+
+/// How many transfers received in full are remembered, so offers of them again are confirmed at once.
+const FINISHED_CAPACITY: usize = 256;
+
+/// Computes the 64-bit FNV-1a hash used to check the integrity of chunks.
+///
+/// # Arguments
+///
+/// * `data` - The bytes to hash.
+pub fn chunk_hash(data: &[u8]) -> u64 {
+    extend_hash(0xcbf29ce484222325, data)
+}
+
+/// Continues an FNV-1a hash with more bytes.
+fn extend_hash(mut hash: u64, data: &[u8]) -> u64 {
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Returns the ID of a transfer, which is the same every time the same data is sent under the same name.
+///
+/// # Arguments
+///
+/// * `name` - The name of the transfer.
+/// * `data` - The data being transferred.
+pub fn transfer_id(name: &str, data: &[u8]) -> u64 {
+    let hash = extend_hash(chunk_hash(name.as_bytes()), &[0]);
+    extend_hash(hash, data)
+}
+
+//=============================================================================
+// TransferPolicy
+//=============================================================================
+/// Controls how chunks are acknowledged and resent, and which transfers are accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferPolicy {
+    /// How long to wait for a chunk's ack before resending it.
+    pub timeout: Duration,
+
+    /// How many times to resend a chunk before giving up.
+    pub retries: u32,
+
+    /// How many chunks may wait for an ack at once.
+    pub window: usize,
+
+    /// The largest transfer accepted from another junction, in bytes. Larger offers are refused.
+    pub max_transfer_size: u64,
+
+    /// How many unfinished transfers from other junctions are kept. When a new offer arrives
+    /// and there is no room, the transfer that has been idle the longest is dropped.
+    pub max_incoming: usize,
+}
+
+impl Default for TransferPolicy {
+    fn default() -> Self {
+        TransferPolicy {
+            timeout: DEFAULT_CHUNK_TIMEOUT,
+            retries: DEFAULT_CHUNK_RETRIES,
+            window: DEFAULT_CHUNK_WINDOW,
+            max_transfer_size: DEFAULT_MAX_TRANSFER_SIZE,
+            max_incoming: DEFAULT_MAX_INCOMING_TRANSFERS,
+        }
+    }
+}
+
+//=============================================================================
+// TransferMessage
+//=============================================================================
+/// The payload of a `Transfer` package.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransferMessage {
+    /// Announces a transfer to the recipient.
+    Offer {
+        transfer_id: u64,
+        size: u64,
+        name: String,
+    },
+    /// Accepts a transfer, asking for the chunks from `next_chunk` on.
+    Accept { transfer_id: u64, next_chunk: u32 },
+    /// Carries one chunk of a transfer.
+    Chunk {
+        transfer_id: u64,
+        index: u32,
+        hash: u64,
+        data: Vec<u8>,
+    },
+    /// Confirms a chunk, or reports that it failed its integrity check.
+    ChunkAck {
+        transfer_id: u64,
+        index: u32,
+        ok: bool,
+    },
+    /// Refuses a transfer the recipient will not accept.
+    Refuse { transfer_id: u64 },
+}
+
+impl TransferMessage {
+    /// Returns the ID of the transfer the message belongs to.
+    pub fn transfer_id(&self) -> u64 {
+        match self {
+            TransferMessage::Offer { transfer_id, .. }
+            | TransferMessage::Accept { transfer_id, .. }
+            | TransferMessage::Chunk { transfer_id, .. }
+            | TransferMessage::ChunkAck { transfer_id, .. }
+            | TransferMessage::Refuse { transfer_id } => *transfer_id,
+        }
+    }
+
+    /// Serializes the message into a byte vector.
+    ///
+    /// Every message starts with a kind byte and the transfer ID as u64 in little-endian,
+    /// followed by the fields of the message in order, integers in little-endian.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<u8>` - The serialized message.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        match self {
+            TransferMessage::Offer {
+                transfer_id,
+                size,
+                name,
+            } => {
+                buffer.push(0);
+                buffer.extend_from_slice(&transfer_id.to_le_bytes());
+                buffer.extend_from_slice(&size.to_le_bytes());
+                buffer.extend_from_slice(name.as_bytes());
+            }
+            TransferMessage::Accept {
+                transfer_id,
+                next_chunk,
+            } => {
+                buffer.push(1);
+                buffer.extend_from_slice(&transfer_id.to_le_bytes());
+                buffer.extend_from_slice(&next_chunk.to_le_bytes());
+            }
+            TransferMessage::Chunk {
+                transfer_id,
+                index,
+                hash,
+                data,
+            } => {
+                buffer.push(2);
+                buffer.extend_from_slice(&transfer_id.to_le_bytes());
+                buffer.extend_from_slice(&index.to_le_bytes());
+                buffer.extend_from_slice(&hash.to_le_bytes());
+                buffer.extend_from_slice(data);
+            }
+            TransferMessage::ChunkAck {
+                transfer_id,
+                index,
+                ok,
+            } => {
+                buffer.push(3);
+                buffer.extend_from_slice(&transfer_id.to_le_bytes());
+                buffer.extend_from_slice(&index.to_le_bytes());
+                buffer.push(*ok as u8);
+            }
+            TransferMessage::Refuse { transfer_id } => {
+                buffer.push(4);
+                buffer.extend_from_slice(&transfer_id.to_le_bytes());
+            }
+        }
+        buffer
+    }
+
+    /// Deserializes a byte slice into a `TransferMessage`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A byte slice containing the serialized message.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Self>` - The message if deserialization is successful, None otherwise.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        let read_u32 =
+            |pos: usize| Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?));
+        let read_u64 =
+            |pos: usize| Some(u64::from_le_bytes(data.get(pos..pos + 8)?.try_into().ok()?));
+
+        let transfer_id = read_u64(1)?;
+        match data[0] {
+            0 => Some(TransferMessage::Offer {
+                transfer_id,
+                size: read_u64(9)?,
+                name: String::from_utf8(data.get(17..)?.to_vec()).ok()?,
+            }),
+            1 if data.len() == 13 => Some(TransferMessage::Accept {
+                transfer_id,
+                next_chunk: read_u32(9)?,
+            }),
+            2 => Some(TransferMessage::Chunk {
+                transfer_id,
+                index: read_u32(9)?,
+                hash: read_u64(13)?,
+                data: data.get(21..)?.to_vec(),
+            }),
+            3 if data.len() == 14 => Some(TransferMessage::ChunkAck {
+                transfer_id,
+                index: read_u32(9)?,
+                ok: data[13] != 0,
+            }),
+            4 if data.len() == 9 => Some(TransferMessage::Refuse { transfer_id }),
+            _ => None,
+        }
+    }
+}
+
+//=============================================================================
+// TransferProgress
+//=============================================================================
+/// How far an outgoing transfer has got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TransferProgress {
+    /// The number of chunks the recipient has confirmed.
+    pub chunks_done: u32,
+
+    /// The number of chunks in the transfer.
+    pub chunk_count: u32,
+
+    /// The number of bytes the recipient has confirmed.
+    pub bytes_done: u64,
+
+    /// The size of the transfer in bytes.
+    pub total_bytes: u64,
+}
+
+impl TransferProgress {
+    /// Returns `true` once every chunk has been confirmed.
+    pub fn is_complete(&self) -> bool {
+        self.chunks_done == self.chunk_count
+    }
+}
+
+//=============================================================================
+// TransferError
+//=============================================================================
+/// The reason an outgoing transfer did not complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferError {
+    /// The recipient stopped confirming chunks. Sending the same data again resumes the transfer.
+    TimedOut,
+    /// The junction was dropped before the transfer completed.
+    Closed,
+    /// The recipient refused the transfer, because it is larger than the recipient accepts.
+    Refused,
+}
+
+impl std::fmt::Display for TransferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TransferError::TimedOut => write!(f, "the recipient stopped confirming chunks"),
+            TransferError::Closed => write!(f, "junction closed before the transfer completed"),
+            TransferError::Refused => write!(f, "the recipient refused the transfer"),
+        }
+    }
+}
+
+impl std::error::Error for TransferError {}
+
+//=============================================================================
+// TransferHandle
+//=============================================================================
+/// A handle to an outgoing transfer.
+pub struct TransferHandle {
+    /// The ID of the transfer.
+    transfer_id: u64,
+
+    /// Receives progress updates.
+    progress: watch::Receiver<TransferProgress>,
+
+    /// Receives the outcome of the transfer.
+    result: oneshot::Receiver<Result<(), TransferError>>,
+}
+
+impl TransferHandle {
+    /// Returns the ID of the transfer.
+    pub fn transfer_id(&self) -> u64 {
+        self.transfer_id
+    }
+
+    /// Returns how far the transfer has got.
+    pub fn progress(&self) -> TransferProgress {
+        *self.progress.borrow()
+    }
+
+    /// Returns a receiver that is notified each time the transfer makes progress.
+    pub fn watch_progress(&self) -> watch::Receiver<TransferProgress> {
+        self.progress.clone()
+    }
+
+    /// Waits until every chunk is confirmed or the transfer is given up on.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), TransferError>` - Ok once the recipient has the whole transfer.
+    pub async fn wait(self) -> Result<(), TransferError> {
+        self.result.await.unwrap_or(Err(TransferError::Closed))
+    }
+}
+
+//=============================================================================
+// CompletedTransfer
+//=============================================================================
+/// A transfer received in full from another junction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletedTransfer {
+    /// The junction that sent the transfer.
+    pub sender_id: JunctionId,
+
+    /// The name the sender gave the transfer.
+    pub name: String,
+
+    /// The transferred data.
+    pub data: Vec<u8>,
+}
+
+/// A transfer being received, kept after an interruption so it can be resumed.
+struct IncomingTransfer {
+    /// The name the sender gave the transfer.
+    name: String,
+
+    /// The size of the transfer in bytes.
+    size: u64,
+
+    /// The chunks received so far.
+    chunks: Vec<Option<Vec<u8>>>,
+
+    /// The number of chunks received so far.
+    received: u32,
+
+    /// When the transfer was last offered or sent a chunk.
+    updated: Instant,
+}
+
+impl IncomingTransfer {
+    /// Returns the number of chunks at the start of the transfer that have all been received.
+    fn next_chunk(&self) -> u32 {
+        self.chunks
+            .iter()
+            .take_while(|chunk| chunk.is_some())
+            .count() as u32
+    }
+
+    /// Returns the size a chunk must have to be valid.
+    fn chunk_len(&self, index: u32) -> usize {
+        let start = index as u64 * CHUNK_SIZE as u64;
+        (self.size - start).min(CHUNK_SIZE as u64) as usize
+    }
+}
+
+//=============================================================================
+// Transfers
+//=============================================================================
+/// Sends and receives the chunked transfers of a junction.
+///
+/// Each transfer starts with an offer, which the recipient accepts with the index of
+/// the first chunk it is missing. The sender then keeps a window of chunks in flight,
+/// each carrying a hash the recipient checks before confirming it. Chunks that fail
+/// the check or go unconfirmed are resent. A recipient keeps the chunks of an
+/// interrupted transfer, so sending the same data under the same name again resumes
+/// from the last confirmed chunk.
+pub struct Transfers {
+    /// The ID of the junction the transfers belong to.
+    junction_id: JunctionId,
+
+    /// Hands packages to the junction for sending.
+    outgoing: mpsc::UnboundedSender<SlowPackage>,
+
+    /// Ack and retry settings for new outgoing transfers.
+    policy: TransferPolicy,
+
+    /// Routes replies to the tasks running outgoing transfers, keyed by transfer ID.
+    active: HashMap<u64, mpsc::UnboundedSender<TransferMessage>>,
+
+    /// Transfers being received, keyed by sender and transfer ID.
+    incoming: HashMap<(JunctionId, u64), IncomingTransfer>,
+
+    /// Transfers already received in full, keyed by sender and transfer ID.
+    finished: HashSet<(JunctionId, u64)>,
+
+    /// The transfers in `finished` in the order they finished, oldest first.
+    finished_order: VecDeque<(JunctionId, u64)>,
+
+    /// Received transfers waiting for the application.
+    completed: VecDeque<CompletedTransfer>,
+}
+
+impl Transfers {
+    /// Creates a new `Transfers`.
+    ///
+    /// # Arguments
+    ///
+    /// * `junction_id` - The ID of the junction the transfers belong to.
+    /// * `outgoing` - Receives the packages the junction should send.
+    pub fn new(junction_id: JunctionId, outgoing: mpsc::UnboundedSender<SlowPackage>) -> Self {
+        Transfers {
+            junction_id,
+            outgoing,
+            policy: TransferPolicy::default(),
+            active: HashMap::new(),
+            incoming: HashMap::new(),
+            finished: HashSet::new(),
+            finished_order: VecDeque::new(),
+            completed: VecDeque::new(),
+        }
+    }
+
+    /// Returns the ack and retry settings.
+    pub fn policy(&self) -> TransferPolicy {
+        self.policy
+    }
+
+    /// Sets the ack and retry settings used for new outgoing transfers, and the limits on
+    /// transfers from other junctions.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The new settings.
+    pub fn set_policy(&mut self, policy: TransferPolicy) {
+        self.policy = policy;
+    }
+
+    /// Starts sending data to another junction.
+    ///
+    /// # Arguments
+    ///
+    /// * `recipient_id` - The junction to send the data to.
+    /// * `name` - The name of the transfer, such as a file name.
+    /// * `data` - The data to send.
+    ///
+    /// # Returns
+    ///
+    /// * `TransferHandle` - A handle that reports progress and resolves when the transfer ends.
+    pub fn start(
+        &mut self,
+        recipient_id: &JunctionId,
+        name: &str,
+        data: Vec<u8>,
+    ) -> TransferHandle {
+        self.active.retain(|_, replies| !replies.is_closed());
+
+        let transfer_id = transfer_id(name, &data);
+        let (replies_sender, replies) = mpsc::unbounded_channel();
+        let (progress_sender, progress) = watch::channel(TransferProgress {
+            chunks_done: 0,
+            chunk_count: data.len().div_ceil(CHUNK_SIZE) as u32,
+            bytes_done: 0,
+            total_bytes: data.len() as u64,
+        });
+        let (result_sender, result) = oneshot::channel();
+        self.active.insert(transfer_id, replies_sender);
+
+        let sender = OutgoingTransfer {
+            junction_id: self.junction_id.clone(),
+            recipient_id: recipient_id.clone(),
+            transfer_id,
+            name: name.to_string(),
+            data,
+            policy: self.policy,
+            outgoing: self.outgoing.clone(),
+            replies,
+            progress: progress_sender,
+        };
+        tokio::spawn(async move {
+            let _ = result_sender.send(sender.run().await);
+        });
+
+        TransferHandle {
+            transfer_id,
+            progress,
+            result,
+        }
+    }
+
+    /// Handles a transfer message received from another junction.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_id` - The junction that sent the message.
+    /// * `payload` - The payload of the `Transfer` package.
+    pub fn handle(&mut self, sender_id: &JunctionId, payload: &[u8]) {
+        let message = match TransferMessage::unpack(payload) {
+            Some(message) => message,
+            None => return,
+        };
+
+        match message {
+            TransferMessage::Offer {
+                transfer_id,
+                size,
+                name,
+            } => self.on_offer(sender_id, transfer_id, size, name),
+            TransferMessage::Chunk {
+                transfer_id,
+                index,
+                hash,
+                data,
+            } => self.on_chunk(sender_id, transfer_id, index, hash, data),
+            reply => {
+                if let Some(replies) = self.active.get(&reply.transfer_id()) {
+                    let _ = replies.send(reply);
+                }
+            }
+        }
+    }
+
+    /// Removes the next transfer received in full.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<CompletedTransfer>` - The transfer, if one is waiting.
+    pub fn pop_completed(&mut self) -> Option<CompletedTransfer> {
+        self.completed.pop_front()
+    }
+
+    /// Accepts an offered transfer, resuming it if some chunks were received before.
+    ///
+    /// Transfers larger than the policy allows, or with more chunks than can be numbered,
+    /// are refused.
+    fn on_offer(&mut self, sender_id: &JunctionId, transfer_id: u64, size: u64, name: String) {
+        let chunk_count = match u32::try_from(size.div_ceil(CHUNK_SIZE as u64)) {
+            Ok(chunk_count) if size <= self.policy.max_transfer_size => chunk_count,
+            _ => {
+                self.reply(sender_id, TransferMessage::Refuse { transfer_id });
+                return;
+            }
+        };
+
+        let key = (sender_id.clone(), transfer_id);
+        let next_chunk = if self.finished.contains(&key) {
+            chunk_count
+        } else {
+            if !self.incoming.contains_key(&key) {
+                self.make_room();
+            }
+            let transfer = self
+                .incoming
+                .entry(key.clone())
+                .or_insert_with(|| IncomingTransfer {
+                    name,
+                    size,
+                    chunks: vec![None; chunk_count as usize],
+                    received: 0,
+                    updated: Instant::now(),
+                });
+            transfer.updated = Instant::now();
+            transfer.next_chunk()
+        };
+
+        self.reply(
+            sender_id,
+            TransferMessage::Accept {
+                transfer_id,
+                next_chunk,
+            },
+        );
+        self.check_finished(key);
+    }
+
+    /// Stores a chunk if it passes its integrity check, and confirms it to the sender.
+    fn on_chunk(
+        &mut self,
+        sender_id: &JunctionId,
+        transfer_id: u64,
+        index: u32,
+        hash: u64,
+        data: Vec<u8>,
+    ) {
+        let key = (sender_id.clone(), transfer_id);
+        let ok = if self.finished.contains(&key) {
+            true
+        } else if let Some(transfer) = self.incoming.get_mut(&key) {
+            let valid = (index as usize) < transfer.chunks.len()
+                && data.len() == transfer.chunk_len(index)
+                && chunk_hash(&data) == hash;
+            if valid && transfer.chunks[index as usize].is_none() {
+                transfer.chunks[index as usize] = Some(data);
+                transfer.received += 1;
+            }
+            transfer.updated = Instant::now();
+            valid
+        } else {
+            // Chunks of transfers that were never offered are ignored
+            return;
+        };
+
+        self.reply(
+            sender_id,
+            TransferMessage::ChunkAck {
+                transfer_id,
+                index,
+                ok,
+            },
+        );
+        self.check_finished(key);
+    }
+
+    /// Moves a transfer to the completed queue once every chunk has been received.
+    fn check_finished(&mut self, key: (JunctionId, u64)) {
+        let done = match self.incoming.get(&key) {
+            Some(transfer) => transfer.received as usize == transfer.chunks.len(),
+            None => false,
+        };
+        if !done {
+            return;
+        }
+
+        let transfer = self.incoming.remove(&key).unwrap();
+        let data = transfer.chunks.into_iter().flatten().flatten().collect();
+        self.completed.push_back(CompletedTransfer {
+            sender_id: key.0.clone(),
+            name: transfer.name,
+            data,
+        });
+
+        if self.finished_order.len() >= FINISHED_CAPACITY
+            && let Some(oldest) = self.finished_order.pop_front()
+        {
+            self.finished.remove(&oldest);
+        }
+        self.finished_order.push_back(key.clone());
+        self.finished.insert(key);
+    }
+
+    /// Drops the transfers that have been idle the longest until a new one fits within `max_incoming`.
+    fn make_room(&mut self) {
+        while self.incoming.len() >= self.policy.max_incoming.max(1) {
+            let idlest = self
+                .incoming
+                .iter()
+                .min_by_key(|(_, transfer)| transfer.updated)
+                .map(|(key, _)| key.clone());
+            match idlest {
+                Some(key) => self.incoming.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    /// Sends a message back to the sender of a transfer.
+    fn reply(&self, recipient_id: &JunctionId, message: TransferMessage) {
+        let package = SlowPackage::new_transfer(
+            recipient_id.clone(),
+            self.junction_id.clone(),
+            &message.pack(),
+        );
+        let _ = self.outgoing.send(package);
+    }
+}
+
+//=============================================================================
+// OutgoingTransfer
+//=============================================================================
+/// The task state of a transfer being sent.
+struct OutgoingTransfer {
+    /// The ID of the sending junction.
+    junction_id: JunctionId,
+
+    /// The junction receiving the transfer.
+    recipient_id: JunctionId,
+
+    /// The ID of the transfer.
+    transfer_id: u64,
+
+    /// The name of the transfer.
+    name: String,
+
+    /// The data being sent.
+    data: Vec<u8>,
+
+    /// Ack and retry settings.
+    policy: TransferPolicy,
+
+    /// Hands packages to the junction for sending.
+    outgoing: mpsc::UnboundedSender<SlowPackage>,
+
+    /// Receives the recipient's replies.
+    replies: mpsc::UnboundedReceiver<TransferMessage>,
+
+    /// Publishes progress to the `TransferHandle`.
+    progress: watch::Sender<TransferProgress>,
+}
+
+impl OutgoingTransfer {
+    /// Sends a message to the recipient.
+    fn send(&self, message: TransferMessage) {
+        let package = SlowPackage::new_transfer(
+            self.recipient_id.clone(),
+            self.junction_id.clone(),
+            &message.pack(),
+        );
+        let _ = self.outgoing.send(package);
+    }
+
+    /// Sends one chunk to the recipient.
+    fn send_chunk(&self, index: u32) {
+        let start = index as usize * CHUNK_SIZE;
+        let end = (start + CHUNK_SIZE).min(self.data.len());
+        let data = self.data[start..end].to_vec();
+        self.send(TransferMessage::Chunk {
+            transfer_id: self.transfer_id,
+            index,
+            hash: chunk_hash(&data),
+            data,
+        });
+    }
+
+    /// Records that the recipient confirmed the chunks counted in `chunks_done`.
+    fn report(&self, chunks_done: u32, bytes_done: u64) {
+        self.progress.send_modify(|progress| {
+            progress.chunks_done = chunks_done;
+            progress.bytes_done = bytes_done;
+        });
+    }
+
+    /// Offers the transfer until the recipient accepts it.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<u32, TransferError>` - The index of the first chunk the recipient is missing.
+    async fn offer(&mut self) -> Result<u32, TransferError> {
+        for _ in 0..=self.policy.retries {
+            self.send(TransferMessage::Offer {
+                transfer_id: self.transfer_id,
+                size: self.data.len() as u64,
+                name: self.name.clone(),
+            });
+
+            let deadline = Instant::now() + self.policy.timeout;
+            loop {
+                match timeout_at(deadline, self.replies.recv()).await {
+                    Ok(Some(TransferMessage::Accept { next_chunk, .. })) => return Ok(next_chunk),
+                    Ok(Some(TransferMessage::Refuse { .. })) => {
+                        return Err(TransferError::Refused);
+                    }
+                    Ok(Some(_)) => continue,
+                    Ok(None) => return Err(TransferError::Closed),
+                    Err(_) => break,
+                }
+            }
+        }
+
+        Err(TransferError::TimedOut)
+    }
+
+    /// Runs the transfer to completion.
+    async fn run(mut self) -> Result<(), TransferError> {
+        let chunk_count = self.data.len().div_ceil(CHUNK_SIZE) as u32;
+        let first = self.offer().await?.min(chunk_count);
+
+        // Everything before the first missing chunk was confirmed by an earlier attempt
+        let mut chunks_done = first;
+        let mut bytes_done = (first as usize * CHUNK_SIZE).min(self.data.len()) as u64;
+        self.report(chunks_done, bytes_done);
+
+        // Chunks waiting for an ack, with their deadline and retry count
+        let mut in_flight: BTreeMap<u32, (Instant, u32)> = BTreeMap::new();
+        let mut next = first;
+
+        while chunks_done < chunk_count {
+            while in_flight.len() < self.policy.window.max(1) && next < chunk_count {
+                self.send_chunk(next);
+                in_flight.insert(next, (Instant::now() + self.policy.timeout, 0));
+                next += 1;
+            }
+
+            let deadline = in_flight.values().map(|(deadline, _)| *deadline).min();
+            let deadline = deadline.unwrap_or_else(Instant::now);
+            match timeout_at(deadline, self.replies.recv()).await {
+                Ok(Some(TransferMessage::ChunkAck {
+                    index, ok: true, ..
+                })) => {
+                    if in_flight.remove(&index).is_some() {
+                        let start = index as usize * CHUNK_SIZE;
+                        chunks_done += 1;
+                        bytes_done += ((start + CHUNK_SIZE).min(self.data.len()) - start) as u64;
+                        self.report(chunks_done, bytes_done);
+                    }
+                }
+                Ok(Some(TransferMessage::ChunkAck {
+                    index, ok: false, ..
+                })) => {
+                    // The chunk was corrupted on the way, so send it again right away
+                    if let Some((deadline, _)) = in_flight.get_mut(&index) {
+                        *deadline = Instant::now();
+                    }
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => return Err(TransferError::Closed),
+                Err(_) => {}
+            }
+
+            let now = Instant::now();
+            let overdue: Vec<u32> = in_flight
+                .iter()
+                .filter(|(_, (deadline, _))| *deadline <= now)
+                .map(|(index, _)| *index)
+                .collect();
+            for index in overdue {
+                let (deadline, retries) = in_flight.get_mut(&index).unwrap();
+                if *retries >= self.policy.retries {
+                    return Err(TransferError::TimedOut);
+                }
+                *retries += 1;
+                *deadline = now + self.policy.timeout;
+                self.send_chunk(index);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use crate::stream::SlowStream;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use tokio::io;
+use tokio::net::TcpStream;
+
+/// The prefix of the stream service names used by tunnels.
+pub const TUNNEL_SERVICE_PREFIX: &str = "tunnel:";
+
+/// Returns the stream service name used by a tunnel.
+///
+/// # Arguments
+///
+/// * `tunnel` - The name of the tunnel.
+pub fn service_name(tunnel: &str) -> String {
+    format!("{}{}", TUNNEL_SERVICE_PREFIX, tunnel)
+}
+
+/// Returns the name of the tunnel a stream service belongs to.
+///
+/// # Arguments
+///
+/// * `service` - The service of a stream.
+///
+/// # Returns
+///
+/// * `Option<&str>` - The tunnel name, or `None` if the service is not a tunnel.
+pub fn tunnel_name(service: &str) -> Option<&str> {
+    service.strip_prefix(TUNNEL_SERVICE_PREFIX)
+}
+
+//=============================================================================
+// TunnelTargets
+//=============================================================================
+/// Maps the tunnels a junction serves to the addresses their connections are forwarded to.
+pub struct TunnelTargets {
+    /// Target addresses keyed by tunnel name.
+    targets: HashMap<String, SocketAddr>,
+}
+
+impl TunnelTargets {
+    /// Creates a new `TunnelTargets` with no tunnels.
+    pub fn new() -> Self {
+        TunnelTargets {
+            targets: HashMap::new(),
+        }
+    }
+
+    /// Adds a tunnel, replacing the target of any tunnel with the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `tunnel` - The name of the tunnel.
+    /// * `target` - The address connections through the tunnel are forwarded to.
+    pub fn add(&mut self, tunnel: &str, target: SocketAddr) {
+        self.targets.insert(tunnel.to_string(), target);
+    }
+
+    /// Removes a tunnel.
+    ///
+    /// # Arguments
+    ///
+    /// * `tunnel` - The name of the tunnel.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the tunnel was removed, `false` otherwise.
+    pub fn remove(&mut self, tunnel: &str) -> bool {
+        self.targets.remove(tunnel).is_some()
+    }
+
+    /// Returns the target address of a tunnel.
+    ///
+    /// # Arguments
+    ///
+    /// * `tunnel` - The name of the tunnel.
+    pub fn get(&self, tunnel: &str) -> Option<SocketAddr> {
+        self.targets.get(tunnel).copied()
+    }
+}
+
+impl Default for TunnelTargets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Copies data both ways between a TCP connection and a stream until both sides are closed.
+///
+/// # Arguments
+///
+/// * `connection` - The TCP connection.
+/// * `stream` - The stream to the other end of the tunnel.
+///
+/// # Returns
+///
+/// * `io::Result<(u64, u64)>` - The bytes copied from the connection and from the stream.
+pub async fn splice(mut connection: TcpStream, mut stream: SlowStream) -> io::Result<(u64, u64)> {
+    io::copy_bidirectional(&mut connection, &mut stream).await
+}
+
+/// Serves a tunnel stream opened by another junction by connecting it to the tunnel's target.
+///
+/// # Arguments
+///
+/// * `stream` - The tunnel stream.
+/// * `target` - The address to connect to.
+pub async fn serve(stream: SlowStream, target: SocketAddr) -> io::Result<()> {
+    let connection = TcpStream::connect(target).await?;
+    splice(connection, stream).await?;
+    Ok(())
+}
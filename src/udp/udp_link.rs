@@ -104,8 +104,8 @@ impl SlowUdpLink {
         let packet = SlowLinkPacket::unpack(data.to_vec());
 
         match packet {
-            SlowLinkPacket::Payload(ref payload_packet) => self.process_payload(&payload_packet),
-            SlowLinkPacket::Acknowledge(ref ack_packet) => self.process_ack(&ack_packet),
+            SlowLinkPacket::Payload(ref payload_packet) => self.process_payload(payload_packet),
+            SlowLinkPacket::Acknowledge(ref ack_packet) => self.process_ack(ack_packet),
             _ => {}
         };
 
@@ -53,8 +53,9 @@ impl SlowUdpPacket {
     /// # Returns
     /// * `Ok(SlowUdpPacket)` - Successfully deserialized packet (either Data or Ack)
     /// * `Err(())` - Failed to deserialize (invalid format)
+    #[allow(clippy::result_unit_err)]
     pub fn unpack(buffer: &[u8]) -> Result<Self, ()> {
-        if buffer.len() < 1 {
+        if buffer.is_empty() {
             return Err(());
         }
 
@@ -104,6 +105,7 @@ impl SlowUdpAckPacket {
     /// # Returns
     /// * `Ok(SlowUdpAckPacket)` - Successfully deserialized packet
     /// * `Err(())` - Failed to deserialize (invalid format or incorrect packet type)
+    #[allow(clippy::result_unit_err)]
     pub fn unpack(buffer: &[u8]) -> Result<Self, ()> {
         if buffer.len() < 13 {
             return Err(());
@@ -173,6 +175,7 @@ impl SlowUdpDataPacket {
     /// # Returns
     /// * `Ok(SlowUdpDataPacket)` - Successfully deserialized packet
     /// * `Err(())` - Failed to deserialize (invalid format or incorrect packet type)
+    #[allow(clippy::result_unit_err)]
     pub fn unpack(buffer: &[u8]) -> Result<Self, ()> {
         if buffer.len() < 9 {
             // Minimum size is 9 bytes (header without data)
@@ -0,0 +1,74 @@
+use slow::ack::{AckError, AckPolicy, AckTracker};
+use slow::junction::JunctionId;
+use slow::package::SlowPackage;
+use std::time::Duration;
+
+fn package(package_id: u32) -> SlowPackage {
+    let mut package =
+        SlowPackage::new_bin_payload(JunctionId::new("b"), JunctionId::new("a"), b"data");
+    package.request_ack();
+    package.set_package_id(package_id);
+    package
+}
+
+#[tokio::test]
+async fn test_ack_tracker_acknowledge() {
+    let mut tracker = AckTracker::default();
+    let handle = tracker.track(package(1));
+    assert_eq!(handle.package_id(), 1);
+    assert_eq!(tracker.len(), 1);
+
+    // Unknown acks are ignored
+    assert!(!tracker.acknowledge(2));
+    assert!(tracker.acknowledge(1));
+    assert!(tracker.is_empty());
+    assert_eq!(handle.wait().await, Ok(()));
+}
+
+#[tokio::test]
+async fn test_ack_tracker_retry() {
+    let policy = AckPolicy {
+        timeout: Duration::ZERO,
+        retries: 1,
+    };
+    let mut tracker = AckTracker::new(policy);
+    let handle = tracker.track(package(1));
+
+    // The first timeout hands the package back for a resend under a new ID
+    let mut due = tracker.take_due();
+    assert_eq!(due.len(), 1);
+    let mut pending = due.pop().unwrap();
+    pending.package_mut().set_retransmit(2);
+    tracker.retry(pending);
+
+    // Acks name the first ID, never the ID of a retransmission
+    assert!(!tracker.acknowledge(2));
+    assert_eq!(tracker.len(), 1);
+
+    // Once the retries are used up the handle fails
+    assert!(tracker.take_due().is_empty());
+    assert!(tracker.is_empty());
+    assert_eq!(handle.wait().await, Err(AckError::TimedOut));
+}
+
+#[tokio::test]
+async fn test_ack_tracker_late_ack() {
+    let policy = AckPolicy {
+        timeout: Duration::ZERO,
+        retries: 2,
+    };
+    let mut tracker = AckTracker::new(policy);
+    let handle = tracker.track(package(1));
+
+    // Resend twice, then the ack for the first transmission arrives
+    for package_id in [2, 3] {
+        let mut pending = tracker.take_due().pop().unwrap();
+        pending.package_mut().set_retransmit(package_id);
+        assert_eq!(pending.package().first_package_id(), 1);
+        tracker.retry(pending);
+    }
+
+    assert!(tracker.acknowledge(1));
+    assert!(tracker.is_empty());
+    assert_eq!(handle.wait().await, Ok(()));
+}
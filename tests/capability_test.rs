@@ -0,0 +1,210 @@
+use serde_json::json;
+use slow::capability::{
+    Agreement, Capabilities, MIN_PROTOCOL_VERSION, NegotiationError, PROTOCOL_VERSION,
+    ProtocolOffer,
+};
+use slow::junction::{JunctionId, SlowJunction};
+use slow::package::{PackageType, SlowPackage};
+use slow::tcp::tcp_junction::SlowTcpJunction;
+use slow::udp::udp_socket::SlowUdpSocket;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+#[test]
+fn test_protocol_offer_negotiate() {
+    let local = ProtocolOffer {
+        min_version: 1,
+        max_version: 3,
+        capabilities: Capabilities::COMPRESSION.union(Capabilities::FRAGMENTATION),
+        required: Capabilities::NONE,
+    };
+    let peer = ProtocolOffer {
+        min_version: 2,
+        max_version: 5,
+        capabilities: Capabilities::COMPRESSION.union(Capabilities::ENCRYPTION),
+        required: Capabilities::NONE,
+    };
+
+    // The newest shared version and the shared features win, whichever side negotiates
+    let agreement = Agreement {
+        version: 3,
+        capabilities: Capabilities::COMPRESSION,
+    };
+    assert_eq!(local.negotiate(&peer), Ok(agreement));
+    assert_eq!(peer.negotiate(&local), Ok(agreement));
+
+    let newer = ProtocolOffer {
+        min_version: 4,
+        ..peer
+    };
+    assert_eq!(
+        local.negotiate(&newer),
+        Err(NegotiationError::NoCommonVersion {
+            local: (1, 3),
+            peer: (4, 5),
+        })
+    );
+
+    let strict = ProtocolOffer {
+        required: Capabilities::ENCRYPTION,
+        ..peer
+    };
+    let error = local.negotiate(&strict).unwrap_err();
+    assert_eq!(
+        error,
+        NegotiationError::MissingCapabilities(Capabilities::ENCRYPTION)
+    );
+    assert!(error.to_string().contains("required capabilities"));
+    assert_eq!(strict.negotiate(&local), Err(error));
+}
+
+#[test]
+fn test_protocol_offer_pack_unpack() {
+    let offer = ProtocolOffer {
+        min_version: 1,
+        max_version: PROTOCOL_VERSION,
+        capabilities: Capabilities::FRAGMENTATION,
+        required: Capabilities::FRAGMENTATION,
+    };
+
+    let packed = offer.pack();
+    assert_eq!(packed.len(), ProtocolOffer::PACKED_SIZE);
+    assert_eq!(ProtocolOffer::unpack(&packed), Some(offer));
+    assert_eq!(ProtocolOffer::unpack(&packed[..4]), None);
+
+    // Peers from before offers were added are refused with their own error
+    let error = ProtocolOffer::default()
+        .negotiate(&ProtocolOffer::LEGACY)
+        .unwrap_err();
+    assert_eq!(error, NegotiationError::LegacyPeer);
+    assert!(error.to_string().contains("legacy protocol"));
+    assert!(
+        ProtocolOffer::LEGACY
+            .negotiate(&ProtocolOffer::default())
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_junction_refuses_incompatible_peer() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1127);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2236);
+
+    let junction_id2 = JunctionId::new("2");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+
+    // junction2 only speaks a version junction1 does not
+    junction2
+        .set_protocol_offer(ProtocolOffer {
+            min_version: PROTOCOL_VERSION + 1,
+            max_version: PROTOCOL_VERSION + 1,
+            ..ProtocolOffer::default()
+        })
+        .await;
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let expected = Err(NegotiationError::NoCommonVersion {
+        local: (MIN_PROTOCOL_VERSION, PROTOCOL_VERSION),
+        peer: (PROTOCOL_VERSION + 1, PROTOCOL_VERSION + 1),
+    });
+    assert_eq!(junction1.get_peer_protocol(addr2).await, Some(expected));
+    assert!(matches!(
+        junction2.get_peer_protocol(addr1).await,
+        Some(Err(NegotiationError::NoCommonVersion { .. }))
+    ));
+
+    // Messages from a refused peer are dropped
+    junction1.send(json!({"key": "value"}), &junction_id2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(junction2.recv().await.is_none());
+
+    // Once the offers overlap again, the peers agree
+    junction2.set_protocol_offer(ProtocolOffer::default()).await;
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        junction1.get_peer_protocol(addr2).await,
+        Some(Ok(Agreement {
+            version: PROTOCOL_VERSION,
+            capabilities: Capabilities::supported(),
+        }))
+    );
+}
+
+#[tokio::test]
+async fn test_junction_refuses_legacy_peer() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1130);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2238);
+
+    let junction = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction");
+
+    // A junction from before offers were added sends a hello without one
+    let legacy = SlowUdpSocket::new(addr2).await.unwrap();
+    let mut hello = SlowPackage::new_hello(0, JunctionId::new("legacy"), &ProtocolOffer::LEGACY);
+    hello.payload.clear();
+    hello.header.payload_size = 0;
+    legacy.send_package(&hello, &addr1).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(
+        junction.get_peer_protocol(addr2).await,
+        Some(Err(NegotiationError::LegacyPeer))
+    );
+
+    // Nothing but the hello response is sent to it, and nothing it sends is delivered
+    let mut message = SlowPackage::new_json_payload(
+        JunctionId::new("1"),
+        JunctionId::new("legacy"),
+        &json!({"key": "value"}),
+    );
+    message.set_package_id(1);
+    legacy.send_package(&message, &addr1).await.unwrap();
+    junction
+        .send(json!({"key": "value"}), &JunctionId::new("legacy"))
+        .await;
+    while let Ok(Some((package, _))) =
+        tokio::time::timeout(Duration::from_millis(200), legacy.receive_package()).await
+    {
+        assert_eq!(package.package_type(), Ok(PackageType::Hello));
+    }
+    assert!(junction.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn test_tcp_junction_refuses_incompatible_peer() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    let junction1 = SlowTcpJunction::new(any_port, JunctionId::new("junction1"));
+    let junction2 = SlowTcpJunction::new(any_port, JunctionId::new("junction2"));
+
+    // junction2 insists on a feature junction1 does not offer
+    junction2
+        .set_protocol_offer(ProtocolOffer {
+            capabilities: Capabilities::ENCRYPTION,
+            required: Capabilities::ENCRYPTION,
+            ..ProtocolOffer::default()
+        })
+        .await;
+
+    let error = junction1
+        .clone()
+        .connect(junction2.local_addr())
+        .await
+        .expect_err("Incompatible junctions linked up");
+    assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+    assert!(error.to_string().contains("required capabilities"));
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(junction1.link_count().await, 0);
+    assert_eq!(junction2.link_count().await, 0);
+}
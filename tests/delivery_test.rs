@@ -0,0 +1,46 @@
+use slow::delivery::{DeliveryError, DeliveryErrorKind};
+use slow::junction::JunctionId;
+use slow::package::{PackageType, SlowPackage};
+
+#[test]
+fn test_delivery_error_report() {
+    let sender = JunctionId::new("sender");
+    let recipient = JunctionId::new("recipient");
+    let reporter = JunctionId::new("reporter");
+
+    let mut package = SlowPackage::new_bin_payload(recipient.clone(), sender.clone(), b"data");
+    package.set_package_id(42);
+
+    let error_package =
+        DeliveryError::report(DeliveryErrorKind::HopLimitExceeded, &package, &reporter).unwrap();
+    assert_eq!(error_package.package_type(), Ok(PackageType::DeliveryError));
+    assert_eq!(*error_package.recipient_id(), sender);
+
+    // The error survives a round trip over the wire
+    let error_package = SlowPackage::unpack(&error_package.pack(1)).unwrap();
+    let error = DeliveryError::from_package(&error_package).unwrap();
+    assert_eq!(error.kind, DeliveryErrorKind::HopLimitExceeded);
+    assert_eq!(error.reporter_id, reporter);
+    assert_eq!(error.recipient_id, recipient);
+    assert_eq!(error.package_id, 42);
+}
+
+#[test]
+fn test_delivery_error_not_reportable() {
+    let sender = JunctionId::new("sender");
+    let reporter = JunctionId::new("reporter");
+
+    // Errors are never reported about errors or broadcasts
+    let package = SlowPackage::new_bin_payload(JunctionId::new("recipient"), sender.clone(), b"");
+    let error_package =
+        DeliveryError::report(DeliveryErrorKind::QueueFull, &package, &reporter).unwrap();
+    assert!(
+        DeliveryError::report(DeliveryErrorKind::QueueFull, &error_package, &reporter).is_none()
+    );
+
+    let howdy = SlowPackage::new_howdy(sender);
+    assert!(
+        DeliveryError::report(DeliveryErrorKind::DestinationUnreachable, &howdy, &reporter)
+            .is_none()
+    );
+}
@@ -0,0 +1,47 @@
+use serde_json::json;
+use slow::discovery::RouteDiscovery;
+use slow::junction::JunctionId;
+use slow::package::SlowPackage;
+use std::time::Duration;
+
+#[test]
+fn test_route_discovery_queue_and_resolve() {
+    let mut discovery = RouteDiscovery::new();
+    let sender_id = JunctionId::new("1");
+    let recipient_id = JunctionId::new("2");
+
+    let package = SlowPackage::new_json_payload(
+        recipient_id.clone(),
+        sender_id.clone(),
+        &json!({"key": "first"}),
+    );
+    assert!(discovery.queue(package));
+
+    // A second package for the same recipient joins the running discovery
+    let package =
+        SlowPackage::new_json_payload(recipient_id.clone(), sender_id, &json!({"key": "second"}));
+    assert!(!discovery.queue(package));
+    assert!(discovery.is_pending(&recipient_id));
+    assert_eq!(discovery.len(), 1);
+
+    let packages = discovery.resolve(&recipient_id);
+    assert_eq!(packages.len(), 2);
+    assert_eq!(packages[0].json_payload(), Some(json!({"key": "first"})));
+    assert!(discovery.is_empty());
+    assert!(discovery.resolve(&recipient_id).is_empty());
+}
+
+#[test]
+fn test_route_discovery_expire() {
+    let mut discovery = RouteDiscovery::new();
+    let recipient_id = JunctionId::new("2");
+
+    assert!(discovery.start(&recipient_id));
+    assert!(!discovery.start(&recipient_id));
+    assert!(discovery.expire(Duration::from_secs(60)).is_empty());
+
+    let expired = discovery.expire(Duration::ZERO);
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].0, recipient_id);
+    assert!(!discovery.is_pending(&recipient_id));
+}
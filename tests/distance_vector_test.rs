@@ -0,0 +1,78 @@
+use slow::distance_vector::{INFINITE_HOPS, MAX_ADVERTISEMENT_ENTRIES, RouteAdvertisement};
+use slow::junction::JunctionId;
+use slow::route::RouteTable;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+#[test]
+fn test_route_advertisement_pack_unpack() {
+    let advertisement = RouteAdvertisement {
+        entries: vec![(JunctionId::new("a"), 1), (JunctionId::new("bb"), 4)],
+    };
+
+    let packed = advertisement.pack();
+    let unpacked = RouteAdvertisement::unpack(&packed).expect("Failed to unpack advertisement");
+    assert_eq!(unpacked, advertisement);
+
+    // Truncated data is rejected
+    assert!(RouteAdvertisement::unpack(&packed[..packed.len() - 1]).is_none());
+}
+
+#[test]
+fn test_route_advertisement_poison_reverse() {
+    let neighbor = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1111);
+    let other = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2222);
+    let routes = vec![
+        (JunctionId::new("via-neighbor"), neighbor, 2),
+        (JunctionId::new("via-other"), other, 3),
+    ];
+
+    let advertisements = RouteAdvertisement::for_neighbor(&routes, neighbor);
+    assert_eq!(advertisements.len(), 1);
+    assert_eq!(
+        advertisements[0].entries,
+        vec![
+            (JunctionId::new("via-neighbor"), INFINITE_HOPS),
+            (JunctionId::new("via-other"), 3),
+        ]
+    );
+
+    // An empty route table still produces a keep-alive advertisement
+    let advertisements = RouteAdvertisement::for_neighbor(&[], neighbor);
+    assert_eq!(advertisements.len(), 1);
+    assert!(advertisements[0].entries.is_empty());
+
+    // Large route tables are split across several advertisements
+    let routes: Vec<_> = (0..MAX_ADVERTISEMENT_ENTRIES + 1)
+        .map(|i| (JunctionId::new(&i.to_string()), other, 1))
+        .collect();
+    assert_eq!(RouteAdvertisement::for_neighbor(&routes, neighbor).len(), 2);
+}
+
+#[test]
+fn test_route_table_set_remove_and_expire() {
+    let mut route_table = RouteTable::new();
+    let junction_id = JunctionId::new("1");
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1111);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2222);
+
+    route_table.set_route(&junction_id, addr1, 4);
+    route_table.set_route(&junction_id, addr2, 2);
+    assert_eq!(route_table.get_best_route(&junction_id), Some(addr2));
+    assert_eq!(
+        route_table.get_best_routes(),
+        vec![(junction_id.clone(), addr2, 2)]
+    );
+
+    assert!(route_table.remove_route(&junction_id, &addr2));
+    assert!(!route_table.remove_route(&junction_id, &addr2));
+    assert_eq!(route_table.get_best_route(&junction_id), Some(addr1));
+
+    route_table.expire_routes(Duration::ZERO);
+    assert!(route_table.get_best_route(&junction_id).is_none());
+
+    // Package tracking survives route expiry
+    assert!(route_table.update_route(&junction_id, addr1, 1, 0.0, 7));
+    route_table.expire_routes(Duration::ZERO);
+    assert!(!route_table.update_route(&junction_id, addr1, 1, 0.0, 7));
+}
@@ -0,0 +1,104 @@
+use serde_json::json;
+use slow::flood::{FloodMode, SeenPackageCache};
+use slow::junction::{JunctionId, SlowJunction};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+/// The number of junctions in the full mesh built by `flood_mesh`.
+const MESH_SIZE: u16 = 5;
+
+/// The number of packages flooded through the mesh.
+const FLOODED_PACKAGES: usize = 10;
+
+/// Floods packages for an unknown junction through a full mesh and returns the number
+/// of duplicates the junctions rejected.
+async fn flood_mesh(base_port: u16, flood_mode: FloodMode) -> usize {
+    let mut junctions = Vec::new();
+    for i in 0..MESH_SIZE {
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), base_port + i);
+        let junction = SlowJunction::new(addr, JunctionId::new(&format!("{}", i + 1)))
+            .await
+            .expect("Failed to create junction");
+        junction.set_flood_mode(flood_mode).await;
+        junctions.push(junction);
+    }
+    for junction in &junctions {
+        for other in &junctions {
+            junction.seed(other.get_address()).await;
+        }
+    }
+
+    // Nobody has a route to this junction, so every package is flooded
+    let nobody = JunctionId::new("nobody");
+    for i in 0..FLOODED_PACKAGES {
+        junctions[0].send(json!({ "package": i }), &nobody).await;
+    }
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    junctions
+        .iter()
+        .map(|junction| junction.get_duplicate_package_count())
+        .sum()
+}
+
+#[test]
+fn test_seen_package_cache_insert() {
+    let mut cache = SeenPackageCache::new(8);
+    let sender_a = JunctionId::new("a");
+    let sender_b = JunctionId::new("b");
+
+    assert!(cache.insert(&sender_a, 1));
+    assert!(!cache.insert(&sender_a, 1));
+
+    // The same package ID from a different sender is a different package
+    assert!(cache.insert(&sender_b, 1));
+    assert!(cache.contains(&sender_b, 1));
+    assert!(!cache.contains(&sender_b, 2));
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn test_seen_package_cache_eviction() {
+    let mut cache = SeenPackageCache::new(3);
+    let sender = JunctionId::new("a");
+
+    for package_id in 1..=4 {
+        assert!(cache.insert(&sender, package_id));
+    }
+
+    // The oldest entry has been evicted to make room
+    assert_eq!(cache.len(), 3);
+    assert!(!cache.contains(&sender, 1));
+    assert!(cache.contains(&sender, 4));
+    assert!(cache.insert(&sender, 1));
+}
+
+#[test]
+fn test_flood_mode_select() {
+    let candidates: Vec<u32> = (0..10).collect();
+
+    let flooded = FloodMode::Flood.select(candidates.clone());
+    assert_eq!(flooded, candidates);
+
+    let gossiped = FloodMode::Gossip { fanout: 3 }.select(candidates.clone());
+    assert_eq!(gossiped.len(), 3);
+    assert!(gossiped.iter().all(|id| candidates.contains(id)));
+
+    let gossiped = FloodMode::Gossip { fanout: 20 }.select(candidates.clone());
+    assert_eq!(gossiped.len(), candidates.len());
+}
+
+#[tokio::test]
+async fn test_junction_flood_duplicates() {
+    let relays = MESH_SIZE as usize - 1;
+
+    // Every junction relays a package once, to the neighbors it did not get it from
+    let flooded = flood_mesh(1131, FloodMode::Flood).await;
+    assert!(flooded > 0);
+    assert!(flooded <= FLOODED_PACKAGES * relays * (relays - 1));
+
+    // Relaying to a single neighbor leaves far fewer duplicates
+    let gossiped = flood_mesh(2239, FloodMode::Gossip { fanout: 1 }).await;
+    assert!(gossiped <= FLOODED_PACKAGES * relays);
+    assert!(gossiped < flooded);
+}
@@ -1,6 +1,10 @@
 use serde_json::json;
+use slow::ack::{AckError, AckPolicy};
+use slow::delivery::DeliveryErrorKind;
 use slow::junction::JunctionId;
 use slow::junction::SlowJunction;
+use slow::route::RoutingMode;
+use slow::rpc::RpcError;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::time::Duration;
 
@@ -225,6 +229,38 @@ async fn test_junction_pair() {
     assert_eq!(pong_package.addr, addr2);
 }
 
+#[tokio::test]
+async fn test_junction_pair_burst() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1136);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2244);
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, JunctionId::new("2"))
+        .await
+        .expect("Failed to create junction2");
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    // Both junctions drain their send queues while packages keep arriving, and across
+    // maintenance ticks, without losing any
+    const BURST: usize = 500;
+    for i in 0..BURST {
+        junction1
+            .send(json!({ "i": i }), &JunctionId::new("2"))
+            .await;
+        junction2
+            .send(json!({ "i": i }), &JunctionId::new("1"))
+            .await;
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    assert_eq!(junction1.get_waiting_package_count().await, BURST);
+    assert_eq!(junction2.get_waiting_package_count().await, BURST);
+}
+
 #[tokio::test]
 async fn test_junction_ping() {
     let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 7777);
@@ -250,6 +286,396 @@ async fn test_junction_ping() {
     assert_eq!(junction1.get_pong_counter().await, 1);
 }
 
+#[tokio::test]
+async fn test_junction_route_discovery() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1113);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2223);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 3333);
+
+    let junction_id3 = JunctionId::new("3");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, JunctionId::new("2"))
+        .await
+        .expect("Failed to create junction2");
+    let junction3 = SlowJunction::new(addr3, junction_id3.clone())
+        .await
+        .expect("Failed to create junction3");
+
+    junction1.set_route_discovery(true);
+    junction1.join(addr2).await;
+    junction2.join(addr3).await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    // No route to junction3 is known yet, so the package waits on a route discovery
+    assert!(junction1.get_best_route(&junction_id3).await.is_none());
+    let ping = json!({"key": "ping"});
+    junction1.send(ping.clone(), &junction_id3).await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    // The route reply has cached a route and released the queued package
+    assert!(!junction1.is_discovering_route(&junction_id3).await);
+    assert_eq!(junction1.get_best_route(&junction_id3).await, Some(addr2));
+    assert_eq!(junction3.get_waiting_package_count().await, 1);
+
+    let received_package = junction3.recv().await.unwrap();
+    assert_eq!(received_package.json, ping);
+    assert_eq!(received_package.addr, addr2);
+}
+
+#[tokio::test]
+async fn test_junction_distance_vector() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1114);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2224);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 3334);
+
+    let junction_id1 = JunctionId::new("1");
+    let junction_id3 = JunctionId::new("3");
+
+    let junction1 = SlowJunction::new(addr1, junction_id1.clone())
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, JunctionId::new("2"))
+        .await
+        .expect("Failed to create junction2");
+    let junction3 = SlowJunction::new(addr3, junction_id3.clone())
+        .await
+        .expect("Failed to create junction3");
+
+    let routing_mode = RoutingMode::DistanceVector {
+        interval: Duration::from_millis(100),
+    };
+    junction1.set_routing_mode(routing_mode).await;
+    junction2.set_routing_mode(routing_mode).await;
+    junction3.set_routing_mode(routing_mode).await;
+
+    junction1.join(addr2).await;
+    junction2.join(addr3).await;
+
+    // Routes propagate through advertisements alone, without any traffic
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(junction1.get_best_route(&junction_id3).await, Some(addr2));
+    assert_eq!(junction3.get_best_route(&junction_id1).await, Some(addr2));
+
+    let ping = json!({"key": "ping"});
+    junction1.send(ping.clone(), &junction_id3).await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    assert_eq!(junction3.get_waiting_package_count().await, 1);
+    assert_eq!(junction2.get_duplicate_package_count(), 0);
+}
+
+#[tokio::test]
+async fn test_junction_traceroute() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1115);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2225);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 3335);
+
+    let junction_id1 = JunctionId::new("1");
+    let junction_id2 = JunctionId::new("2");
+    let junction_id3 = JunctionId::new("3");
+
+    let junction1 = SlowJunction::new(addr1, junction_id1.clone())
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+    let _junction3 = SlowJunction::new(addr3, junction_id3.clone())
+        .await
+        .expect("Failed to create junction3");
+
+    junction1.join(addr2).await;
+    junction2.join(addr3).await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    let hops = junction1
+        .traceroute(&junction_id3)
+        .await
+        .expect("Traceroute got no reply");
+
+    let path: Vec<JunctionId> = hops.iter().map(|hop| hop.junction_id.clone()).collect();
+    assert_eq!(path, vec![junction_id1, junction_id2, junction_id3]);
+    assert!(
+        hops.windows(2)
+            .all(|w| w[0].timestamp_ms <= w[1].timestamp_ms)
+    );
+}
+
+#[tokio::test]
+async fn test_junction_queue_full() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1116);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2226);
+
+    let junction_id2 = JunctionId::new("2");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+
+    junction2.set_receive_queue_capacity(1);
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // The second package does not fit in the received queue and is reported back
+    junction1.send(json!({"key": 1}), &junction_id2).await;
+    junction1.send(json!({"key": 2}), &junction_id2).await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    assert_eq!(junction2.get_waiting_package_count().await, 1);
+
+    let error = junction1.recv_delivery_error().await.unwrap();
+    assert_eq!(error.kind, DeliveryErrorKind::QueueFull);
+    assert_eq!(error.reporter_id, junction_id2);
+    assert_eq!(error.recipient_id, junction_id2);
+    assert!(junction1.recv_delivery_error().await.is_none());
+}
+
+#[tokio::test]
+async fn test_junction_route_discovery_unreachable() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1138);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2246);
+
+    let junction_id1 = JunctionId::new("1");
+    let missing_id = JunctionId::new("missing");
+
+    let junction1 = SlowJunction::new(addr1, junction_id1.clone())
+        .await
+        .expect("Failed to create junction1");
+    let _junction2 = SlowJunction::new(addr2, JunctionId::new("2"))
+        .await
+        .expect("Failed to create junction2");
+
+    junction1.set_route_discovery(true);
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Nobody answers the route request, so the queued package is given up on
+    junction1.send(json!({"key": "lost"}), &missing_id).await;
+    tokio::time::sleep(Duration::from_millis(1300)).await;
+
+    let error = junction1.recv_delivery_error().await.unwrap();
+    assert_eq!(error.kind, DeliveryErrorKind::DestinationUnreachable);
+    assert_eq!(error.reporter_id, junction_id1);
+    assert_eq!(error.recipient_id, missing_id);
+    assert!(junction1.recv_delivery_error().await.is_none());
+}
+
+#[tokio::test]
+async fn test_junction_send_acked() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1117);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2227);
+
+    let junction_id2 = JunctionId::new("2");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let handle = junction1.send_acked(json!({"key": 1}), &junction_id2).await;
+    assert_eq!(handle.wait().await, Ok(()));
+    assert_eq!(junction2.get_waiting_package_count().await, 1);
+
+    // Nobody acks a package for a junction that does not exist
+    junction1
+        .set_ack_policy(AckPolicy {
+            timeout: Duration::from_millis(100),
+            retries: 1,
+        })
+        .await;
+    let handle = junction1
+        .send_acked(json!({"key": 2}), &JunctionId::new("missing"))
+        .await;
+    assert_eq!(handle.wait().await, Err(AckError::TimedOut));
+}
+
+#[tokio::test]
+async fn test_junction_send_acked_queued() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1140);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2248);
+
+    let junction_id2 = JunctionId::new("2");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // The acked package waits its turn behind the packages queued before it
+    for i in 0..20 {
+        junction1.send(json!({"key": i}), &junction_id2).await;
+    }
+    let handle = junction1
+        .send_acked(json!({"key": "acked"}), &junction_id2)
+        .await;
+    assert_eq!(handle.wait().await, Ok(()));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(junction2.get_waiting_package_count().await, 21);
+    for i in 0..20 {
+        assert_eq!(junction2.recv().await.unwrap().json, json!({"key": i}));
+    }
+    assert_eq!(
+        junction2.recv().await.unwrap().json,
+        json!({"key": "acked"})
+    );
+}
+
+#[tokio::test]
+async fn test_junction_rpc_call() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1118);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2228);
+
+    let junction_id2 = JunctionId::new("2");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+
+    junction2
+        .register_rpc_handler("double", |args| {
+            args.as_i64()
+                .map(|n| json!(n * 2))
+                .ok_or_else(|| "expected a number".to_string())
+        })
+        .await;
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let timeout = Duration::from_secs(1);
+    let result = junction1
+        .call(&junction_id2, "double", json!(21), timeout)
+        .await;
+    assert_eq!(result, Ok(json!(42)));
+
+    let result = junction1
+        .call(&junction_id2, "double", json!("x"), timeout)
+        .await;
+    assert_eq!(
+        result,
+        Err(RpcError::HandlerFailed("expected a number".to_string()))
+    );
+
+    let result = junction1
+        .call(&junction_id2, "triple", json!(1), timeout)
+        .await;
+    assert_eq!(result, Err(RpcError::MethodNotFound("triple".to_string())));
+
+    // RPC packages are never queued for the application
+    assert_eq!(junction1.get_waiting_package_count().await, 0);
+    assert_eq!(junction2.get_waiting_package_count().await, 0);
+
+    // Nobody answers a call to a junction that does not exist
+    let result = junction1
+        .call(
+            &JunctionId::new("missing"),
+            "double",
+            json!(1),
+            Duration::from_millis(200),
+        )
+        .await;
+    assert_eq!(result, Err(RpcError::TimedOut));
+}
+
+#[tokio::test]
+async fn test_junction_send_ordered() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1119);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2229);
+
+    let junction_id2 = JunctionId::new("2");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for i in 0..5 {
+        junction1
+            .send_ordered(json!({"key": i}), &junction_id2)
+            .await;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for i in 0..5 {
+        let packet = junction2.recv().await.expect("Missing ordered package");
+        assert_eq!(packet.json, json!({"key": i}));
+    }
+    assert!(junction2.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn test_junction_message_expiry() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1125);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2234);
+
+    let junction_id2 = JunctionId::new("2");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Already expired when it is queued, so it is dropped before the send loop runs
+    junction1
+        .send_expiring(json!({"key": "stale"}), &junction_id2, Duration::ZERO)
+        .await;
+    assert_eq!(junction1.get_expired_package_count(), 1);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(junction1.get_expired_package_count(), 1);
+    assert!(junction2.recv().await.is_none());
+
+    // Expires while waiting in the receive queue
+    junction1
+        .send_expiring(
+            json!({"key": "short"}),
+            &junction_id2,
+            Duration::from_millis(150),
+        )
+        .await;
+    junction1
+        .send_expiring(
+            json!({"key": "long"}),
+            &junction_id2,
+            Duration::from_secs(60),
+        )
+        .await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let packet = junction2.recv().await.expect("Missing unexpired package");
+    assert_eq!(packet.json, json!({"key": "long"}));
+    assert_eq!(junction2.get_expired_package_count(), 1);
+}
+
 #[test]
 fn test_junction_id_serialization() {
     // Create a JunctionId
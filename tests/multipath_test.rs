@@ -0,0 +1,134 @@
+use serde_json::json;
+use slow::junction::{JunctionId, SlowJunction};
+use slow::multipath::{MultipathPolicy, MultipathSelector};
+use slow::package::SlowPackage;
+use slow::route::RouteTable;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+fn addr(port: u16) -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port)
+}
+
+fn package(sender: &str, recipient: &str) -> SlowPackage {
+    SlowPackage::new_json_payload(
+        JunctionId::new(recipient),
+        JunctionId::new(sender),
+        &json!({ "data": "test" }),
+    )
+}
+
+#[test]
+fn test_multipath_single() {
+    let mut selector = MultipathSelector::default();
+    let package = package("a", "b");
+    let candidates = [(1u32, 2), (2u32, 1)];
+
+    for _ in 0..10 {
+        assert_eq!(selector.select(&package, &candidates), Some(1));
+    }
+
+    let empty: [(u32, u32); 0] = [];
+    assert_eq!(selector.select(&package, &empty), None);
+}
+
+#[test]
+fn test_multipath_weighted_round_robin() {
+    let mut selector = MultipathSelector::new(MultipathPolicy::WeightedRoundRobin);
+    let package = package("a", "b");
+    let candidates = [(1u32, 2), (2u32, 1)];
+
+    let mut counts = [0; 3];
+    for _ in 0..30 {
+        let link = selector.select(&package, &candidates).unwrap();
+        counts[link as usize] += 1;
+    }
+
+    // Traffic is split in proportion to the weights
+    assert_eq!(counts[1], 20);
+    assert_eq!(counts[2], 10);
+}
+
+#[test]
+fn test_multipath_flow_hash() {
+    let mut selector = MultipathSelector::new(MultipathPolicy::FlowHash);
+    let candidates = [(1u32, 1), (2u32, 1), (3u32, 1)];
+
+    // Every package of a flow takes the same path
+    let package = package("a", "b");
+    let first = selector.select(&package, &candidates);
+    for _ in 0..10 {
+        assert_eq!(selector.select(&package, &candidates), first);
+    }
+}
+
+#[test]
+fn test_route_table_multipath_routes() {
+    let mut route_table = RouteTable::new();
+    let junction_id = JunctionId::new("b");
+
+    route_table.set_route(&junction_id, addr(1), 2);
+    route_table.set_route(&junction_id, addr(2), 3);
+    route_table.set_route(&junction_id, addr(3), 5);
+
+    let routes = route_table.get_routes(&junction_id);
+    assert_eq!(routes, vec![(addr(1), 2), (addr(2), 3), (addr(3), 5)]);
+
+    // Routes too far behind the best one are not used for load balancing
+    let routes = route_table.get_multipath_routes(&junction_id);
+    assert_eq!(routes, vec![(addr(1), 2), (addr(2), 1)]);
+
+    assert!(
+        route_table
+            .get_multipath_routes(&JunctionId::new("c"))
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_junction_send_critical_shared_relay() {
+    let ports = [1137, 2245, 3336, 4443, 5556];
+    let mut junctions = Vec::new();
+    for (i, port) in ports.iter().enumerate() {
+        let junction = SlowJunction::new(addr(*port), JunctionId::new(&format!("{}", i + 1)))
+            .await
+            .expect("Failed to create junction");
+        junctions.push(junction);
+    }
+
+    // junction1 -> (junction2, junction3) -> junction4 -> junction5
+    junctions[0].join(addr(ports[1])).await;
+    junctions[0].join(addr(ports[2])).await;
+    junctions[1].join(addr(ports[3])).await;
+    junctions[2].join(addr(ports[3])).await;
+    junctions[3].join(addr(ports[4])).await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    // junction1 learns a route to junction5 through each of its neighbors
+    junctions[4]
+        .send(json!({ "key": "hello" }), &JunctionId::new("1"))
+        .await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert!(junctions[0].recv().await.is_some());
+
+    let relayed: Vec<u32> = junctions
+        .iter()
+        .map(|junction| junction.get_unique_package_count())
+        .collect();
+    let duplicates = junctions[3].get_duplicate_package_count();
+
+    junctions[0]
+        .send_critical(json!({ "key": "critical" }), &JunctionId::new("5"))
+        .await;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+
+    // The copies leave through different next hops...
+    assert_eq!(junctions[1].get_unique_package_count(), relayed[1] + 1);
+    assert_eq!(junctions[2].get_unique_package_count(), relayed[2] + 1);
+
+    // ...but both reach junction4, so losing it would lose both
+    assert_eq!(junctions[3].get_unique_package_count(), relayed[3] + 1);
+    assert_eq!(junctions[3].get_duplicate_package_count(), duplicates + 1);
+
+    assert_eq!(junctions[4].get_waiting_package_count().await, 1);
+}
@@ -1,6 +1,9 @@
 use serde_json::json;
+use slow::capability::ProtocolOffer;
 use slow::junction::JunctionId;
 use slow::package::{PackageType, SlowPackage};
+use slow::priority::Priority;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[test]
 fn test_package_new_json_payload() {
@@ -91,13 +94,15 @@ fn test_package_hello() {
     let sender = JunctionId::new("sender");
     let package_id = 12345;
 
-    let hello = SlowPackage::new_hello(package_id, sender.clone());
+    let offer = ProtocolOffer::default();
+
+    let hello = SlowPackage::new_hello(package_id, sender.clone(), &offer);
 
     assert_eq!(hello.package_type().unwrap(), PackageType::Hello);
     assert_eq!(hello.sender_id(), &sender);
     assert_eq!(hello.package_id(), package_id);
     assert_eq!(hello.hop_count(), 0);
-    assert!(hello.payload.is_empty());
+    assert_eq!(hello.protocol_offer(), Ok(offer));
 }
 
 #[test]
@@ -177,6 +182,17 @@ fn test_package_type_conversion() {
     assert_eq!(u8::from(PackageType::Json), 3);
     assert_eq!(u8::from(PackageType::Bin), 4);
     assert_eq!(u8::from(PackageType::Howdy), 5);
+    assert_eq!(u8::from(PackageType::RouteRequest), 6);
+    assert_eq!(u8::from(PackageType::RouteReply), 7);
+    assert_eq!(u8::from(PackageType::RouteAdvertisement), 8);
+    assert_eq!(u8::from(PackageType::Traceroute), 9);
+    assert_eq!(u8::from(PackageType::TracerouteReply), 10);
+    assert_eq!(u8::from(PackageType::DeliveryError), 11);
+    assert_eq!(u8::from(PackageType::Ack), 12);
+    assert_eq!(u8::from(PackageType::RpcRequest), 13);
+    assert_eq!(u8::from(PackageType::RpcResponse), 14);
+    assert_eq!(u8::from(PackageType::Stream), 15);
+    assert_eq!(u8::from(PackageType::Transfer), 16);
 
     assert_eq!(PackageType::try_from(0).unwrap(), PackageType::Hello);
     assert_eq!(PackageType::try_from(1).unwrap(), PackageType::Ping);
@@ -184,7 +200,132 @@ fn test_package_type_conversion() {
     assert_eq!(PackageType::try_from(3).unwrap(), PackageType::Json);
     assert_eq!(PackageType::try_from(4).unwrap(), PackageType::Bin);
     assert_eq!(PackageType::try_from(5).unwrap(), PackageType::Howdy);
+    assert_eq!(PackageType::try_from(6).unwrap(), PackageType::RouteRequest);
+    assert_eq!(PackageType::try_from(7).unwrap(), PackageType::RouteReply);
+    assert_eq!(
+        PackageType::try_from(8).unwrap(),
+        PackageType::RouteAdvertisement
+    );
+    assert_eq!(PackageType::try_from(9).unwrap(), PackageType::Traceroute);
+    assert_eq!(
+        PackageType::try_from(10).unwrap(),
+        PackageType::TracerouteReply
+    );
+    assert_eq!(
+        PackageType::try_from(11).unwrap(),
+        PackageType::DeliveryError
+    );
+    assert_eq!(PackageType::try_from(12).unwrap(), PackageType::Ack);
+    assert_eq!(PackageType::try_from(13).unwrap(), PackageType::RpcRequest);
+    assert_eq!(PackageType::try_from(14).unwrap(), PackageType::RpcResponse);
+    assert_eq!(PackageType::try_from(15).unwrap(), PackageType::Stream);
+    assert_eq!(PackageType::try_from(16).unwrap(), PackageType::Transfer);
 
     // Test invalid conversion
-    assert!(PackageType::try_from(6).is_err());
+    assert!(PackageType::try_from(255).is_err());
+}
+
+#[test]
+fn test_package_ack() {
+    let recipient = JunctionId::new("recipient");
+    let sender = JunctionId::new("sender");
+
+    // The ack flag survives serialization
+    let mut package = SlowPackage::new_ping(recipient.clone(), sender.clone());
+    assert!(!package.is_ack_requested());
+    package.request_ack();
+    let deserialized = SlowPackage::unpack(&package.pack(7)).unwrap();
+    assert!(deserialized.is_ack_requested());
+    assert_eq!(deserialized.package_id(), 7);
+
+    let ack = SlowPackage::new_ack(sender, recipient, 7);
+    assert_eq!(ack.package_type().unwrap(), PackageType::Ack);
+    assert_eq!(ack.acked_package_id(), Some(7));
+    assert!(!ack.is_ack_requested());
+}
+
+#[test]
+fn test_package_retransmit() {
+    let recipient = JunctionId::new("recipient");
+    let sender = JunctionId::new("sender");
+
+    let mut package = SlowPackage::new_json_payload(recipient, sender, &json!({"n": 1}));
+    package.set_package_id(7);
+    assert!(!package.is_retransmit());
+    assert_eq!(package.first_package_id(), 7);
+    let plain_len = package.pack(7).len();
+
+    // Every retransmission remembers the first ID, not the one before it
+    package.set_retransmit(8);
+    package.set_retransmit(9);
+    let packed = package.pack(package.package_id());
+    assert_eq!(packed.len(), plain_len + 4);
+
+    let deserialized = SlowPackage::unpack(&packed).unwrap();
+    assert!(deserialized.is_retransmit());
+    assert_eq!(deserialized.package_id(), 9);
+    assert_eq!(deserialized.first_package_id(), 7);
+}
+
+#[test]
+fn test_package_sequence() {
+    let recipient = JunctionId::new("recipient");
+    let sender = JunctionId::new("sender");
+
+    // Unordered packages carry no sequence on the wire
+    let mut package = SlowPackage::new_bin_payload(recipient, sender, b"data");
+    let unordered_len = package.pack(1).len();
+    assert_eq!(package.sequence(), None);
+
+    package.set_sequence(42);
+    let packed = package.pack(1);
+    assert_eq!(packed.len(), unordered_len + 4);
+
+    let deserialized = SlowPackage::unpack(&packed).unwrap();
+    assert_eq!(deserialized.sequence(), Some(42));
+    assert_eq!(deserialized.payload, b"data");
+}
+
+#[test]
+fn test_package_expiry() {
+    let recipient = JunctionId::new("recipient");
+    let sender = JunctionId::new("sender");
+
+    // Packages without a lifetime carry no deadline on the wire
+    let mut package = SlowPackage::new_bin_payload(recipient, sender, b"data");
+    let plain_len = package.pack(1).len();
+    assert_eq!(package.expires_at(), None);
+    assert!(!package.is_expired());
+
+    let deadline = UNIX_EPOCH + Duration::from_millis(4_000_000_000_123);
+    package.set_sequence(7);
+    package.set_expires_at(deadline);
+    let packed = package.pack(1);
+    assert_eq!(packed.len(), plain_len + 4 + 8);
+
+    let deserialized = SlowPackage::unpack(&packed).unwrap();
+    assert_eq!(deserialized.sequence(), Some(7));
+    assert_eq!(deserialized.expires_at(), Some(deadline));
+    assert!(!deserialized.is_expired());
+
+    package.set_expires_at(SystemTime::now() - Duration::from_secs(1));
+    assert!(package.is_expired());
+    package.set_max_age(Duration::from_secs(60));
+    assert!(!package.is_expired());
+}
+
+#[test]
+fn test_package_priority() {
+    let recipient = JunctionId::new("recipient");
+    let sender = JunctionId::new("sender");
+
+    // Without an explicit priority the package type decides
+    let mut package = SlowPackage::new_bin_payload(recipient.clone(), sender.clone(), b"data");
+    assert_eq!(package.priority(), Priority::Bulk);
+    assert_eq!(SlowPackage::new_howdy(sender).priority(), Priority::Control);
+
+    package.set_priority(Priority::Control);
+    let deserialized = SlowPackage::unpack(&package.pack(1)).unwrap();
+    assert_eq!(deserialized.priority(), Priority::Control);
+    assert_eq!(deserialized.payload, b"data");
 }
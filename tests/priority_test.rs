@@ -0,0 +1,124 @@
+use serde_json::json;
+use slow::junction::{JunctionId, SlowJunction};
+use slow::priority::{Priority, PriorityQueue, PriorityWeights, SendScheduler};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+#[test]
+fn test_priority_queue_weighted_order() {
+    let mut queue = PriorityQueue::new(PriorityWeights {
+        control: 2,
+        interactive: 1,
+        bulk: 1,
+    });
+    for i in 0..4 {
+        queue.push(Priority::Bulk, ("bulk", i));
+        queue.push(Priority::Interactive, ("interactive", i));
+        queue.push(Priority::Control, ("control", i));
+    }
+    assert_eq!(queue.len(), 12);
+
+    let order: Vec<_> = std::iter::from_fn(|| queue.pop()).collect();
+    assert_eq!(
+        order,
+        vec![
+            ("control", 0),
+            ("control", 1),
+            ("interactive", 0),
+            ("bulk", 0),
+            ("control", 2),
+            ("control", 3),
+            ("interactive", 1),
+            ("bulk", 1),
+            ("interactive", 2),
+            ("bulk", 2),
+            ("interactive", 3),
+            ("bulk", 3),
+        ]
+    );
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn test_priority_queue_zero_weight() {
+    let mut queue = PriorityQueue::new(PriorityWeights {
+        control: 1,
+        interactive: 1,
+        bulk: 0,
+    });
+    queue.push(Priority::Bulk, 1);
+    queue.push(Priority::Control, 2);
+
+    // A class with no weight only goes when nothing else is waiting
+    assert_eq!(queue.pop(), Some(2));
+    assert_eq!(queue.pop(), Some(1));
+    assert_eq!(queue.pop(), None);
+}
+
+#[tokio::test]
+async fn test_send_scheduler_order() {
+    let scheduler = Arc::new(SendScheduler::default());
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    // Hold the permit while sends of each priority line up behind it
+    let permit = scheduler.acquire(Priority::Control).await;
+    let mut tasks = Vec::new();
+    for priority in [Priority::Bulk, Priority::Interactive, Priority::Control] {
+        let scheduler = scheduler.clone();
+        let order = order.clone();
+        tasks.push(tokio::spawn(async move {
+            let _permit = scheduler.acquire(priority).await;
+            order.lock().await.push(priority);
+        }));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    drop(permit);
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert_eq!(
+        *order.lock().await,
+        vec![Priority::Control, Priority::Interactive, Priority::Bulk]
+    );
+}
+
+#[tokio::test]
+async fn test_junction_send_with_priority() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1126);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2235);
+
+    let junction_id2 = JunctionId::new("2");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+
+    let weights = PriorityWeights {
+        control: 4,
+        interactive: 2,
+        bulk: 1,
+    };
+    junction1.set_priority_weights(weights).await;
+    assert_eq!(junction1.get_priority_weights().await, weights);
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let message = json!({"key": "urgent"});
+    junction1
+        .send_with_priority(message.clone(), &junction_id2, Priority::Control)
+        .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let received = junction2
+        .recv()
+        .await
+        .expect("Prioritised message was not delivered");
+    assert_eq!(received.json, message);
+}
@@ -0,0 +1,75 @@
+use slow::junction::JunctionId;
+use slow::reorder::{ReorderBuffer, ReorderPolicy, SequenceCounter};
+use std::time::Duration;
+
+#[test]
+fn test_sequence_counter() {
+    let mut counter = SequenceCounter::new();
+    let a = JunctionId::new("a");
+    let b = JunctionId::new("b");
+
+    // Each recipient has its own sequence
+    assert_eq!(counter.next(&a), 1);
+    assert_eq!(counter.next(&a), 2);
+    assert_eq!(counter.next(&b), 1);
+}
+
+#[test]
+fn test_reorder_buffer_in_order() {
+    let mut buffer = ReorderBuffer::default();
+    let sender = JunctionId::new("sender");
+
+    assert_eq!(buffer.push(&sender, 1, "one"), vec!["one"]);
+    assert_eq!(buffer.push(&sender, 3, "three"), Vec::<&str>::new());
+    assert_eq!(buffer.push(&sender, 4, "four"), Vec::<&str>::new());
+    assert_eq!(buffer.len(), 2);
+
+    // The missing package releases everything behind it
+    assert_eq!(buffer.push(&sender, 2, "two"), vec!["two", "three", "four"]);
+    assert!(buffer.is_empty());
+
+    // Duplicates and late packages are dropped
+    assert_eq!(buffer.push(&sender, 2, "two"), Vec::<&str>::new());
+
+    // Senders are ordered independently
+    let other = JunctionId::new("other");
+    assert_eq!(buffer.push(&other, 1, "other"), vec!["other"]);
+}
+
+#[test]
+fn test_reorder_buffer_window() {
+    let mut buffer = ReorderBuffer::new(ReorderPolicy {
+        window: 2,
+        timeout: Duration::from_secs(60),
+    });
+    let sender = JunctionId::new("sender");
+
+    assert!(buffer.push(&sender, 2, 2).is_empty());
+    assert!(buffer.push(&sender, 3, 3).is_empty());
+
+    // Overflowing the window gives up on the gap
+    assert_eq!(buffer.push(&sender, 5, 5), vec![2, 3]);
+    assert_eq!(buffer.push(&sender, 4, 4), vec![4, 5]);
+    assert!(buffer.push(&sender, 3, 3).is_empty());
+
+    // A sender that starts again from 1 is followed once it is well past the window
+    assert_eq!(buffer.push(&sender, 1, 1), vec![1]);
+}
+
+#[test]
+fn test_reorder_buffer_timeout() {
+    let mut buffer = ReorderBuffer::new(ReorderPolicy {
+        window: 64,
+        timeout: Duration::ZERO,
+    });
+    let sender = JunctionId::new("sender");
+
+    assert!(buffer.push(&sender, 2, 2).is_empty());
+    assert!(buffer.push(&sender, 4, 4).is_empty());
+
+    // Each expired gap is skipped in turn
+    assert_eq!(buffer.take_expired(), vec![2]);
+    assert_eq!(buffer.take_expired(), vec![4]);
+    assert!(buffer.take_expired().is_empty());
+    assert!(buffer.is_empty());
+}
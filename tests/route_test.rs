@@ -6,28 +6,28 @@ use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 fn test_route_info() {
     let mut route_package_info = RoutePackageInfo::new();
     let success = route_package_info.update(132);
-    assert!(success == true);
+    assert!(success);
 
     let success = route_package_info.update(3);
-    assert!(success == false);
+    assert!(!success);
 
     let success = route_package_info.update(101);
-    assert!(success == true);
+    assert!(success);
 
     let success = route_package_info.update(100);
-    assert!(success == false);
+    assert!(!success);
 
     let success = route_package_info.update(33);
-    assert!(success == false);
+    assert!(!success);
 
     let success = route_package_info.update(134);
-    assert!(success == true);
+    assert!(success);
 
     let success = route_package_info.update(133);
-    assert!(success == true);
+    assert!(success);
 
     let success = route_package_info.update(132);
-    assert!(success == false);
+    assert!(!success);
 }
 
 #[test]
@@ -0,0 +1,78 @@
+use serde_json::json;
+use slow::rpc::{self, PendingCalls, RpcError, RpcRegistry, RpcRequest, RpcResponse};
+use std::sync::Arc;
+
+fn request(call_id: u64, method: &str) -> RpcRequest {
+    RpcRequest {
+        call_id,
+        method: method.to_string(),
+        args: json!({"a": 2, "b": 3}),
+    }
+}
+
+#[test]
+fn test_rpc_serve() {
+    let mut registry = RpcRegistry::new();
+    registry.register(
+        "add",
+        Arc::new(|args| {
+            let a = args["a"].as_i64().ok_or("missing a")?;
+            let b = args["b"].as_i64().ok_or("missing b")?;
+            Ok(json!(a + b))
+        }),
+    );
+    registry.register("fail", Arc::new(|_| Err("broken".to_string())));
+
+    let response = rpc::serve(registry.get("add"), request(1, "add"));
+    assert_eq!(response.call_id, 1);
+    assert_eq!(response.result, Ok(json!(5)));
+
+    let response = rpc::serve(registry.get("fail"), request(2, "fail"));
+    assert_eq!(
+        response.result,
+        Err(RpcError::HandlerFailed("broken".to_string()))
+    );
+
+    let response = rpc::serve(registry.get("missing"), request(3, "missing"));
+    assert_eq!(
+        response.result,
+        Err(RpcError::MethodNotFound("missing".to_string()))
+    );
+
+    assert!(registry.unregister("add"));
+    assert!(registry.get("add").is_none());
+}
+
+#[test]
+fn test_rpc_pack_unpack() {
+    let request = request(7, "add");
+    assert_eq!(RpcRequest::unpack(&request.pack()), Some(request));
+
+    let response = RpcResponse {
+        call_id: 7,
+        result: Err(RpcError::MethodNotFound("add".to_string())),
+    };
+    assert_eq!(RpcResponse::unpack(&response.pack()), Some(response));
+    assert_eq!(RpcResponse::unpack(b"garbage"), None);
+}
+
+#[tokio::test]
+async fn test_pending_calls() {
+    let mut calls = PendingCalls::new();
+    let (call_id1, receiver1) = calls.start();
+    let (call_id2, _receiver2) = calls.start();
+    assert_ne!(call_id1, call_id2);
+    assert_eq!(calls.len(), 2);
+
+    // Responses are matched to calls by call ID
+    let response = RpcResponse {
+        call_id: call_id1,
+        result: Ok(json!("done")),
+    };
+    assert!(calls.complete(response.clone()));
+    assert!(!calls.complete(response));
+    assert_eq!(receiver1.await.unwrap(), Ok(json!("done")));
+
+    calls.cancel(call_id2);
+    assert!(calls.is_empty());
+}
@@ -35,7 +35,7 @@ impl JunctionSimulation {
         let target_junction = &self.junctions[rng.gen_range(0..self.junctions.len())];
 
         source_junction
-            .ping(&target_junction.get_junction_id())
+            .ping(target_junction.get_junction_id())
             .await;
 
         sleep(Duration::from_millis(250)).await;
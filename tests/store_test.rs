@@ -0,0 +1,295 @@
+use serde_json::json;
+use slow::delivery::DeliveryErrorKind;
+use slow::junction::{JunctionId, SlowJunction};
+use slow::package::SlowPackage;
+use slow::store::{PackageStore, StorePolicy};
+use slow::tcp::tcp_junction::SlowTcpJunction;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+/// Returns a JSON package from "a" to `recipient` with the given package ID.
+fn message(recipient: &str, package_id: u32) -> SlowPackage {
+    let mut package = SlowPackage::new_json_payload(
+        JunctionId::new(recipient),
+        JunctionId::new("a"),
+        &json!({ "n": package_id }),
+    );
+    package.set_package_id(package_id);
+    package
+}
+
+#[test]
+fn test_package_store_limits() {
+    let mut store = PackageStore::open(StorePolicy {
+        max_packages: 2,
+        ..StorePolicy::default()
+    })
+    .unwrap();
+
+    assert!(store.hold(message("b", 1)));
+    assert!(store.hold(message("c", 2)));
+    assert!(store.hold(message("b", 3)));
+    assert!(!store.hold(SlowPackage::new_howdy(JunctionId::new("a"))));
+
+    // The oldest package made room for the third
+    assert_eq!(store.len(), 2);
+    assert_eq!(
+        store.recipients(),
+        vec![JunctionId::new("c"), JunctionId::new("b")]
+    );
+
+    let taken = store.take(&JunctionId::new("b"));
+    assert_eq!(taken.len(), 1);
+    assert_eq!(taken[0].package_id(), 3);
+    assert_eq!(store.len(), 1);
+
+    let mut store = PackageStore::open(StorePolicy {
+        max_bytes: 10,
+        ..StorePolicy::default()
+    })
+    .unwrap();
+    assert!(!store.hold(message("b", 1)));
+    assert!(store.is_empty());
+
+    let mut store = PackageStore::open(StorePolicy {
+        max_age: Duration::ZERO,
+        ..StorePolicy::default()
+    })
+    .unwrap();
+    assert!(store.hold(message("b", 1)));
+    assert_eq!(store.expire().len(), 1);
+    assert!(store.is_empty());
+}
+
+#[test]
+fn test_package_store_discard() {
+    let mut store = PackageStore::open(StorePolicy::default()).unwrap();
+    let mut resent = message("b", 1);
+    resent.set_retransmit(4);
+    assert!(store.hold(message("b", 1)));
+    assert!(store.hold(resent));
+    assert!(store.hold(message("b", 2)));
+
+    // An ack for the first ID drops every transmission of the package
+    assert!(!store.discard(&JunctionId::new("b"), 1));
+    assert!(store.discard(&JunctionId::new("a"), 1));
+    assert_eq!(store.len(), 1);
+    assert_eq!(store.take(&JunctionId::new("b"))[0].package_id(), 2);
+}
+
+#[test]
+fn test_package_store_persistence() {
+    let path = std::env::temp_dir().join("slow_store_test.bin");
+    let _ = std::fs::remove_file(&path);
+    let policy = StorePolicy {
+        path: Some(path.clone()),
+        ..StorePolicy::default()
+    };
+
+    let mut store = PackageStore::open(policy.clone()).unwrap();
+    store.hold(message("b", 1));
+    store.hold(message("c", 2));
+    drop(store);
+
+    let mut store = PackageStore::open(policy.clone()).unwrap();
+    assert_eq!(store.len(), 2);
+    let taken = store.take(&JunctionId::new("c"));
+    assert_eq!(taken.len(), 1);
+    assert_eq!(taken[0].pack(2), message("c", 2).pack(2));
+    drop(store);
+
+    let store = PackageStore::open(policy).unwrap();
+    assert_eq!(store.recipients(), vec![JunctionId::new("b")]);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_junction_store_and_forward() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1123);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2233);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1124);
+
+    let junction_id3 = JunctionId::new("3");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, JunctionId::new("2"))
+        .await
+        .expect("Failed to create junction2");
+    junction2
+        .enable_store_and_forward(StorePolicy::default())
+        .await
+        .unwrap();
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // junction3 is not on the mesh yet, so junction2 holds the message
+    let message = json!({"key": "held"});
+    junction1.send(message.clone(), &junction_id3).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(junction2.get_stored_package_count().await, 1);
+
+    let junction3 = SlowJunction::new(addr3, junction_id3)
+        .await
+        .expect("Failed to create junction3");
+    junction3.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(junction2.get_stored_package_count().await, 0);
+    let received = junction3
+        .recv()
+        .await
+        .expect("Held message was not delivered");
+    assert_eq!(received.json, message);
+    assert_eq!(received.addr, addr2);
+}
+
+#[tokio::test]
+async fn test_junction_held_package_expires_unreachable() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1139);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2247);
+
+    let junction_id2 = JunctionId::new("2");
+    let missing_id = JunctionId::new("missing");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+    junction2
+        .enable_store_and_forward(StorePolicy {
+            max_age: Duration::from_millis(200),
+            ..StorePolicy::default()
+        })
+        .await
+        .unwrap();
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    junction1.send(json!({"key": "held"}), &missing_id).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(junction2.get_stored_package_count().await, 1);
+
+    // The recipient never shows up, so junction2 drops the held copy and reports it
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert_eq!(junction2.get_stored_package_count().await, 0);
+    let error = junction1.recv_delivery_error().await.unwrap();
+    assert_eq!(error.kind, DeliveryErrorKind::DestinationUnreachable);
+    assert_eq!(error.reporter_id, junction_id2);
+    assert_eq!(error.recipient_id, missing_id);
+}
+
+#[tokio::test]
+async fn test_junction_holds_with_neighbours() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1128);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2237);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1129);
+    let addr4 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1141);
+    let addr5 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2249);
+
+    let junction_id2 = JunctionId::new("2");
+    let junction_id4 = JunctionId::new("4");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+    let junction3 = SlowJunction::new(addr3, JunctionId::new("3"))
+        .await
+        .expect("Failed to create junction3");
+    junction2
+        .enable_store_and_forward(StorePolicy::default())
+        .await
+        .unwrap();
+
+    junction1.join(addr2).await;
+    junction3.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // junction2 floods the message on to junction3, but has no route to junction4 so it
+    // holds a copy as well
+    let message = json!({"key": "held"});
+    junction1.send(message.clone(), &junction_id4).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(junction2.get_stored_package_count().await, 1);
+
+    // junction4 shows up behind junction5, and junction2 learns a route from its package
+    let junction4 = SlowJunction::new(addr4, junction_id4)
+        .await
+        .expect("Failed to create junction4");
+    let junction5 = SlowJunction::new(addr5, JunctionId::new("5"))
+        .await
+        .expect("Failed to create junction5");
+    junction4.join(addr5).await;
+    junction5.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    junction4.send(json!({"key": "hi"}), &junction_id2).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    assert_eq!(junction2.get_stored_package_count().await, 0);
+    let received = junction4
+        .recv()
+        .await
+        .expect("Held message was not delivered");
+    assert_eq!(received.json, message);
+    assert_eq!(received.addr, addr5);
+}
+
+#[tokio::test]
+async fn test_tcp_junction_store_and_forward() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9711);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9712);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9713);
+
+    let junction_id1 = JunctionId::new("junction1");
+    let junction_id3 = JunctionId::new("junction3");
+
+    let junction1 = SlowTcpJunction::new(addr1, junction_id1.clone());
+    let junction2 = SlowTcpJunction::new(addr2, JunctionId::new("junction2"));
+    junction2
+        .enable_store_and_forward(StorePolicy::default())
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    junction1
+        .clone()
+        .connect(addr2)
+        .await
+        .expect("Failed to connect junction1 to junction2");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // junction3 is not on the mesh yet, so junction2 holds the message
+    let package = SlowPackage::new_bin_payload(junction_id3.clone(), junction_id1, b"held");
+    junction1.send_package(&package).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(junction2.stored_package_count().await, 1);
+
+    // junction2 learns the way to junction3 from its first package
+    let junction3 = SlowTcpJunction::new(addr3, junction_id3.clone());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    junction3
+        .clone()
+        .connect(addr2)
+        .await
+        .expect("Failed to connect junction3 to junction2");
+    junction3
+        .send_package(&SlowPackage::new_howdy(junction_id3))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    assert_eq!(junction2.stored_package_count().await, 0);
+    let received = junction3
+        .receive_package()
+        .await
+        .expect("Held package was not delivered");
+    assert_eq!(received.payload, b"held");
+}
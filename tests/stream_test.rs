@@ -0,0 +1,199 @@
+use slow::junction::{JunctionId, SlowJunction};
+use slow::stream::{OutgoingSegment, SegmentKind, SlowStream, StreamMux, StreamSegment};
+use slow::tcp::tcp_junction::SlowTcpJunction;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{Mutex, mpsc};
+
+/// Carries the segments of one mux to another, dropping every `drop_every`th one.
+fn pump(
+    mut segments: mpsc::UnboundedReceiver<OutgoingSegment>,
+    sender_id: JunctionId,
+    to: Arc<Mutex<StreamMux>>,
+    opened: mpsc::UnboundedSender<SlowStream>,
+    drop_every: usize,
+) {
+    tokio::spawn(async move {
+        let mut count = 0;
+        while let Some((_, segment)) = segments.recv().await {
+            count += 1;
+            if count % drop_every != 0
+                && let Some(stream) = to.lock().await.handle(&sender_id, segment)
+            {
+                let _ = opened.send(stream);
+            }
+        }
+    });
+}
+
+/// Drives the retransmissions of a mux.
+fn ticker(mux: Arc<Mutex<StreamMux>>) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            mux.lock().await.tick();
+        }
+    });
+}
+
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+#[test]
+fn test_stream_segment_pack_unpack() {
+    let segment = StreamSegment {
+        kind: SegmentKind::Data,
+        stream_id: 7,
+        initiator: true,
+        sequence: 3,
+        ack: 5,
+        window: 16,
+        data: b"hello".to_vec(),
+    };
+
+    assert_eq!(StreamSegment::unpack(&segment.pack()), Some(segment));
+    assert_eq!(StreamSegment::unpack(&[1, 0, 0]), None);
+}
+
+#[tokio::test]
+async fn test_stream_over_lossy_link() {
+    let id_a = JunctionId::new("a");
+    let id_b = JunctionId::new("b");
+
+    let (out_a, segments_a) = mpsc::unbounded_channel();
+    let (out_b, segments_b) = mpsc::unbounded_channel();
+    let (incoming_a, _accepted_a) = mpsc::unbounded_channel();
+    let (incoming_b, mut accepted_b) = mpsc::unbounded_channel();
+
+    let mux_a = Arc::new(Mutex::new(StreamMux::new(out_a)));
+    let mux_b = Arc::new(Mutex::new(StreamMux::new(out_b)));
+
+    // Every fifth segment is lost in each direction
+    pump(segments_a, id_a.clone(), mux_b.clone(), incoming_b, 5);
+    pump(segments_b, id_b.clone(), mux_a.clone(), incoming_a, 5);
+    ticker(mux_a.clone());
+    ticker(mux_b.clone());
+
+    let mut stream_a = mux_a.lock().await.open(&id_b, "bulk");
+    let data = payload(100_000);
+    let expected = data.clone();
+
+    let writer = tokio::spawn(async move {
+        stream_a.write_all(&data).await.unwrap();
+        stream_a.shutdown().await.unwrap();
+    });
+
+    let mut stream_b = tokio::time::timeout(Duration::from_secs(5), accepted_b.recv())
+        .await
+        .expect("No stream was opened")
+        .unwrap();
+    assert_eq!(stream_b.peer_id(), id_a);
+    assert_eq!(stream_b.service(), "bulk");
+
+    let mut received = Vec::new();
+    tokio::time::timeout(Duration::from_secs(30), stream_b.read_to_end(&mut received))
+        .await
+        .expect("Stream did not finish")
+        .unwrap();
+
+    assert_eq!(received, expected);
+    writer.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_junction_stream() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1120);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2230);
+
+    let junction_id2 = JunctionId::new("2");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // junction2 echoes everything back
+    tokio::spawn(async move {
+        let mut stream = junction2.accept_stream().await.unwrap();
+        let mut buffer = vec![0u8; 4096];
+        loop {
+            let count = stream.read(&mut buffer).await.unwrap();
+            if count == 0 {
+                break;
+            }
+            stream.write_all(&buffer[..count]).await.unwrap();
+        }
+        stream.shutdown().await.unwrap();
+    });
+
+    let stream = junction1.open_stream(&junction_id2).await;
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let data = payload(20_000);
+    let expected = data.clone();
+
+    tokio::spawn(async move {
+        writer.write_all(&data).await.unwrap();
+        writer.shutdown().await.unwrap();
+    });
+
+    let mut received = Vec::new();
+    tokio::time::timeout(Duration::from_secs(10), reader.read_to_end(&mut received))
+        .await
+        .expect("Stream did not finish")
+        .unwrap();
+    assert_eq!(received, expected);
+}
+
+#[tokio::test]
+async fn test_tcp_junction_stream() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9705);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9706);
+
+    let junction_id1 = JunctionId::new("junction1");
+    let junction_id2 = JunctionId::new("junction2");
+
+    let junction1 = SlowTcpJunction::new(addr1, junction_id1.clone());
+    let junction2 = SlowTcpJunction::new(addr2, junction_id2.clone());
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    junction1
+        .clone()
+        .connect(addr2)
+        .await
+        .expect("Failed to connect junction1 to junction2");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // The writer blocks once the reader's window is full, so it runs alongside the reader
+    let mut stream = junction1.open_stream(&junction_id2).await;
+    let data = payload(50_000);
+    let expected = data.clone();
+    let writer = tokio::spawn(async move {
+        stream.write_all(&data).await.unwrap();
+        stream.shutdown().await.unwrap();
+    });
+
+    let mut accepted = tokio::time::timeout(Duration::from_secs(5), junction2.accept_stream())
+        .await
+        .expect("No stream was opened")
+        .unwrap();
+    assert_eq!(accepted.peer_id(), junction_id1);
+
+    let mut received = Vec::new();
+    tokio::time::timeout(Duration::from_secs(10), accepted.read_to_end(&mut received))
+        .await
+        .expect("Stream did not finish")
+        .unwrap();
+    assert_eq!(received, expected);
+    writer.await.unwrap();
+
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}
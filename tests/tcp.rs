@@ -1,6 +1,8 @@
 #[cfg(test)]
 mod tcp {
+    mod test_tcp_frame;
     mod test_tcp_junction;
     mod test_tcp_link;
+    mod test_tcp_peer;
     mod test_tcp_stream;
 }
@@ -0,0 +1,173 @@
+use slow::junction::JunctionId;
+use slow::tcp::tcp_frame::{FrameFlags, FrameType, SlowTcpFrame, crc32};
+use slow::tcp::tcp_handshake::{HELLO_MESSAGE, HELLO_RESPONSE, LinkIdentity};
+use slow::tcp::tcp_link::SlowTcpLink;
+use slow::tcp::tcp_listener::SlowTcpListener;
+use slow::tcp::tcp_stream::SlowTcpStream;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use tokio::task;
+
+/// Connects two raw streams over the loopback interface.
+async fn stream_pair() -> (SlowTcpStream, SlowTcpStream) {
+    let addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
+    let listener = SlowTcpListener::new(addr).await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    let accept_handle = task::spawn(async move { listener.accept().await.unwrap() });
+    let client = SlowTcpStream::connect(server_addr).await.unwrap();
+    let server = accept_handle.await.unwrap();
+    (client, server)
+}
+
+#[test]
+fn test_frame_type_conversion() {
+    for frame_type in [
+        FrameType::Handshake,
+        FrameType::Package,
+        FrameType::Heartbeat,
+        FrameType::Close,
+        FrameType::Unknown(200),
+    ] {
+        assert_eq!(FrameType::from_u8(frame_type.to_u8()), frame_type);
+    }
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[tokio::test]
+async fn test_tcp_frame_types() {
+    let (client, server) = stream_pair().await;
+    let unknown_flags = FrameFlags::from_bits(0x80);
+
+    let frames = [
+        (FrameType::Handshake, FrameFlags::NONE, b"hello".to_vec()),
+        (FrameType::Package, FrameFlags::CHECKSUM, vec![0xA5; 4096]),
+        (FrameType::Heartbeat, FrameFlags::NONE, Vec::new()),
+        (
+            FrameType::Unknown(200),
+            unknown_flags.union(FrameFlags::CHECKSUM),
+            b"from the future".to_vec(),
+        ),
+        (FrameType::Close, FrameFlags::NONE, Vec::new()),
+    ];
+    for (frame_type, flags, data) in &frames {
+        SlowTcpFrame::send(*frame_type, *flags, data, &client)
+            .await
+            .expect("Failed to send frame");
+    }
+
+    let mut buffer = vec![0u8; 8192];
+    for (frame_type, flags, data) in &frames {
+        let header = SlowTcpFrame::receive(&mut buffer, &server)
+            .await
+            .expect("Failed to receive frame");
+        assert_eq!(header.frame_type, *frame_type);
+        assert_eq!(header.flags, *flags);
+        assert_eq!(&buffer[..header.size], data.as_slice());
+    }
+}
+
+#[tokio::test]
+async fn test_tcp_frame_checksum_mismatch() {
+    let (client, server) = stream_pair().await;
+
+    // A package frame whose checksum does not match its data
+    let data = b"corrupted";
+    let mut frame = Vec::new();
+    frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    frame.push(FrameType::Package.to_u8());
+    frame.push(FrameFlags::CHECKSUM.bits());
+    frame.extend_from_slice(data);
+    frame.extend_from_slice(&(crc32(data) ^ 1).to_be_bytes());
+    frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    client.write(&frame).await.unwrap();
+
+    let mut buffer = [0u8; 64];
+    let error = SlowTcpFrame::receive(&mut buffer, &server)
+        .await
+        .expect_err("Accepted a frame with a bad checksum");
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+/// Tests that a link only hands packages to its owner.
+///
+/// This test verifies:
+/// 1. Frames of unknown types are skipped, during and after the handshake, even ones
+///    larger than a handshake
+/// 2. Heartbeats are consumed and checksummed packages are delivered
+/// 3. A close frame ends the link
+#[tokio::test]
+async fn test_tcp_link_skips_unknown_frames() {
+    let addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
+    let listener = SlowTcpListener::new(addr).await.unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    let accept_handle = task::spawn(async move {
+        let stream = listener.accept().await.unwrap();
+        let identity = LinkIdentity::new(JunctionId::new("listener"), 0);
+        SlowTcpLink::accept(stream, &identity).await
+    });
+
+    // Handshake by hand from a junction that sends a frame type the link does not know
+    let client = SlowTcpStream::connect(server_addr).await.unwrap();
+    let identity = LinkIdentity::new(JunctionId::new("future"), 0);
+    let none = FrameFlags::NONE;
+    SlowTcpFrame::send(FrameType::Unknown(99), none, b"early", &client)
+        .await
+        .unwrap();
+    // Larger than any handshake, so it is only skipped if the link does not buffer it
+    let large = vec![7u8; 256 * 1024];
+    SlowTcpFrame::send(
+        FrameType::Unknown(98),
+        FrameFlags::CHECKSUM,
+        &large,
+        &client,
+    )
+    .await
+    .unwrap();
+    SlowTcpFrame::send(
+        FrameType::Handshake,
+        none,
+        &identity.pack(HELLO_MESSAGE),
+        &client,
+    )
+    .await
+    .unwrap();
+    let link = accept_handle
+        .await
+        .unwrap()
+        .expect("Link refused the handshake");
+    assert_eq!(link.peer().junction_id, JunctionId::new("future"));
+
+    let mut buffer = vec![0u8; 1024];
+    let header = SlowTcpFrame::receive(&mut buffer, &client).await.unwrap();
+    assert_eq!(header.frame_type, FrameType::Handshake);
+    assert!(LinkIdentity::unpack(HELLO_RESPONSE, &buffer[..header.size]).is_some());
+
+    SlowTcpFrame::send(FrameType::Unknown(99), none, b"skip me", &client)
+        .await
+        .unwrap();
+    SlowTcpFrame::send(FrameType::Heartbeat, none, &[], &client)
+        .await
+        .unwrap();
+    SlowTcpFrame::send(
+        FrameType::Package,
+        FrameFlags::CHECKSUM,
+        b"package",
+        &client,
+    )
+    .await
+    .unwrap();
+    SlowTcpFrame::send(FrameType::Close, none, &[], &client)
+        .await
+        .unwrap();
+
+    let size = link
+        .receive(&mut buffer)
+        .await
+        .expect("No package received");
+    assert_eq!(&buffer[..size], b"package");
+    let error = link
+        .receive(&mut buffer)
+        .await
+        .expect_err("Link outlived the close frame");
+    assert_eq!(error.kind(), std::io::ErrorKind::ConnectionReset);
+}
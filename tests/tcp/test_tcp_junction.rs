@@ -1,8 +1,13 @@
+use serde_json::json;
+use slow::delivery::DeliveryErrorKind;
 use slow::junction::JunctionId;
 use slow::package::SlowPackage;
+use slow::rpc::RpcError;
+use slow::tcp::tcp_handshake::LinkIdentity;
 use slow::tcp::tcp_junction::SlowTcpJunction;
+use slow::tcp::tcp_link::{HeartbeatPolicy, SlowTcpLink};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::time;
 
 /// Tests a basic TCP junction connection between two nodes.
@@ -463,7 +468,7 @@ async fn test_tcp_junction_hello() {
 
     // Each junction will connect to at least one other junction
     // but we'll avoid self-connections
-    for i in 0..NUM_JUNCTIONS {
+    for (i, junction) in junctions.iter().enumerate() {
         // Choose a random target junction that is not the current junction
         let mut targets: Vec<usize> = (0..NUM_JUNCTIONS).filter(|&j| j != i).collect();
         targets.shuffle(&mut rng);
@@ -471,11 +476,10 @@ async fn test_tcp_junction_hello() {
         // Connect to a random number of junctions (1 to 3)
         let num_connections = rng.gen_range(1..=3).min(targets.len());
 
-        for j in 0..num_connections {
-            let target_idx = targets[j];
+        for &target_idx in targets.iter().take(num_connections) {
             let target_addr = addresses[target_idx];
 
-            junctions[i]
+            junction
                 .clone()
                 .connect(target_addr)
                 .await
@@ -493,8 +497,8 @@ async fn test_tcp_junction_hello() {
     time::sleep(Duration::from_millis(300)).await;
 
     // Print connection stats for each junction
-    for i in 0..NUM_JUNCTIONS {
-        let link_count = junctions[i].link_count().await;
+    for (i, junction) in junctions.iter().enumerate() {
+        let link_count = junction.link_count().await;
         println!("Junction{} has {} links", i + 1, link_count);
         assert!(
             link_count > 0,
@@ -535,13 +539,13 @@ async fn test_tcp_junction_hello() {
     time::sleep(Duration::from_millis(500)).await;
 
     // Check that all junctions received the package
-    for i in 0..NUM_JUNCTIONS {
+    for (i, junction) in junctions.iter().enumerate() {
         if i == src_idx {
             // Skip the source junction
             continue;
         }
 
-        let received_count = junctions[i].received_package_count();
+        let received_count = junction.received_package_count();
         println!("Junction{} received {} packages", i + 1, received_count);
 
         // Assert that each junction has received exactly 1 package
@@ -553,3 +557,691 @@ async fn test_tcp_junction_hello() {
         );
     }
 }
+
+/// Tests tracing the path through a linear network of three TCP junctions.
+///
+/// This test verifies:
+/// 1. Routes learned from a howdy package are used by the traceroute
+/// 2. Every junction on the path records itself in order
+/// 3. The traced junction echoes the record back to the origin
+#[tokio::test]
+async fn test_tcp_junction_traceroute() {
+    // Create addresses for the three junctions
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9401);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9402);
+    let addr3 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9403);
+
+    // Create IDs for the three junctions
+    let junction_id1 = JunctionId::new("junction1");
+    let junction_id2 = JunctionId::new("junction2");
+    let junction_id3 = JunctionId::new("junction3");
+
+    // Create the junction instances
+    let junction1 = SlowTcpJunction::new(addr1, junction_id1.clone());
+    let junction2 = SlowTcpJunction::new(addr2, junction_id2.clone());
+    let junction3 = SlowTcpJunction::new(addr3, junction_id3.clone());
+
+    // Allow some time for junctions to initialize and start listening
+    time::sleep(Duration::from_millis(100)).await;
+
+    // Connect the junctions in a line: 1 -> 2 -> 3
+    junction1
+        .clone()
+        .connect(addr2)
+        .await
+        .expect("Failed to connect junction1 to junction2");
+
+    junction2
+        .clone()
+        .connect(addr3)
+        .await
+        .expect("Failed to connect junction2 to junction3");
+
+    // Allow some time for all connections to be established
+    time::sleep(Duration::from_millis(200)).await;
+
+    // Let junction3 announce itself so the other junctions learn a route to it
+    let howdy_package = SlowPackage::new_howdy(junction_id3.clone());
+    junction3
+        .send_package(&howdy_package)
+        .await
+        .expect("Failed to send howdy package from junction3");
+
+    time::sleep(Duration::from_millis(200)).await;
+
+    // Trace the path from junction1 to junction3
+    let hops = junction1
+        .traceroute(&junction_id3)
+        .await
+        .expect("Traceroute got no reply");
+
+    let path: Vec<JunctionId> = hops.iter().map(|hop| hop.junction_id.clone()).collect();
+    assert_eq!(
+        path,
+        vec![junction_id1, junction_id2, junction_id3],
+        "Traceroute should record every junction on the path"
+    );
+
+    // Traceroute packages are handled internally and never queued for the application
+    assert_eq!(junction1.waiting_package_count().await, 0);
+    assert_eq!(junction3.waiting_package_count().await, 0);
+
+    // Close all junctions
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+    junction3.close().await.expect("Failed to close junction3");
+}
+
+/// Tests reporting a package for an unknown junction back to its sender.
+///
+/// This test verifies:
+/// 1. A junction with no link towards the recipient drops the package
+/// 2. The drop is reported to the sender as destination unreachable
+#[tokio::test]
+async fn test_tcp_junction_destination_unreachable() {
+    // Create addresses for the two junctions
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9501);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9502);
+
+    // Create IDs for the two junctions
+    let junction_id1 = JunctionId::new("junction1");
+    let junction_id2 = JunctionId::new("junction2");
+    let missing_id = JunctionId::new("missing");
+
+    // Create the junction instances
+    let junction1 = SlowTcpJunction::new(addr1, junction_id1.clone());
+    let junction2 = SlowTcpJunction::new(addr2, junction_id2.clone());
+
+    // Allow some time for junctions to initialize and start listening
+    time::sleep(Duration::from_millis(100)).await;
+
+    junction1
+        .clone()
+        .connect(addr2)
+        .await
+        .expect("Failed to connect junction1 to junction2");
+
+    time::sleep(Duration::from_millis(100)).await;
+
+    // Send a package to a junction that does not exist
+    let package = SlowPackage::new_bin_payload(missing_id.clone(), junction_id1.clone(), b"lost");
+    junction1
+        .send_package(&package)
+        .await
+        .expect("Failed to send package from junction1");
+
+    time::sleep(Duration::from_millis(200)).await;
+
+    let error = junction1
+        .receive_delivery_error()
+        .await
+        .expect("Junction1 should have received a delivery error");
+    assert_eq!(error.kind, DeliveryErrorKind::DestinationUnreachable);
+    assert_eq!(error.reporter_id, junction_id2);
+    assert_eq!(error.recipient_id, missing_id);
+    assert_eq!(junction1.waiting_package_count().await, 0);
+
+    // Close all junctions
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}
+
+/// Tests an acknowledged send between two TCP junctions.
+///
+/// This test verifies:
+/// 1. The recipient acknowledges a package that asks for it
+/// 2. The delivery handle resolves once the ack arrives
+#[tokio::test]
+async fn test_tcp_junction_send_acked() {
+    // Create addresses for the two junctions
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9601);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9602);
+
+    // Create IDs for the two junctions
+    let junction_id1 = JunctionId::new("junction1");
+    let junction_id2 = JunctionId::new("junction2");
+
+    // Create the junction instances
+    let junction1 = SlowTcpJunction::new(addr1, junction_id1.clone());
+    let junction2 = SlowTcpJunction::new(addr2, junction_id2.clone());
+
+    // Allow some time for junctions to initialize and start listening
+    time::sleep(Duration::from_millis(100)).await;
+
+    junction1
+        .clone()
+        .connect(addr2)
+        .await
+        .expect("Failed to connect junction1 to junction2");
+
+    time::sleep(Duration::from_millis(100)).await;
+
+    let package =
+        SlowPackage::new_bin_payload(junction_id2.clone(), junction_id1.clone(), b"ack me");
+    let handle = junction1
+        .send_package_acked(&package)
+        .await
+        .expect("Failed to send package from junction1");
+
+    assert_eq!(handle.wait().await, Ok(()));
+    assert_eq!(junction2.waiting_package_count().await, 1);
+
+    // The ack is consumed by junction1 rather than queued
+    assert_eq!(junction1.waiting_package_count().await, 0);
+
+    // Close all junctions
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}
+
+/// Tests that a resent package is delivered once but acknowledged every time.
+///
+/// This test verifies:
+/// 1. A resent copy of a delivered package is not delivered again
+/// 2. Both transmissions are acked with the package ID of the first
+#[tokio::test]
+async fn test_tcp_junction_resent_package() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+    let junction = SlowTcpJunction::new(any_port, JunctionId::new("junction"));
+
+    let identity = LinkIdentity::new(JunctionId::new("sender"), 0);
+    let sender = SlowTcpLink::connect(junction.local_addr(), &identity)
+        .await
+        .expect("Failed to connect the sender");
+
+    let mut package = SlowPackage::new_bin_payload(
+        JunctionId::new("junction"),
+        JunctionId::new("sender"),
+        b"once",
+    );
+    package.request_ack();
+    package.set_package_id(1);
+    sender.send_message(&package.pack(1)).await.unwrap();
+
+    // The ack for the first transmission is lost, so the sender resends it
+    package.set_retransmit(2);
+    sender.send_message(&package.pack(2)).await.unwrap();
+
+    let mut acked = Vec::new();
+    while acked.len() < 2 {
+        let data = time::timeout(Duration::from_secs(1), sender.receive_message())
+            .await
+            .expect("Timed out waiting for acks")
+            .expect("Failed to receive");
+        let received = SlowPackage::unpack(&data).expect("Failed to unpack");
+        if let Some(package_id) = received.acked_package_id() {
+            acked.push(package_id);
+        }
+    }
+    assert_eq!(acked, vec![1, 1]);
+    assert_eq!(junction.waiting_package_count().await, 1);
+    assert_eq!(junction.rejected_package_count(), 1);
+
+    junction.close().await.expect("Failed to close junction");
+}
+
+/// Tests RPC calls between two TCP junctions.
+///
+/// This test verifies:
+/// 1. A call to a registered method returns the handler's result
+/// 2. A call to an unknown method returns `MethodNotFound`
+/// 3. A failing handler returns `HandlerFailed`
+#[tokio::test]
+async fn test_tcp_junction_rpc_call() {
+    // Create addresses for the two junctions
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9701);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9702);
+
+    // Create IDs for the two junctions
+    let junction_id1 = JunctionId::new("junction1");
+    let junction_id2 = JunctionId::new("junction2");
+
+    // Create the junction instances
+    let junction1 = SlowTcpJunction::new(addr1, junction_id1.clone());
+    let junction2 = SlowTcpJunction::new(addr2, junction_id2.clone());
+
+    junction2.register_rpc_handler("echo", Ok).await;
+    junction2
+        .register_rpc_handler("fail", |_| Err("broken".to_string()))
+        .await;
+
+    // Allow some time for junctions to initialize and start listening
+    time::sleep(Duration::from_millis(100)).await;
+
+    junction1
+        .clone()
+        .connect(addr2)
+        .await
+        .expect("Failed to connect junction1 to junction2");
+
+    time::sleep(Duration::from_millis(100)).await;
+
+    let timeout = Duration::from_secs(1);
+    let result = junction1
+        .call(&junction_id2, "echo", json!({"hello": "world"}), timeout)
+        .await;
+    assert_eq!(result, Ok(json!({"hello": "world"})));
+
+    let result = junction1
+        .call(&junction_id2, "missing", json!(null), timeout)
+        .await;
+    assert_eq!(result, Err(RpcError::MethodNotFound("missing".to_string())));
+
+    let result = junction1
+        .call(&junction_id2, "fail", json!(null), timeout)
+        .await;
+    assert_eq!(result, Err(RpcError::HandlerFailed("broken".to_string())));
+
+    // With no link to send it on, the call fails without waiting for the timeout
+    let unlinked = SlowTcpJunction::new(
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0),
+        JunctionId::new("unlinked"),
+    );
+    let started = Instant::now();
+    let result = unlinked
+        .call(&junction_id2, "echo", json!(null), timeout)
+        .await;
+    assert!(matches!(result, Err(RpcError::SendFailed(_))));
+    assert!(started.elapsed() < timeout);
+
+    // RPC packages are consumed by the junctions rather than queued
+    assert_eq!(junction1.waiting_package_count().await, 0);
+    assert_eq!(junction2.waiting_package_count().await, 0);
+
+    // Close all junctions
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}
+
+/// Tests ordered sends between two TCP junctions.
+///
+/// This test verifies:
+/// 1. Ordered packages carry their sequence across the link
+/// 2. The recipient delivers them in the order they were sent
+#[tokio::test]
+async fn test_tcp_junction_send_ordered() {
+    // Create addresses for the two junctions
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9703);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9704);
+
+    // Create IDs for the two junctions
+    let junction_id1 = JunctionId::new("junction1");
+    let junction_id2 = JunctionId::new("junction2");
+
+    // Create the junction instances
+    let junction1 = SlowTcpJunction::new(addr1, junction_id1.clone());
+    let junction2 = SlowTcpJunction::new(addr2, junction_id2.clone());
+
+    // Allow some time for junctions to initialize and start listening
+    time::sleep(Duration::from_millis(100)).await;
+
+    junction1
+        .clone()
+        .connect(addr2)
+        .await
+        .expect("Failed to connect junction1 to junction2");
+
+    time::sleep(Duration::from_millis(100)).await;
+
+    for i in 0..5u8 {
+        let package =
+            SlowPackage::new_bin_payload(junction_id2.clone(), junction_id1.clone(), &[i]);
+        junction1
+            .send_package_ordered(&package)
+            .await
+            .expect("Failed to send package from junction1");
+    }
+
+    time::sleep(Duration::from_millis(100)).await;
+
+    for i in 0..5u8 {
+        let package = junction2
+            .receive_package()
+            .await
+            .expect("Missing ordered package");
+        assert_eq!(package.sequence(), Some(i as u32 + 1));
+        assert_eq!(package.payload, vec![i]);
+    }
+
+    // Close all junctions
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}
+
+/// Tests message expiry between two TCP junctions.
+///
+/// This test verifies:
+/// 1. A package whose deadline has passed is not sent
+/// 2. A package that expires while waiting in the receive queue is not delivered
+#[tokio::test]
+async fn test_tcp_junction_message_expiry() {
+    // Create addresses for the two junctions
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9714);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9715);
+
+    // Create IDs for the two junctions
+    let junction_id1 = JunctionId::new("junction1");
+    let junction_id2 = JunctionId::new("junction2");
+
+    // Create the junction instances
+    let junction1 = SlowTcpJunction::new(addr1, junction_id1.clone());
+    let junction2 = SlowTcpJunction::new(addr2, junction_id2.clone());
+
+    // Allow some time for junctions to initialize and start listening
+    time::sleep(Duration::from_millis(100)).await;
+
+    junction1
+        .clone()
+        .connect(addr2)
+        .await
+        .expect("Failed to connect junction1 to junction2");
+
+    time::sleep(Duration::from_millis(100)).await;
+
+    let mut stale = SlowPackage::new_bin_payload(junction_id2.clone(), junction_id1.clone(), b"1");
+    stale.set_expires_at(SystemTime::now() - Duration::from_secs(1));
+    assert!(junction1.send_package(&stale).await.is_err());
+    assert_eq!(junction1.expired_package_count(), 1);
+
+    let mut short = SlowPackage::new_bin_payload(junction_id2.clone(), junction_id1.clone(), b"2");
+    short.set_max_age(Duration::from_millis(150));
+    junction1
+        .send_package(&short)
+        .await
+        .expect("Failed to send package from junction1");
+
+    time::sleep(Duration::from_millis(300)).await;
+
+    assert_eq!(junction2.waiting_package_count().await, 1);
+    assert!(junction2.receive_package().await.is_none());
+    assert_eq!(junction2.expired_package_count(), 1);
+
+    // Close all junctions
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}
+
+/// Tests a TCP junction listening on an ephemeral port.
+///
+/// This test verifies:
+/// 1. Binding port 0 reports the port actually picked
+/// 2. A connection that never finishes its handshake does not hold up other connections
+/// 3. The listener keeps accepting after the first connection
+#[tokio::test]
+async fn test_tcp_junction_ephemeral_listener() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    let junction_id1 = JunctionId::new("junction1");
+    let junction_id2 = JunctionId::new("junction2");
+    let junction_id3 = JunctionId::new("junction3");
+
+    let junction1 = SlowTcpJunction::new(any_port, junction_id1.clone());
+    let junction2 = SlowTcpJunction::new(any_port, junction_id2.clone());
+    let junction3 = SlowTcpJunction::new(any_port, junction_id3.clone());
+
+    let addr1 = junction1.local_addr();
+    assert_ne!(addr1.port(), 0);
+    assert_ne!(addr1.port(), junction2.local_addr().port());
+
+    // A peer that connects but never says hello
+    let _silent = tokio::net::TcpStream::connect(addr1)
+        .await
+        .expect("Failed to open silent connection");
+
+    time::timeout(Duration::from_secs(2), junction2.clone().connect(addr1))
+        .await
+        .expect("Handshake waited on the silent connection")
+        .expect("Failed to connect junction2 to junction1");
+    time::timeout(Duration::from_secs(2), junction3.clone().connect(addr1))
+        .await
+        .expect("Handshake waited on the silent connection")
+        .expect("Failed to connect junction3 to junction1");
+
+    time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(junction1.link_count().await, 2);
+
+    let package = SlowPackage::new_bin_payload(junction_id1, junction_id3, b"hello");
+    junction3
+        .send_package(&package)
+        .await
+        .expect("Failed to send package from junction3");
+    time::sleep(Duration::from_millis(100)).await;
+
+    let received = junction1
+        .receive_package()
+        .await
+        .expect("Package was not received");
+    assert_eq!(received.payload, b"hello");
+
+    // Close all junctions
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+    junction3.close().await.expect("Failed to close junction3");
+}
+
+/// Tests the identities exchanged when TCP junctions link up.
+///
+/// This test verifies:
+/// 1. Both ends learn the junction ID of the other from the handshake
+/// 2. The junction map is filled in with each peer's listening address
+#[tokio::test]
+async fn test_tcp_junction_identity() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    let junction_id1 = JunctionId::new("junction1");
+    let junction_id2 = JunctionId::new("junction2");
+
+    let junction1 = SlowTcpJunction::new(any_port, junction_id1.clone());
+    let junction2 = SlowTcpJunction::new(any_port, junction_id2.clone());
+
+    junction1
+        .clone()
+        .connect(junction2.local_addr())
+        .await
+        .expect("Failed to connect junction1 to junction2");
+    time::sleep(Duration::from_millis(100)).await;
+
+    let peers1 = junction1.peers().await;
+    assert_eq!(peers1.len(), 1);
+    assert_eq!(peers1[0].junction_id, junction_id2);
+    let identity2 = junction2.identity().await;
+    assert_eq!(peers1[0].offer, identity2.offer);
+    assert_eq!(peers1[0].listen_port, identity2.listen_port);
+
+    let peers2 = junction2.peers().await;
+    assert_eq!(peers2.len(), 1);
+    assert_eq!(peers2[0].junction_id, junction_id1);
+
+    // The accepting side maps the dialer to its listening address, not the dialing port
+    assert_eq!(
+        junction1.get_junction_addr(&junction_id2).await,
+        Some(junction2.local_addr())
+    );
+    assert_eq!(
+        junction2.get_junction_addr(&junction_id1).await,
+        Some(junction1.local_addr())
+    );
+
+    // Close all junctions
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}
+
+/// Tests heartbeats and the idle timeout on TCP links.
+///
+/// This test verifies:
+/// 1. Heartbeats are consumed by the link rather than handed over as frames
+/// 2. A link whose peer stops sending anything is closed and removed
+/// 3. Heartbeats keep a quiet link between two junctions open past the idle timeout
+#[tokio::test]
+async fn test_tcp_junction_heartbeat() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+    let policy = HeartbeatPolicy {
+        interval: Duration::from_millis(50),
+        idle_timeout: Duration::from_millis(500),
+    };
+
+    let junction1 = SlowTcpJunction::new(any_port, JunctionId::new("junction1"));
+    let junction2 = SlowTcpJunction::new(any_port, JunctionId::new("junction2"));
+
+    // Heartbeats are opt-in
+    assert!(junction1.heartbeat_policy().await.is_none());
+    junction1.set_heartbeat_policy(Some(policy)).await;
+    junction2.set_heartbeat_policy(Some(policy)).await;
+
+    junction1
+        .clone()
+        .connect(junction2.local_addr())
+        .await
+        .expect("Failed to connect junction1 to junction2");
+
+    // A peer that finishes the handshake and then goes silent
+    let identity = LinkIdentity::new(JunctionId::new("frozen"), 0);
+    let frozen = SlowTcpLink::connect(junction1.local_addr(), &identity)
+        .await
+        .expect("Failed to connect the frozen peer");
+
+    // The frozen peer only hears heartbeats until junction1 gives up on it and closes the
+    // link, so its first receive ends with the close rather than with a frame
+    let mut buffer = [0u8; 64];
+    let received = time::timeout(Duration::from_secs(5), frozen.receive(&mut buffer))
+        .await
+        .expect("The idle link was never closed");
+    let error = received.expect_err("A heartbeat was handed over as a frame");
+    assert_eq!(error.kind(), std::io::ErrorKind::ConnectionReset);
+
+    // The link between the junctions is older than the idle timeout by now
+    time::timeout(Duration::from_secs(5), async {
+        while junction1.link_count().await != 1 {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("The idle link was never removed");
+    assert_eq!(junction2.link_count().await, 1);
+    assert_eq!(
+        junction1.peers().await[0].junction_id,
+        JunctionId::new("junction2")
+    );
+
+    // Close all junctions
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}
+
+/// Tests waiting for packages on a TCP junction.
+///
+/// This test verifies:
+/// 1. `recv_timeout` gives up when nothing arrives
+/// 2. A waiting `recv` wakes as soon as a package is delivered
+/// 3. Packages queued before the call are returned at once, in order
+#[tokio::test]
+async fn test_tcp_junction_recv() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    let junction1 = SlowTcpJunction::new(any_port, JunctionId::new("junction1"));
+    let junction2 = SlowTcpJunction::new(any_port, JunctionId::new("junction2"));
+    junction1
+        .clone()
+        .connect(junction2.local_addr())
+        .await
+        .expect("Failed to connect junction1 to junction2");
+    time::sleep(Duration::from_millis(100)).await;
+
+    assert!(
+        junction2
+            .recv_timeout(Duration::from_millis(100))
+            .await
+            .is_none()
+    );
+
+    let receiver = junction2.clone();
+    let waiting = tokio::spawn(async move { receiver.recv().await });
+    time::sleep(Duration::from_millis(50)).await;
+    assert!(!waiting.is_finished());
+
+    let package = |message: &str| {
+        SlowPackage::new_json_payload(
+            JunctionId::new("junction2"),
+            JunctionId::new("junction1"),
+            &json!({ "message": message }),
+        )
+    };
+    junction1
+        .send_package(&package("wake up"))
+        .await
+        .expect("Failed to send package");
+    let received = time::timeout(Duration::from_secs(1), waiting)
+        .await
+        .expect("recv was not woken")
+        .unwrap();
+    assert_eq!(received.json_payload().unwrap()["message"], "wake up");
+
+    for message in ["first", "second"] {
+        junction1
+            .send_package(&package(message))
+            .await
+            .expect("Failed to send package");
+    }
+    time::sleep(Duration::from_millis(100)).await;
+    for message in ["first", "second"] {
+        let received = junction2
+            .recv_timeout(Duration::from_millis(100))
+            .await
+            .expect("Queued package was not returned");
+        assert_eq!(received.json_payload().unwrap()["message"], message);
+    }
+    assert_eq!(junction2.waiting_package_count().await, 0);
+
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}
+
+/// Tests that packages sent at the same time are all delivered.
+///
+/// Each send reserves its own package ID, so the recipient never mistakes one
+/// package for a duplicate of another.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_tcp_junction_concurrent_sends() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    let junction1 = SlowTcpJunction::new(any_port, JunctionId::new("junction1"));
+    let junction2 = SlowTcpJunction::new(any_port, JunctionId::new("junction2"));
+    junction1
+        .clone()
+        .connect(junction2.local_addr())
+        .await
+        .expect("Failed to connect junction1 to junction2");
+    time::sleep(Duration::from_millis(100)).await;
+
+    let senders: Vec<_> = (0..50)
+        .map(|i| {
+            let junction1 = junction1.clone();
+            tokio::spawn(async move {
+                let package = SlowPackage::new_json_payload(
+                    JunctionId::new("junction2"),
+                    JunctionId::new("junction1"),
+                    &json!({ "index": i }),
+                );
+                junction1.send_package(&package).await
+            })
+        })
+        .collect();
+    for sender in senders {
+        sender.await.unwrap().expect("Failed to send package");
+    }
+
+    let mut received = Vec::new();
+    while let Some(package) = junction2.recv_timeout(Duration::from_millis(500)).await {
+        received.push(package.json_payload().unwrap()["index"].as_u64().unwrap());
+        if received.len() == 50 {
+            break;
+        }
+    }
+    received.sort();
+    assert_eq!(received, (0..50).collect::<Vec<u64>>());
+    assert_eq!(junction2.rejected_package_count(), 0);
+
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}
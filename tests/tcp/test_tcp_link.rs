@@ -1,6 +1,7 @@
+use slow::junction::JunctionId;
+use slow::tcp::tcp_handshake::LinkIdentity;
 use slow::tcp::tcp_link::SlowTcpLink;
 use std::net::SocketAddr;
-use tokio;
 use tokio::time::{Duration, sleep};
 
 #[tokio::test]
@@ -9,11 +10,15 @@ async fn test_tcp_link() {
     let addr = "127.0.0.1:12345".parse::<SocketAddr>().unwrap();
 
     // Start the SlowTcpLink listener in a task
-    let listener_handle = tokio::spawn(async move { SlowTcpLink::listen(addr).await });
+    let listener_identity = LinkIdentity::new(JunctionId::new("listener"), addr.port());
+    let connector_identity = LinkIdentity::new(JunctionId::new("connector"), 0);
+    let listener_handle =
+        tokio::spawn(async move { SlowTcpLink::listen(addr, &listener_identity).await });
     sleep(Duration::from_millis(100)).await;
 
     // Connect to the listener
-    let connector_handle = tokio::spawn(async move { SlowTcpLink::connect(addr).await });
+    let connector_handle =
+        tokio::spawn(async move { SlowTcpLink::connect(addr, &connector_identity).await });
 
     // Wait for both operations to complete
     let (listener_result, connector_result) = tokio::join!(listener_handle, connector_handle);
@@ -22,6 +27,17 @@ async fn test_tcp_link() {
     let listener_link = listener_result.unwrap().expect("Listener failed");
     let connector_link = connector_result.unwrap().expect("Connector failed");
 
+    // Each end learns who is at the other end from the handshake
+    assert_eq!(
+        listener_link.peer().junction_id,
+        JunctionId::new("connector")
+    );
+    assert_eq!(
+        connector_link.peer().junction_id,
+        JunctionId::new("listener")
+    );
+    assert_eq!(connector_link.peer_addr(), Some(addr));
+
     // Test data transmission from connector to listener
     let test_message = b"Hello from connector!";
     connector_link
@@ -159,3 +175,126 @@ async fn test_tcp_link() {
         );
     }
 }
+
+#[test]
+fn test_link_identity_pack_unpack() {
+    let identity = LinkIdentity::new(JunctionId::new("junction"), 9000);
+    let packed = identity.pack(b"SLOW_HELLO");
+
+    assert_eq!(LinkIdentity::unpack(b"SLOW_HELLO", &packed), Some(identity));
+    assert_eq!(LinkIdentity::unpack(b"SLOW_WELCOME", &packed), None);
+    assert_eq!(LinkIdentity::unpack(b"SLOW_HELLO", &packed[..14]), None);
+    assert_eq!(LinkIdentity::unpack(b"SLOW_HELLO", b"SLOW_HELLO"), None);
+}
+
+#[tokio::test]
+async fn test_tcp_link_send_queue() {
+    let addr = "127.0.0.1:12346".parse::<SocketAddr>().unwrap();
+    let listener_identity = LinkIdentity::new(JunctionId::new("listener"), addr.port());
+    let connector_identity = LinkIdentity::new(JunctionId::new("connector"), 0);
+
+    let listener_handle =
+        tokio::spawn(async move { SlowTcpLink::listen(addr, &listener_identity).await });
+    sleep(Duration::from_millis(100)).await;
+    let connector_link = SlowTcpLink::connect(addr, &connector_identity)
+        .await
+        .expect("Connector failed");
+    let _stalled_link = listener_handle.await.unwrap().expect("Listener failed");
+
+    // The listener never reads, so the queue fills up without a send ever waiting
+    let frame = vec![0x5A; 64 * 1024];
+    let mut queued = 0;
+    let error = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            match connector_link.send(&frame).await {
+                Ok(_) => queued += 1,
+                Err(e) => break e,
+            }
+        }
+    })
+    .await
+    .expect("Sending waited on the stalled peer");
+    assert_eq!(error.kind(), std::io::ErrorKind::WouldBlock);
+    assert!(queued >= SlowTcpLink::max_frame_size() / frame.len());
+    assert!(connector_link.queued_frames() > 0);
+
+    // Closing gives up on the frames the peer never took
+    tokio::time::timeout(Duration::from_secs(3), connector_link.close())
+        .await
+        .expect("Closing waited on the stalled peer")
+        .expect("Failed to close connector link");
+}
+
+#[tokio::test]
+async fn test_tcp_link_large_message() {
+    let addr = "127.0.0.1:12347".parse::<SocketAddr>().unwrap();
+    let listener_identity = LinkIdentity::new(JunctionId::new("listener"), addr.port());
+    let connector_identity = LinkIdentity::new(JunctionId::new("connector"), 0);
+
+    let listener_handle =
+        tokio::spawn(async move { SlowTcpLink::listen(addr, &listener_identity).await });
+    sleep(Duration::from_millis(100)).await;
+    let connector_link = SlowTcpLink::connect(addr, &connector_identity)
+        .await
+        .expect("Connector failed");
+    let listener_link = listener_handle.await.unwrap().expect("Listener failed");
+
+    // A message several frames long arrives whole
+    let max_size = SlowTcpLink::max_frame_size();
+    let message: Vec<u8> = (0..max_size * 3 + max_size / 2)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    connector_link
+        .send_message(&message)
+        .await
+        .expect("Failed to send large message");
+    let received = listener_link
+        .receive_message()
+        .await
+        .expect("Failed to receive large message");
+    assert_eq!(received, message);
+
+    // It can also be received into a buffer large enough for it
+    connector_link
+        .send_message(&message)
+        .await
+        .expect("Failed to send large message");
+    let mut buffer = vec![0u8; message.len()];
+    let size = listener_link
+        .receive(&mut buffer)
+        .await
+        .expect("Failed to receive large message into buffer");
+    assert_eq!(&buffer[..size], message.as_slice());
+
+    // An empty message arrives as an empty message, both ways of receiving
+    connector_link
+        .send_message(&[])
+        .await
+        .expect("Failed to send empty message");
+    let received = listener_link
+        .receive_message()
+        .await
+        .expect("Failed to receive empty message");
+    assert!(received.is_empty());
+    connector_link
+        .send_message(&[])
+        .await
+        .expect("Failed to send empty message");
+    let size = listener_link
+        .receive(&mut buffer)
+        .await
+        .expect("Failed to receive empty message into buffer");
+    assert_eq!(size, 0);
+
+    // A message over the receiver's limit fails the link instead of being buffered
+    listener_link.set_max_message_size(max_size * 2);
+    connector_link
+        .send_message(&message)
+        .await
+        .expect("Failed to send large message");
+    let error = listener_link
+        .receive_message()
+        .await
+        .expect_err("Received a message over the limit");
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,340 @@
+use slow::capability::{Capabilities, ProtocolOffer};
+use slow::junction::JunctionId;
+use slow::package::SlowPackage;
+use slow::tcp::tcp_junction::SlowTcpJunction;
+use slow::tcp::tcp_link::LinkDirection;
+use slow::tcp::tcp_peer::{LinkLimits, PeerEvent, PeerEventKind, ReconnectPolicy};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::time::{self, Duration};
+
+/// Drains the peer events queued on a junction.
+async fn peer_events(junction: &Arc<SlowTcpJunction>) -> Vec<PeerEvent> {
+    let mut events = Vec::new();
+    while let Some(event) = junction.receive_peer_event().await {
+        events.push(event);
+    }
+    events
+}
+
+#[test]
+fn test_reconnect_policy_backoff() {
+    let policy = ReconnectPolicy {
+        initial_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(1000),
+        jitter: 0.0,
+        max_attempts: None,
+    };
+    assert_eq!(policy.delay(1), Duration::from_millis(100));
+    assert_eq!(policy.delay(2), Duration::from_millis(200));
+    assert_eq!(policy.delay(4), Duration::from_millis(800));
+    assert_eq!(policy.delay(5), Duration::from_millis(1000));
+    assert_eq!(policy.delay(100), Duration::from_millis(1000));
+
+    let policy = ReconnectPolicy {
+        jitter: 0.5,
+        ..policy
+    };
+    for _ in 0..100 {
+        let delay = policy.delay(2);
+        assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(300));
+    }
+}
+
+#[test]
+fn test_link_limits() {
+    let limits = LinkLimits::default();
+    assert!(limits.allows(LinkDirection::Inbound, 1000, 1000));
+
+    let limits = LinkLimits {
+        max_inbound: Some(2),
+        max_outbound: Some(1),
+        max_total: Some(3),
+    };
+    assert!(limits.allows(LinkDirection::Inbound, 1, 1));
+    assert!(!limits.allows(LinkDirection::Inbound, 2, 0));
+    assert!(limits.allows(LinkDirection::Outbound, 2, 0));
+    assert!(!limits.allows(LinkDirection::Outbound, 1, 1));
+    assert!(!limits.allows(LinkDirection::Inbound, 1, 2));
+}
+
+/// Tests that a dialed link is redialed after it drops.
+///
+/// This test verifies:
+/// 1. The link comes back after the remote end closes it
+/// 2. The drop and the reconnect are reported as events and counted
+#[tokio::test]
+async fn test_tcp_junction_reconnect() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    let junction1 = SlowTcpJunction::new(any_port, JunctionId::new("junction1"));
+    let junction2 = SlowTcpJunction::new(any_port, JunctionId::new("junction2"));
+    let addr2 = junction2.local_addr();
+
+    junction1
+        .enable_reconnect(ReconnectPolicy {
+            initial_delay: Duration::from_millis(50),
+            ..ReconnectPolicy::default()
+        })
+        .await;
+    junction1
+        .clone()
+        .connect(addr2)
+        .await
+        .expect("Failed to connect junction1 to junction2");
+    time::sleep(Duration::from_millis(100)).await;
+
+    // junction2 drops the link, and junction1 dials it again
+    junction2.close().await.expect("Failed to close junction2");
+    time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(junction1.link_count().await, 1);
+    assert_eq!(junction2.link_count().await, 1);
+
+    let events = peer_events(&junction1).await;
+    let kinds: Vec<_> = events.iter().map(|event| &event.kind).collect();
+    assert!(events.iter().all(|event| event.addr == addr2));
+    assert!(matches!(kinds[0], PeerEventKind::Connected { .. }));
+    assert!(matches!(kinds[1], PeerEventKind::Disconnected { .. }));
+    assert!(matches!(
+        kinds[2],
+        PeerEventKind::Reconnecting { attempt: 1, .. }
+    ));
+    assert!(matches!(kinds[3], PeerEventKind::Connected { .. }));
+
+    let stats = junction1.peer_stats().await;
+    assert_eq!(stats.connects, 2);
+    assert_eq!(stats.disconnects, 1);
+    assert_eq!(stats.reconnect_attempts, 1);
+    assert_eq!(stats.reconnect_failures, 0);
+
+    // Closing the junction itself does not redial
+    junction1.close().await.expect("Failed to close junction1");
+    time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(junction1.link_count().await, 0);
+    junction2.close().await.expect("Failed to close junction2");
+}
+
+/// Tests that redialing stops when the peer turns out to be incompatible.
+#[tokio::test]
+async fn test_tcp_junction_reconnect_gives_up() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    let junction1 = SlowTcpJunction::new(any_port, JunctionId::new("junction1"));
+    let junction2 = SlowTcpJunction::new(any_port, JunctionId::new("junction2"));
+
+    junction1
+        .enable_reconnect(ReconnectPolicy {
+            initial_delay: Duration::from_millis(50),
+            ..ReconnectPolicy::default()
+        })
+        .await;
+    junction1
+        .clone()
+        .connect(junction2.local_addr())
+        .await
+        .expect("Failed to connect junction1 to junction2");
+    time::sleep(Duration::from_millis(100)).await;
+
+    // junction2 now insists on a feature junction1 does not offer
+    junction2
+        .set_protocol_offer(ProtocolOffer {
+            capabilities: Capabilities::ENCRYPTION,
+            required: Capabilities::ENCRYPTION,
+            ..ProtocolOffer::default()
+        })
+        .await;
+    junction2.close().await.expect("Failed to close junction2");
+    time::sleep(Duration::from_millis(500)).await;
+
+    assert_eq!(junction1.link_count().await, 0);
+    let events = peer_events(&junction1).await;
+    assert!(matches!(
+        events.last().map(|event| &event.kind),
+        Some(PeerEventKind::GaveUp { attempts: 1 })
+    ));
+
+    let stats = junction1.peer_stats().await;
+    assert_eq!(stats.reconnect_attempts, 1);
+    assert_eq!(stats.reconnect_failures, 1);
+    assert_eq!(stats.gave_up, 1);
+}
+
+/// Tests that two junctions dialing each other end up with a single link.
+///
+/// This test verifies:
+/// 1. Both junctions keep the same link, the one dialed by the lower ID
+/// 2. Packages still flow over the kept link
+#[tokio::test]
+async fn test_tcp_junction_duplicate_links() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    let junction1 = SlowTcpJunction::new(any_port, JunctionId::new("junction1"));
+    let junction2 = SlowTcpJunction::new(any_port, JunctionId::new("junction2"));
+
+    let (connected1, connected2) = tokio::join!(
+        junction1.clone().connect(junction2.local_addr()),
+        junction2.clone().connect(junction1.local_addr()),
+    );
+    connected1.expect("Failed to connect junction1 to junction2");
+    connected2.expect("Failed to connect junction2 to junction1");
+    time::sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(junction1.link_count().await, 1);
+    assert_eq!(junction2.link_count().await, 1);
+    assert_eq!(
+        junction1.peers().await[0].junction_id,
+        JunctionId::new("junction2")
+    );
+
+    // Dialing again does not add a second link either
+    junction2
+        .clone()
+        .connect(junction1.local_addr())
+        .await
+        .expect("Failed to connect junction2 to junction1 again");
+    time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(junction1.link_count().await, 1);
+    assert_eq!(junction2.link_count().await, 1);
+
+    let package = SlowPackage::new_json_payload(
+        JunctionId::new("junction2"),
+        junction1.junction_id().clone(),
+        &serde_json::json!({ "message": "one link" }),
+    );
+    junction1
+        .send_package(&package)
+        .await
+        .expect("Failed to send package");
+    time::sleep(Duration::from_millis(100)).await;
+    assert!(junction2.receive_package().await.is_some());
+
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}
+
+/// Tests that links past the link limits are turned away.
+///
+/// This test verifies:
+/// 1. An inbound link past the limit is refused at accept time
+/// 2. Dialing past the outbound limit fails without connecting
+#[tokio::test]
+async fn test_tcp_junction_link_limits() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    let hub = SlowTcpJunction::new(any_port, JunctionId::new("hub"));
+    let junction1 = SlowTcpJunction::new(any_port, JunctionId::new("junction1"));
+    let junction2 = SlowTcpJunction::new(any_port, JunctionId::new("junction2"));
+
+    hub.set_link_limits(LinkLimits {
+        max_inbound: Some(1),
+        max_outbound: Some(0),
+        ..LinkLimits::default()
+    })
+    .await;
+
+    junction1
+        .clone()
+        .connect(hub.local_addr())
+        .await
+        .expect("Failed to connect junction1 to hub");
+    time::sleep(Duration::from_millis(100)).await;
+
+    let error = junction2
+        .clone()
+        .connect(hub.local_addr())
+        .await
+        .expect_err("hub accepted a link past its inbound limit");
+    assert_eq!(error.kind(), std::io::ErrorKind::ConnectionRefused);
+
+    let error = hub
+        .clone()
+        .connect(junction2.local_addr())
+        .await
+        .expect_err("hub dialed past its outbound limit");
+    assert_eq!(error.kind(), std::io::ErrorKind::ConnectionRefused);
+
+    time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(hub.link_count().await, 1);
+    assert_eq!(junction2.link_count().await, 0);
+    assert_eq!(hub.rejected_link_count(), 2);
+
+    hub.close().await.expect("Failed to close hub");
+    junction1.close().await.expect("Failed to close junction1");
+}
+
+/// Tests that two links dialed by the same junction are settled the same way at both ends.
+///
+/// This test verifies:
+/// 1. Both junctions keep one link after the same junction dials the other twice at once
+/// 2. Packages still flow both ways over the kept link
+#[tokio::test]
+async fn test_tcp_junction_duplicate_dials() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    let junction1 = SlowTcpJunction::new(any_port, JunctionId::new("junction1"));
+    let junction2 = SlowTcpJunction::new(any_port, JunctionId::new("junction2"));
+
+    let (connected1, connected2) = tokio::join!(
+        junction2.clone().connect(junction1.local_addr()),
+        junction2.clone().connect(junction1.local_addr()),
+    );
+    connected1.expect("Failed to connect junction2 to junction1");
+    connected2.expect("Failed to connect junction2 to junction1");
+    time::sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(junction1.link_count().await, 1);
+    assert_eq!(junction2.link_count().await, 1);
+
+    for (sender, recipient) in [(&junction1, &junction2), (&junction2, &junction1)] {
+        let package = SlowPackage::new_json_payload(
+            recipient.junction_id().clone(),
+            sender.junction_id().clone(),
+            &serde_json::json!({ "message": "one link" }),
+        );
+        sender
+            .send_package(&package)
+            .await
+            .expect("Failed to send package");
+        time::sleep(Duration::from_millis(100)).await;
+        assert!(recipient.receive_package().await.is_some());
+    }
+
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}
+
+/// Tests that the link limits hold when several links arrive at once.
+///
+/// This test verifies:
+/// 1. Only one of three concurrent inbound links is kept under a limit of one
+/// 2. The others are counted as rejected
+#[tokio::test]
+async fn test_tcp_junction_concurrent_link_limits() {
+    let any_port = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+    let hub = SlowTcpJunction::new(any_port, JunctionId::new("hub"));
+    let junction1 = SlowTcpJunction::new(any_port, JunctionId::new("junction1"));
+    let junction2 = SlowTcpJunction::new(any_port, JunctionId::new("junction2"));
+    let junction3 = SlowTcpJunction::new(any_port, JunctionId::new("junction3"));
+
+    hub.set_link_limits(LinkLimits {
+        max_inbound: Some(1),
+        ..LinkLimits::default()
+    })
+    .await;
+
+    let _ = tokio::join!(
+        junction1.clone().connect(hub.local_addr()),
+        junction2.clone().connect(hub.local_addr()),
+        junction3.clone().connect(hub.local_addr()),
+    );
+    time::sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(hub.link_count().await, 1);
+    assert_eq!(hub.rejected_link_count(), 2);
+
+    hub.close().await.expect("Failed to close hub");
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+    junction3.close().await.expect("Failed to close junction3");
+}
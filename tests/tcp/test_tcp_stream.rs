@@ -11,10 +11,7 @@ async fn test_tcp_stream() {
     let server_addr = listener.local_addr().unwrap();
 
     // Spawn accept task
-    let accept_handle = task::spawn(async move {
-        let stream = listener.accept().await.unwrap();
-        stream
-    });
+    let accept_handle = task::spawn(async move { listener.accept().await.unwrap() });
 
     // Connect client
     let stream1 = SlowTcpStream::connect(server_addr).await.unwrap();
@@ -0,0 +1,52 @@
+use slow::junction::JunctionId;
+use slow::package::{PackageType, SlowPackage};
+use slow::traceroute::{TracerouteHop, TracerouteRecord};
+
+#[test]
+fn test_traceroute_record_pack_unpack() {
+    let record = TracerouteRecord {
+        trace_id: 7,
+        hops: vec![
+            TracerouteHop {
+                junction_id: JunctionId::new("a"),
+                timestamp_ms: 1000,
+            },
+            TracerouteHop {
+                junction_id: JunctionId::new("bb"),
+                timestamp_ms: 1005,
+            },
+        ],
+    };
+
+    let packed = record.pack();
+    assert_eq!(TracerouteRecord::unpack(&packed), Some(record));
+
+    // Truncated or padded records are rejected
+    assert!(TracerouteRecord::unpack(&packed[..packed.len() - 1]).is_none());
+    let mut padded = packed.clone();
+    padded.push(0);
+    assert!(TracerouteRecord::unpack(&padded).is_none());
+}
+
+#[test]
+fn test_traceroute_record_hop() {
+    let origin = JunctionId::new("origin");
+    let relay = JunctionId::new("relay");
+    let record = TracerouteRecord::new(1, &origin);
+
+    let mut package =
+        SlowPackage::new_traceroute(JunctionId::new("target"), origin.clone(), &record.pack());
+    assert_eq!(package.package_type(), Ok(PackageType::Traceroute));
+    assert!(TracerouteRecord::record_hop(&mut package, &relay));
+
+    // The package survives a round trip with the extended record
+    let unpacked = SlowPackage::unpack(&package.pack(1)).unwrap();
+    let record = TracerouteRecord::unpack(&unpacked.payload).unwrap();
+    let path: Vec<JunctionId> = record.hops.into_iter().map(|hop| hop.junction_id).collect();
+    assert_eq!(path, vec![origin, relay.clone()]);
+
+    // Packages without a record are left untouched
+    let mut package = SlowPackage::new_ping(JunctionId::new("target"), relay.clone());
+    assert!(!TracerouteRecord::record_hop(&mut package, &relay));
+    assert!(package.payload.is_empty());
+}
@@ -0,0 +1,330 @@
+use slow::junction::{JunctionId, SlowJunction};
+use slow::package::SlowPackage;
+use slow::tcp::tcp_junction::SlowTcpJunction;
+use slow::transfer::{
+    CHUNK_SIZE, CompletedTransfer, TransferError, TransferMessage, TransferPolicy, Transfers,
+    chunk_hash,
+};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::{Mutex, mpsc};
+
+/// Controls what a pump lets through.
+#[derive(Default)]
+struct PumpControl {
+    /// How many more chunks are delivered before the rest are dropped.
+    budget: AtomicUsize,
+
+    /// How many chunks are corrupted before being delivered.
+    corrupt: AtomicUsize,
+
+    /// How many chunks have been delivered.
+    delivered: AtomicUsize,
+}
+
+/// Carries the packages of one `Transfers` to another.
+fn pump(
+    mut packages: mpsc::UnboundedReceiver<SlowPackage>,
+    to: Arc<Mutex<Transfers>>,
+    control: Arc<PumpControl>,
+) {
+    tokio::spawn(async move {
+        while let Some(package) = packages.recv().await {
+            let mut payload = package.payload.clone();
+            if let Some(TransferMessage::Chunk { .. }) = TransferMessage::unpack(&payload) {
+                let budget = control.budget.load(Ordering::SeqCst);
+                if budget == 0 {
+                    continue;
+                }
+                control.budget.store(budget - 1, Ordering::SeqCst);
+                if control.corrupt.load(Ordering::SeqCst) > 0 {
+                    control.corrupt.fetch_sub(1, Ordering::SeqCst);
+                    *payload.last_mut().unwrap() ^= 0xff;
+                }
+                control.delivered.fetch_add(1, Ordering::SeqCst);
+            }
+            to.lock().await.handle(package.sender_id(), &payload);
+        }
+    });
+}
+
+/// Returns `size` bytes of recognisable test data.
+fn payload(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i % 251) as u8).collect()
+}
+
+#[test]
+fn test_transfer_message_pack_unpack() {
+    let messages = vec![
+        TransferMessage::Offer {
+            transfer_id: 7,
+            size: 3000,
+            name: "notes.txt".to_string(),
+        },
+        TransferMessage::Accept {
+            transfer_id: 7,
+            next_chunk: 2,
+        },
+        TransferMessage::Chunk {
+            transfer_id: 7,
+            index: 2,
+            hash: chunk_hash(b"chunk"),
+            data: b"chunk".to_vec(),
+        },
+        TransferMessage::ChunkAck {
+            transfer_id: 7,
+            index: 2,
+            ok: false,
+        },
+        TransferMessage::Refuse { transfer_id: 7 },
+    ];
+
+    for message in messages {
+        assert_eq!(TransferMessage::unpack(&message.pack()), Some(message));
+    }
+
+    assert_eq!(TransferMessage::unpack(&[]), None);
+    assert_eq!(TransferMessage::unpack(&[1, 0, 0]), None);
+    assert_ne!(chunk_hash(b"chunk"), chunk_hash(b"chunl"));
+}
+
+#[tokio::test]
+async fn test_transfers_resume_and_integrity() {
+    let sender_id = JunctionId::new("sender");
+    let receiver_id = JunctionId::new("receiver");
+
+    let (sender_outgoing, sender_packages) = mpsc::unbounded_channel();
+    let (receiver_outgoing, receiver_packages) = mpsc::unbounded_channel();
+    let sender = Arc::new(Mutex::new(Transfers::new(
+        sender_id.clone(),
+        sender_outgoing,
+    )));
+    let receiver = Arc::new(Mutex::new(Transfers::new(
+        receiver_id.clone(),
+        receiver_outgoing,
+    )));
+    sender.lock().await.set_policy(TransferPolicy {
+        timeout: Duration::from_millis(50),
+        retries: 2,
+        window: 4,
+        ..TransferPolicy::default()
+    });
+
+    // Only the first ten chunks get through, and the first one arrives corrupted
+    let control = Arc::new(PumpControl::default());
+    control.budget.store(10, Ordering::SeqCst);
+    control.corrupt.store(1, Ordering::SeqCst);
+    pump(sender_packages, receiver.clone(), control.clone());
+    pump(
+        receiver_packages,
+        sender.clone(),
+        Arc::new(PumpControl::default()),
+    );
+
+    let data = payload(CHUNK_SIZE * 20 + 100);
+    let handle = sender
+        .lock()
+        .await
+        .start(&receiver_id, "data.bin", data.clone());
+    let result = tokio::time::timeout(Duration::from_secs(5), handle.wait())
+        .await
+        .expect("Transfer did not give up");
+    assert_eq!(result, Err(TransferError::TimedOut));
+    assert!(receiver.lock().await.pop_completed().is_none());
+
+    // The corrupted chunk was resent, so nine good chunks made it across
+    control.delivered.store(0, Ordering::SeqCst);
+    control.budget.store(usize::MAX, Ordering::SeqCst);
+    let handle = sender
+        .lock()
+        .await
+        .start(&receiver_id, "data.bin", data.clone());
+    let result = tokio::time::timeout(Duration::from_secs(5), handle.wait())
+        .await
+        .expect("Transfer did not finish");
+    assert_eq!(result, Ok(()));
+    assert_eq!(control.delivered.load(Ordering::SeqCst), 21 - 9);
+
+    let completed = receiver.lock().await.pop_completed();
+    assert_eq!(
+        completed,
+        Some(CompletedTransfer {
+            sender_id,
+            name: "data.bin".to_string(),
+            data,
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_transfers_limits() {
+    let sender_id = JunctionId::new("sender");
+    let (outgoing, mut replies) = mpsc::unbounded_channel();
+    let mut receiver = Transfers::new(JunctionId::new("receiver"), outgoing);
+    receiver.set_policy(TransferPolicy {
+        max_transfer_size: 10 * CHUNK_SIZE as u64,
+        max_incoming: 2,
+        ..TransferPolicy::default()
+    });
+
+    let mut offer = |receiver: &mut Transfers, transfer_id: u64, size: u64| {
+        let message = TransferMessage::Offer {
+            transfer_id,
+            size,
+            name: "data.bin".to_string(),
+        };
+        receiver.handle(&sender_id, &message.pack());
+        TransferMessage::unpack(&replies.try_recv().unwrap().payload).unwrap()
+    };
+
+    // Offers above the limit, or with more chunks than fit in a u32, are refused
+    for size in [10 * CHUNK_SIZE as u64 + 1, u64::MAX] {
+        assert_eq!(
+            offer(&mut receiver, 1, size),
+            TransferMessage::Refuse { transfer_id: 1 }
+        );
+    }
+
+    // Only the two most recently active transfers are kept
+    for transfer_id in [2, 3, 4] {
+        let reply = offer(&mut receiver, transfer_id, CHUNK_SIZE as u64);
+        assert_eq!(
+            reply,
+            TransferMessage::Accept {
+                transfer_id,
+                next_chunk: 0
+            }
+        );
+    }
+    let data = payload(CHUNK_SIZE);
+    for transfer_id in [2, 3, 4] {
+        let chunk = TransferMessage::Chunk {
+            transfer_id,
+            index: 0,
+            hash: chunk_hash(&data),
+            data: data.clone(),
+        };
+        receiver.handle(&sender_id, &chunk.pack());
+    }
+    let mut acked = Vec::new();
+    while let Ok(package) = replies.try_recv() {
+        acked.push(
+            TransferMessage::unpack(&package.payload)
+                .unwrap()
+                .transfer_id(),
+        );
+    }
+    assert_eq!(acked, vec![3, 4]);
+}
+
+#[tokio::test]
+async fn test_transfers_refused() {
+    let receiver_id = JunctionId::new("receiver");
+    let (sender_outgoing, sender_packages) = mpsc::unbounded_channel();
+    let (receiver_outgoing, receiver_packages) = mpsc::unbounded_channel();
+    let sender = Arc::new(Mutex::new(Transfers::new(
+        JunctionId::new("sender"),
+        sender_outgoing,
+    )));
+    let receiver = Arc::new(Mutex::new(Transfers::new(
+        receiver_id.clone(),
+        receiver_outgoing,
+    )));
+    receiver.lock().await.set_policy(TransferPolicy {
+        max_transfer_size: CHUNK_SIZE as u64,
+        ..TransferPolicy::default()
+    });
+
+    let control = Arc::new(PumpControl::default());
+    control.budget.store(usize::MAX, Ordering::SeqCst);
+    pump(sender_packages, receiver.clone(), control.clone());
+    pump(receiver_packages, sender.clone(), control);
+
+    let handle = sender
+        .lock()
+        .await
+        .start(&receiver_id, "data.bin", payload(CHUNK_SIZE + 1));
+    let result = tokio::time::timeout(Duration::from_secs(5), handle.wait())
+        .await
+        .expect("Transfer was not refused");
+    assert_eq!(result, Err(TransferError::Refused));
+}
+
+#[tokio::test]
+async fn test_junction_transfer() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1122);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2232);
+
+    let junction_id1 = JunctionId::new("1");
+    let junction_id2 = JunctionId::new("2");
+
+    let junction1 = SlowJunction::new(addr1, junction_id1.clone())
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let path = std::env::temp_dir().join("slow_transfer_test.bin");
+    let data = payload(30_000);
+    std::fs::write(&path, &data).unwrap();
+
+    let handle = junction1
+        .send_file(&junction_id2, &path)
+        .await
+        .expect("Failed to read file");
+    let mut progress = handle.watch_progress();
+    let result = tokio::time::timeout(Duration::from_secs(10), handle.wait())
+        .await
+        .expect("Transfer did not finish");
+    assert_eq!(result, Ok(()));
+    std::fs::remove_file(&path).unwrap();
+
+    let progress = *progress.borrow_and_update();
+    assert!(progress.is_complete());
+    assert_eq!(progress.bytes_done, data.len() as u64);
+
+    let completed = junction2.receive_transfer().await.unwrap();
+    assert_eq!(completed.sender_id, junction_id1);
+    assert_eq!(completed.name, "slow_transfer_test.bin");
+    assert_eq!(completed.data, data);
+}
+
+#[tokio::test]
+async fn test_tcp_junction_transfer() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9709);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9710);
+
+    let junction_id1 = JunctionId::new("junction1");
+    let junction_id2 = JunctionId::new("junction2");
+
+    let junction1 = SlowTcpJunction::new(addr1, junction_id1.clone());
+    let junction2 = SlowTcpJunction::new(addr2, junction_id2.clone());
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    junction1
+        .clone()
+        .connect(addr2)
+        .await
+        .expect("Failed to connect junction1 to junction2");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let data = payload(100_000);
+    let handle = junction1
+        .send_transfer(&junction_id2, "bulk", data.clone())
+        .await;
+    let result = tokio::time::timeout(Duration::from_secs(10), handle.wait())
+        .await
+        .expect("Transfer did not finish");
+    assert_eq!(result, Ok(()));
+
+    let completed = junction2.receive_transfer().await.unwrap();
+    assert_eq!(completed.sender_id, junction_id1);
+    assert_eq!(completed.name, "bulk");
+    assert_eq!(completed.data, data);
+}
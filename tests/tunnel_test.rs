@@ -0,0 +1,114 @@
+use slow::junction::{JunctionId, SlowJunction};
+use slow::tcp::tcp_junction::SlowTcpJunction;
+use slow::tunnel::{self, TunnelTargets};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Starts a TCP server that echoes everything back, returning its address.
+async fn start_echo_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut connection, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let (mut reader, mut writer) = connection.split();
+                let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            });
+        }
+    });
+    addr
+}
+
+/// Sends a message through a forwarded port and checks that it is echoed back.
+async fn assert_echo(forward_addr: SocketAddr, message: &[u8]) {
+    let mut connection = TcpStream::connect(forward_addr).await.unwrap();
+    connection.write_all(message).await.unwrap();
+    connection.shutdown().await.unwrap();
+
+    let mut echoed = Vec::new();
+    tokio::time::timeout(Duration::from_secs(10), connection.read_to_end(&mut echoed))
+        .await
+        .expect("Tunnel did not close")
+        .unwrap();
+    assert_eq!(echoed, message);
+}
+
+#[test]
+fn test_tunnel_targets() {
+    let target = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8080);
+    let mut targets = TunnelTargets::new();
+    targets.add("web", target);
+    assert_eq!(targets.get("web"), Some(target));
+    assert!(targets.remove("web"));
+    assert_eq!(targets.get("web"), None);
+
+    let service = tunnel::service_name("web");
+    assert_eq!(tunnel::tunnel_name(&service), Some("web"));
+    assert_eq!(tunnel::tunnel_name("web"), None);
+}
+
+#[tokio::test]
+async fn test_junction_forward_port() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1121);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2231);
+
+    let junction_id2 = JunctionId::new("2");
+
+    let junction1 = SlowJunction::new(addr1, JunctionId::new("1"))
+        .await
+        .expect("Failed to create junction1");
+    let junction2 = SlowJunction::new(addr2, junction_id2.clone())
+        .await
+        .expect("Failed to create junction2");
+
+    junction1.join(addr2).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let echo_addr = start_echo_server().await;
+    junction2.add_tunnel("echo", echo_addr).await;
+
+    let listen_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+    let forward_addr = junction1
+        .forward_port(listen_addr, &junction_id2, "echo")
+        .await
+        .expect("Failed to forward port");
+
+    // Each connection gets its own stream through the tunnel
+    assert_echo(forward_addr, b"hello through the mesh").await;
+    assert_echo(forward_addr, &vec![7u8; 10_000]).await;
+}
+
+#[tokio::test]
+async fn test_tcp_junction_forward_port() {
+    let addr1 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9707);
+    let addr2 = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9708);
+
+    let junction_id2 = JunctionId::new("junction2");
+
+    let junction1 = SlowTcpJunction::new(addr1, JunctionId::new("junction1"));
+    let junction2 = SlowTcpJunction::new(addr2, junction_id2.clone());
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    junction1
+        .clone()
+        .connect(addr2)
+        .await
+        .expect("Failed to connect junction1 to junction2");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let echo_addr = start_echo_server().await;
+    junction2.add_tunnel("echo", echo_addr).await;
+
+    let listen_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+    let forward_addr = junction1
+        .forward_port(listen_addr, &junction_id2, "echo")
+        .await
+        .expect("Failed to forward port");
+
+    assert_echo(forward_addr, b"hello through the mesh").await;
+
+    junction1.close().await.expect("Failed to close junction1");
+    junction2.close().await.expect("Failed to close junction2");
+}